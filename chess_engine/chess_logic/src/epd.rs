@@ -0,0 +1,134 @@
+use crate::GAME;
+use crate::movegen::{self, Move};
+use crate::notation::move_from_san;
+
+/// One line of an EPD file: the position and its operations, each an opcode with its operands.
+#[derive(Clone)]
+pub struct EpdRecord {
+    pub game: GAME,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl EpdRecord {
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(name, _)| name == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id").and_then(|operands| operands.first()).map(String::as_str)
+    }
+
+    /// The moves of the `bm` operation, in SAN or UCI notation. A move that is not legal here is an error,
+    /// so that a record is never tested against fewer moves than it lists.
+    ///```
+    /// use chess_logic::*;
+    /// let record = parse_epd("6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8 Qd8; am Kf1 a1b1").unwrap();
+    /// assert_eq!(record.best_moves(), Err("bm Qd8 is not a legal move".to_string()));
+    /// assert_eq!(record.avoid_moves().unwrap().len(), 2);
+    /// assert_eq!(parse_epd("6k1/5ppp/8/8/8/8/8/R5K1 w - -").unwrap().best_moves(), Ok(vec![]));
+    ///```
+    pub fn best_moves(&self) -> Result<Vec<Move>, String> {
+        self.moves("bm")
+    }
+
+    /// The moves of the `am` operation, which a solution must avoid.
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, String> {
+        self.moves("am")
+    }
+
+    /// The `dm` operation: the side to move mates in this many moves.
+    pub fn mate_in(&self) -> Option<u32> {
+        self.operation("dm").and_then(|operands| operands.first()).and_then(|moves| moves.parse().ok())
+    }
+
+    fn moves(&self, opcode: &str) -> Result<Vec<Move>, String> {
+        self.operation(opcode).unwrap_or(&[]).iter()
+            .map(|notation| move_from_san(&self.game, notation).or_else(|| movegen::move_from_uci(&self.game, notation))
+                .ok_or_else(|| format!("{} {} is not a legal move", opcode, notation)))
+            .collect()
+    }
+}
+
+/// Reads an EPD line: four position fields, then operations separated by semicolons.
+/// Operands may be quoted strings, which keep their spaces.
+/// # Reading a test position
+///```
+/// use chess_logic::*;
+/// let record = parse_epd(r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";"#).unwrap();
+/// assert_eq!(record.id(), Some("WAC.001"));
+/// assert_eq!(record.best_moves().unwrap().iter().map(|best| best.to_uci()).collect::<Vec<_>>(), ["g3g6"]);
+/// assert!(record.avoid_moves().unwrap().is_empty());
+///
+/// let record = parse_epd("6k1/5ppp/8/8/8/8/8/R5K1 w - - dm 1; am Ra2 Kf1; c0 \"back rank\"").unwrap();
+/// assert_eq!(record.mate_in(), Some(1));
+/// assert_eq!(record.avoid_moves().unwrap().len(), 2);
+/// assert_eq!(record.operation("c0").unwrap(), ["back rank"]);
+/// assert!(parse_epd("8/8/8 w - -").is_err());
+///```
+pub fn parse_epd(line: &str) -> Result<EpdRecord, String> {
+    let line = line.trim();
+    let mut fields = line.splitn(5, char::is_whitespace);
+    let position: Vec<&str> = fields.by_ref().take(4).collect();
+    if position.len() < 4 {
+        return Err(format!("expected four position fields in '{}'", line));
+    }
+    let mut fen = position.join(" ");
+    let rest = fields.next().unwrap_or("");
+    let operations = parse_operations(rest)?;
+
+    // The halfmove clock and fullmove number may be given as operations
+    let counter = |opcode: &str, default: &str| {
+        operations.iter().find(|(name, _)| name == opcode).and_then(|(_, operands)| operands.first().cloned()).unwrap_or_else(|| default.to_string())
+    };
+    fen += &format!(" {} {}", counter("hmvc", "0"), counter("fmvn", "1"));
+    let game = crate::init_game_from_fen(&fen)?;
+    Ok(EpdRecord { game, operations })
+}
+
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut operations = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut chars = text.chars();
+    while let Some(symbol) = chars.next() {
+        match symbol {
+            '"' => {
+                let mut closed = false;
+                for inside in chars.by_ref() {
+                    if inside == '"' {
+                        closed = true;
+                        break;
+                    }
+                    word.push(inside);
+                }
+                if !closed {
+                    return Err(format!("unterminated string in '{}'", text));
+                }
+                words.push(std::mem::take(&mut word));
+            }
+            ';' => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                if !words.is_empty() {
+                    let opcode = words.remove(0);
+                    operations.push((opcode, std::mem::take(&mut words)));
+                }
+            }
+            symbol if symbol.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            symbol => word.push(symbol),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        let opcode = words.remove(0);
+        operations.push((opcode, words));
+    }
+    Ok(operations)
+}
@@ -0,0 +1,632 @@
+use crate::COLORS;
+use crate::TYPES;
+use crate::GAME;
+use crate::{piece_color_index, piece_type_index};
+use crate::movegen::{KNIGHT_JUMPS, SLIDING_OFFSETS};
+
+// Indexed by piece_type_index: pawn, knight, bishop, rook, queen, king
+pub const MIDDLEGAME_VALUES: [i32; 6] = [82, 337, 365, 477, 1025, 0];
+pub const ENDGAME_VALUES: [i32; 6] = [94, 281, 297, 512, 936, 0];
+pub const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+pub const TOTAL_PHASE: i32 = 24;
+
+// The terms below were measured in self-play against the same engine with them weighing
+// nothing: 258 games at 2+0.02 from the openings in match_runner/openings.pgn, +135 -61 =62,
+// +102 +/- 38 Elo, SPRT elo0=0 elo1=10 accepted at llr 2.99.
+// Mobility is counted from a typical number of reachable squares, per piece type
+const MOBILITY_BASE: [i32; 6] = [0, 4, 6, 7, 13, 0];
+const MOBILITY_MIDDLEGAME: [i32; 6] = [0, 4, 4, 2, 1, 0];
+const MOBILITY_ENDGAME: [i32; 6] = [0, 4, 4, 4, 2, 0];
+const DOUBLED_PAWN: [i32; 2] = [-10, -20];                      // middlegame, endgame
+const ISOLATED_PAWN: [i32; 2] = [-10, -15];
+// Indexed by rank seen from the pawn's side, the second rank first at index 1
+const PASSED_PAWN_MIDDLEGAME: [i32; 8] = [0, 5, 5, 10, 20, 35, 60, 0];
+const PASSED_PAWN_ENDGAME: [i32; 8] = [0, 10, 15, 25, 45, 75, 120, 0];
+const PAWN_SHIELD: [i32; 2] = [12, 6];                          // middlegame only, per pawn one and two squares in front of the king
+const KING_ZONE_ATTACK: i32 = -6;                               // middlegame, per enemy attack on the squares around the king
+const TEMPO: [i32; 2] = [10, 5];
+
+// Piece-square tables are seen from white's side with a8 first, the same layout as GAME.board
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME_TABLE: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+/// Every weight of the evaluation that can be tuned, in centipawns. The defaults are the
+/// constants the engine plays with; tools such as the tuner read and write them as named groups.
+/// # Changing a weight
+///```
+/// use chess_logic::*;
+/// let game = init_game();
+/// assert_eq!(evaluate_trace_with(&game, &EvalParams::default()), evaluate_trace(&game));
+///
+/// let mut params = EvalParams::default();
+/// let mut values = params.values();
+/// assert_eq!(values.len(), params.groups().iter().map(|(_, group)| group.len()).sum::<usize>());
+/// values[params.index_of("TEMPO", 0).unwrap()] = 30;
+/// params.set_values(&values);
+/// assert_eq!(params.tempo, [30, 5]);
+/// assert_eq!(evaluate_trace_with(&game, &params).score, 30);
+///```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalParams {
+    pub middlegame_values: [i32; 6],
+    pub endgame_values: [i32; 6],
+    pub piece_tables: [[i32; 64]; 5],                           // pawn to queen, shared by both phases
+    pub king_middlegame_table: [i32; 64],
+    pub king_endgame_table: [i32; 64],
+    pub mobility_middlegame: [i32; 6],
+    pub mobility_endgame: [i32; 6],
+    pub doubled_pawn: [i32; 2],
+    pub isolated_pawn: [i32; 2],
+    pub passed_pawn_middlegame: [i32; 8],
+    pub passed_pawn_endgame: [i32; 8],
+    pub pawn_shield: [i32; 2],
+    pub king_zone_attack: [i32; 1],
+    pub tempo: [i32; 2],
+}
+
+pub const DEFAULT_EVAL_PARAMS: EvalParams = EvalParams {
+    middlegame_values: MIDDLEGAME_VALUES,
+    endgame_values: ENDGAME_VALUES,
+    piece_tables: [PAWN_TABLE, KNIGHT_TABLE, BISHOP_TABLE, ROOK_TABLE, QUEEN_TABLE],
+    king_middlegame_table: KING_MIDDLEGAME_TABLE,
+    king_endgame_table: KING_ENDGAME_TABLE,
+    mobility_middlegame: MOBILITY_MIDDLEGAME,
+    mobility_endgame: MOBILITY_ENDGAME,
+    doubled_pawn: DOUBLED_PAWN,
+    isolated_pawn: ISOLATED_PAWN,
+    passed_pawn_middlegame: PASSED_PAWN_MIDDLEGAME,
+    passed_pawn_endgame: PASSED_PAWN_ENDGAME,
+    pawn_shield: PAWN_SHIELD,
+    king_zone_attack: [KING_ZONE_ATTACK],
+    tempo: TEMPO,
+};
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        DEFAULT_EVAL_PARAMS
+    }
+}
+
+impl EvalParams {
+    /// The weights grouped under the names of the constants they default to.
+    pub fn groups(&self) -> Vec<(&'static str, &[i32])> {
+        vec![
+            ("MIDDLEGAME_VALUES", &self.middlegame_values[..]),
+            ("ENDGAME_VALUES", &self.endgame_values[..]),
+            ("PAWN_TABLE", &self.piece_tables[0][..]),
+            ("KNIGHT_TABLE", &self.piece_tables[1][..]),
+            ("BISHOP_TABLE", &self.piece_tables[2][..]),
+            ("ROOK_TABLE", &self.piece_tables[3][..]),
+            ("QUEEN_TABLE", &self.piece_tables[4][..]),
+            ("KING_MIDDLEGAME_TABLE", &self.king_middlegame_table[..]),
+            ("KING_ENDGAME_TABLE", &self.king_endgame_table[..]),
+            ("MOBILITY_MIDDLEGAME", &self.mobility_middlegame[..]),
+            ("MOBILITY_ENDGAME", &self.mobility_endgame[..]),
+            ("DOUBLED_PAWN", &self.doubled_pawn[..]),
+            ("ISOLATED_PAWN", &self.isolated_pawn[..]),
+            ("PASSED_PAWN_MIDDLEGAME", &self.passed_pawn_middlegame[..]),
+            ("PASSED_PAWN_ENDGAME", &self.passed_pawn_endgame[..]),
+            ("PAWN_SHIELD", &self.pawn_shield[..]),
+            ("KING_ZONE_ATTACK", &self.king_zone_attack[..]),
+            ("TEMPO", &self.tempo[..]),
+        ]
+    }
+
+    pub fn groups_mut(&mut self) -> Vec<(&'static str, &mut [i32])> {
+        let [pawn, knight, bishop, rook, queen] = &mut self.piece_tables;
+        vec![
+            ("MIDDLEGAME_VALUES", &mut self.middlegame_values[..]),
+            ("ENDGAME_VALUES", &mut self.endgame_values[..]),
+            ("PAWN_TABLE", &mut pawn[..]),
+            ("KNIGHT_TABLE", &mut knight[..]),
+            ("BISHOP_TABLE", &mut bishop[..]),
+            ("ROOK_TABLE", &mut rook[..]),
+            ("QUEEN_TABLE", &mut queen[..]),
+            ("KING_MIDDLEGAME_TABLE", &mut self.king_middlegame_table[..]),
+            ("KING_ENDGAME_TABLE", &mut self.king_endgame_table[..]),
+            ("MOBILITY_MIDDLEGAME", &mut self.mobility_middlegame[..]),
+            ("MOBILITY_ENDGAME", &mut self.mobility_endgame[..]),
+            ("DOUBLED_PAWN", &mut self.doubled_pawn[..]),
+            ("ISOLATED_PAWN", &mut self.isolated_pawn[..]),
+            ("PASSED_PAWN_MIDDLEGAME", &mut self.passed_pawn_middlegame[..]),
+            ("PASSED_PAWN_ENDGAME", &mut self.passed_pawn_endgame[..]),
+            ("PAWN_SHIELD", &mut self.pawn_shield[..]),
+            ("KING_ZONE_ATTACK", &mut self.king_zone_attack[..]),
+            ("TEMPO", &mut self.tempo[..]),
+        ]
+    }
+
+    /// All weights in one vector, group after group.
+    pub fn values(&self) -> Vec<i32> {
+        self.groups().iter().flat_map(|(_, group)| group.iter().copied()).collect()
+    }
+
+    /// The inverse of `values`; extra values are ignored and missing ones leave the weights as they are.
+    pub fn set_values(&mut self, values: &[i32]) {
+        let mut values = values.iter();
+        for (_, group) in self.groups_mut() {
+            for (weight, value) in group.iter_mut().zip(values.by_ref()) {
+                *weight = *value;
+            }
+        }
+    }
+
+    /// Where a weight of a group sits in `values`.
+    pub fn index_of(&self, name: &str, index: usize) -> Option<usize> {
+        let mut offset = 0;
+        for (group_name, group) in self.groups() {
+            if group_name == name {
+                return if index < group.len() { Some(offset + index) } else { None };
+            }
+            offset += group.len();
+        }
+        None
+    }
+
+}
+
+/// Where the evaluation reads its weights: the engine plays with the constants above, compiled
+/// in, and only the tuner goes through an `EvalParams` it changes at runtime. Every weight is a
+/// middlegame and an endgame value unless it says otherwise.
+trait EvalWeights {
+    fn material(&self, type_index: usize) -> (i32, i32);
+    fn piece_square(&self, piece: u8, tile: usize) -> (i32, i32);
+    fn mobility(&self, type_index: usize) -> (i32, i32);
+    fn doubled_pawn(&self) -> (i32, i32);
+    fn isolated_pawn(&self) -> (i32, i32);
+    fn passed_pawn(&self, relative_rank: usize) -> (i32, i32);
+    fn pawn_shield(&self, distance: usize) -> i32;              // middlegame only, 1 or 2 squares in front of the king
+    fn king_zone_attack(&self) -> i32;                          // middlegame only
+    fn tempo(&self) -> (i32, i32);
+}
+
+// The compile-time constants the engine evaluates with
+struct EngineWeights;
+
+impl EvalWeights for EngineWeights {
+    fn material(&self, type_index: usize) -> (i32, i32) {
+        (MIDDLEGAME_VALUES[type_index], ENDGAME_VALUES[type_index])
+    }
+
+    fn piece_square(&self, piece: u8, tile: usize) -> (i32, i32) {
+        let table_tile = if piece & COLORS::WHITE > 0 { tile } else { tile ^ 56 };
+        if piece & TYPES::PAWN > 0 {
+            (PAWN_TABLE[table_tile], PAWN_TABLE[table_tile])
+        } else if piece & TYPES::KNIGHT > 0 {
+            (KNIGHT_TABLE[table_tile], KNIGHT_TABLE[table_tile])
+        } else if piece & TYPES::BISHOP > 0 {
+            (BISHOP_TABLE[table_tile], BISHOP_TABLE[table_tile])
+        } else if piece & TYPES::ROOK > 0 {
+            (ROOK_TABLE[table_tile], ROOK_TABLE[table_tile])
+        } else if piece & TYPES::QUEEN > 0 {
+            (QUEEN_TABLE[table_tile], QUEEN_TABLE[table_tile])
+        } else {
+            (KING_MIDDLEGAME_TABLE[table_tile], KING_ENDGAME_TABLE[table_tile])
+        }
+    }
+
+    fn mobility(&self, type_index: usize) -> (i32, i32) {
+        (MOBILITY_MIDDLEGAME[type_index], MOBILITY_ENDGAME[type_index])
+    }
+
+    fn doubled_pawn(&self) -> (i32, i32) {
+        (DOUBLED_PAWN[0], DOUBLED_PAWN[1])
+    }
+
+    fn isolated_pawn(&self) -> (i32, i32) {
+        (ISOLATED_PAWN[0], ISOLATED_PAWN[1])
+    }
+
+    fn passed_pawn(&self, relative_rank: usize) -> (i32, i32) {
+        (PASSED_PAWN_MIDDLEGAME[relative_rank], PASSED_PAWN_ENDGAME[relative_rank])
+    }
+
+    fn pawn_shield(&self, distance: usize) -> i32 {
+        PAWN_SHIELD[distance - 1]
+    }
+
+    fn king_zone_attack(&self) -> i32 {
+        KING_ZONE_ATTACK
+    }
+
+    fn tempo(&self) -> (i32, i32) {
+        (TEMPO[0], TEMPO[1])
+    }
+}
+
+impl EvalWeights for EvalParams {
+    fn material(&self, type_index: usize) -> (i32, i32) {
+        (self.middlegame_values[type_index], self.endgame_values[type_index])
+    }
+
+    fn piece_square(&self, piece: u8, tile: usize) -> (i32, i32) {
+        let table_tile = if piece & COLORS::WHITE > 0 { tile } else { tile ^ 56 };
+        if piece & TYPES::KING > 0 {
+            (self.king_middlegame_table[table_tile], self.king_endgame_table[table_tile])
+        } else {
+            let value = self.piece_tables[piece_type_index(piece)][table_tile];
+            (value, value)
+        }
+    }
+
+    fn mobility(&self, type_index: usize) -> (i32, i32) {
+        (self.mobility_middlegame[type_index], self.mobility_endgame[type_index])
+    }
+
+    fn doubled_pawn(&self) -> (i32, i32) {
+        (self.doubled_pawn[0], self.doubled_pawn[1])
+    }
+
+    fn isolated_pawn(&self) -> (i32, i32) {
+        (self.isolated_pawn[0], self.isolated_pawn[1])
+    }
+
+    fn passed_pawn(&self, relative_rank: usize) -> (i32, i32) {
+        (self.passed_pawn_middlegame[relative_rank], self.passed_pawn_endgame[relative_rank])
+    }
+
+    fn pawn_shield(&self, distance: usize) -> i32 {
+        self.pawn_shield[distance - 1]
+    }
+
+    fn king_zone_attack(&self) -> i32 {
+        self.king_zone_attack[0]
+    }
+
+    fn tempo(&self) -> (i32, i32) {
+        (self.tempo[0], self.tempo[1])
+    }
+}
+
+/// One evaluation term for white and black, middlegame and endgame, in centipawns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TermScore {
+    pub middlegame: [i32; 2],                                   // white, black
+    pub endgame: [i32; 2],
+}
+
+impl TermScore {
+    fn add(&mut self, color_index: usize, middlegame: i32, endgame: i32) {
+        self.middlegame[color_index] += middlegame;
+        self.endgame[color_index] += endgame;
+    }
+
+    /// White's share minus black's, blended between middlegame and endgame by the phase.
+    pub fn tapered(&self, phase: i32) -> i32 {
+        taper(self.middlegame[0] - self.middlegame[1], self.endgame[0] - self.endgame[1], phase)
+    }
+}
+
+fn taper(middlegame: i32, endgame: i32, phase: i32) -> i32 {
+    (middlegame * phase + endgame * (TOTAL_PHASE - phase)) / TOTAL_PHASE
+}
+
+/// Every term of the static evaluation, and the score they add up to from white's side.
+/// The phase runs from 24 with all pieces on the board down to 0 with pawns and kings only.
+/// # Looking inside the evaluation
+///```
+/// use chess_logic::*;
+/// let trace = evaluate_trace(&init_game());
+/// assert_eq!(trace.phase, 24);
+/// assert_eq!(trace.material.middlegame[0], trace.material.middlegame[1]);
+/// assert_eq!(trace.tempo.middlegame, [10, 0]);
+/// assert_eq!(trace.score, 10);
+/// assert_eq!(evaluate(&init_game()), trace.score);
+///
+/// // A passed pawn on the seventh, black to move
+/// let game = init_game_from_fen("6k1/1P3ppp/8/8/8/8/5PPP/6K1 b - - 0 1").unwrap();
+/// assert_eq!(evaluate(&game), -evaluate_trace(&game).score);
+/// let mut params = EvalParams::default();
+/// params.passed_pawn_endgame[6] = 200;
+/// let trace = evaluate_trace_with(&game, &params);
+/// assert_eq!(trace.phase, 0);
+/// assert_eq!(trace.passed_pawns.endgame, [200, 0]);
+/// assert_eq!(trace.score, evaluate_trace(&game).score + 80);
+///```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvalTrace {
+    pub material: TermScore,
+    pub piece_squares: TermScore,
+    pub mobility: TermScore,
+    pub pawns: TermScore,
+    pub king_safety: TermScore,
+    pub passed_pawns: TermScore,
+    pub tempo: TermScore,
+    pub phase: i32,
+    pub score: i32,
+}
+
+impl EvalTrace {
+    /// The terms with their names, in the order they are computed.
+    pub fn terms(&self) -> [(&'static str, TermScore); 7] {
+        [
+            ("material", self.material),
+            ("piece squares", self.piece_squares),
+            ("mobility", self.mobility),
+            ("pawns", self.pawns),
+            ("king safety", self.king_safety),
+            ("passed pawns", self.passed_pawns),
+            ("tempo", self.tempo),
+        ]
+    }
+}
+
+pub fn evaluate_trace(game: &GAME) -> EvalTrace {
+    trace_with_weights(game, &EngineWeights)
+}
+
+/// The evaluation trace with other weights than the engine's own.
+pub fn evaluate_trace_with(game: &GAME, params: &EvalParams) -> EvalTrace {
+    trace_with_weights(game, params)
+}
+
+fn trace_with_weights<W: EvalWeights>(game: &GAME, weights: &W) -> EvalTrace {
+    let mut trace = EvalTrace::default();
+    trace.phase = walk_terms(game, weights, &mut trace);
+    let (mut middlegame, mut endgame) = (0, 0);
+    for (_, term) in trace.terms().iter() {
+        middlegame += term.middlegame[0] - term.middlegame[1];
+        endgame += term.endgame[0] - term.endgame[1];
+    }
+    trace.score = taper(middlegame, endgame, trace.phase);
+    trace
+}
+
+#[derive(Clone, Copy)]
+enum Term {
+    Material,
+    PieceSquares,
+    Mobility,
+    Pawns,
+    KingSafety,
+    PassedPawns,
+    Tempo,
+}
+
+// Where walk_terms puts what it scores: the engine only keeps the sum in one TermScore,
+// the trace keeps every term apart
+trait TermSink {
+    fn add(&mut self, term: Term, color_index: usize, middlegame: i32, endgame: i32);
+}
+
+impl TermSink for TermScore {
+    #[inline]
+    fn add(&mut self, _term: Term, color_index: usize, middlegame: i32, endgame: i32) {
+        TermScore::add(self, color_index, middlegame, endgame);
+    }
+}
+
+impl TermSink for EvalTrace {
+    fn add(&mut self, term: Term, color_index: usize, middlegame: i32, endgame: i32) {
+        let score = match term {
+            Term::Material => &mut self.material,
+            Term::PieceSquares => &mut self.piece_squares,
+            Term::Mobility => &mut self.mobility,
+            Term::Pawns => &mut self.pawns,
+            Term::KingSafety => &mut self.king_safety,
+            Term::PassedPawns => &mut self.passed_pawns,
+            Term::Tempo => &mut self.tempo,
+        };
+        score.add(color_index, middlegame, endgame);
+    }
+}
+
+// Scores every term of the evaluation into the sink and gives the game phase
+fn walk_terms<W: EvalWeights, S: TermSink>(game: &GAME, weights: &W, sink: &mut S) -> i32 {
+    let mut pawns = [0u64; 2];                                  // pawn tiles as bit sets, by color
+    let mut king_zone_attacks = [0i32; 2];                      // attacks on the squares around each king
+    let king_zones = [king_zone(game.king_tiles[0] as usize), king_zone(game.king_tiles[1] as usize)];
+    let mut phase = 0;
+
+    for (tile, piece) in game.board.iter().enumerate() {
+        if *piece == TYPES::NONE {
+            continue;
+        }
+        let color_index = piece_color_index(*piece);
+        let type_index = piece_type_index(*piece);
+        let (middlegame_square, endgame_square) = weights.piece_square(*piece, tile);
+        let (middlegame_value, endgame_value) = weights.material(type_index);
+        sink.add(Term::Material, color_index, middlegame_value, endgame_value);
+        sink.add(Term::PieceSquares, color_index, middlegame_square, endgame_square);
+        phase += PHASE_WEIGHTS[type_index];
+
+        if *piece & TYPES::PAWN > 0 {
+            pawns[color_index] |= 1 << tile;
+        } else if *piece & TYPES::KING == 0 {
+            let (reachable, zone_attacks) = count_attacks(game, *piece, tile, king_zones[1 - color_index]);
+            let mobility = reachable - MOBILITY_BASE[type_index];
+            let (middlegame_weight, endgame_weight) = weights.mobility(type_index);
+            sink.add(Term::Mobility, color_index, mobility * middlegame_weight, mobility * endgame_weight);
+            king_zone_attacks[1 - color_index] += zone_attacks;
+        }
+    }
+
+    for color_index in 0..2 {
+        let mut remaining = pawns[color_index];
+        while remaining != 0 {
+            let tile = remaining.trailing_zeros() as usize;
+            remaining &= remaining - 1;
+            let (row, file) = (tile / 8, tile % 8);
+            let files = (file.saturating_sub(1)..=(file + 1).min(7)).fold(0u64, |files, neighbour| files | FILE_A << neighbour);
+            if pawns[color_index] & files & !(FILE_A << file) == 0 {
+                let (middlegame_weight, endgame_weight) = weights.isolated_pawn();
+                sink.add(Term::Pawns, color_index, middlegame_weight, endgame_weight);
+            }
+            // No enemy pawn ahead on this file or the next ones
+            let rows_ahead = if color_index == 0 { (1u64 << (row * 8)) - 1 } else { u64::MAX.checked_shl((row as u32 + 1) * 8).unwrap_or(0) };
+            if pawns[1 - color_index] & files & rows_ahead == 0 {
+                let relative_rank = if color_index == 0 { 7 - row } else { row };
+                let (middlegame_weight, endgame_weight) = weights.passed_pawn(relative_rank);
+                sink.add(Term::PassedPawns, color_index, middlegame_weight, endgame_weight);
+            }
+        }
+        for file in 0..8 {
+            let file_pawns = pawns[color_index] & FILE_A << file;
+            if file_pawns & file_pawns.wrapping_sub(1) != 0 {
+                let extra = file_pawns.count_ones() as i32 - 1;
+                let (middlegame_weight, endgame_weight) = weights.doubled_pawn();
+                sink.add(Term::Pawns, color_index, middlegame_weight * extra, endgame_weight * extra);
+            }
+        }
+    }
+
+    for (color_index, zone_attacks) in king_zone_attacks.iter().enumerate() {
+        let king_tile = game.king_tiles[color_index] as usize;
+        let own_pawn = TYPES::PAWN | if color_index == 0 { COLORS::WHITE } else { COLORS::BLACK };
+        let forward: i32 = if color_index == 0 { -8 } else { 8 };
+        let file = king_tile % 8;
+        let mut shield = 0;
+        for shield_file in file.saturating_sub(1)..=(file + 1).min(7) {
+            let base = (king_tile / 8 * 8 + shield_file) as i32;
+            let near = base + forward;
+            let far = base + 2 * forward;
+            if (0..64).contains(&near) && game.board[near as usize] == own_pawn {
+                shield += weights.pawn_shield(1);
+            } else if (0..64).contains(&far) && game.board[far as usize] == own_pawn {
+                shield += weights.pawn_shield(2);
+            }
+        }
+        sink.add(Term::KingSafety, color_index, shield + weights.king_zone_attack() * zone_attacks, 0);
+    }
+
+    let (middlegame_tempo, endgame_tempo) = weights.tempo();
+    sink.add(Term::Tempo, piece_color_index(game.turn), middlegame_tempo, endgame_tempo);
+
+    phase.min(TOTAL_PHASE)
+}
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;                      // the a-file as a bit set, a8 being bit 0
+
+// The king's square and its neighbours as a bit set
+fn king_zone(king_tile: usize) -> u64 {
+    let (row, file) = ((king_tile / 8) as i32, (king_tile % 8) as i32);
+    let mut zone = 0;
+    for zone_row in (row - 1).max(0)..=(row + 1).min(7) {
+        for zone_file in (file - 1).max(0)..=(file + 1).min(7) {
+            zone |= 1u64 << (zone_row * 8 + zone_file);
+        }
+    }
+    zone
+}
+
+// Of the squares a knight, bishop, rook or queen attacks: how many are empty or hold an enemy
+// piece, and how many lie in the enemy king's zone
+fn count_attacks(game: &GAME, piece: u8, tile: usize, enemy_king_zone: u64) -> (i32, i32) {
+    let own_color = piece & (COLORS::WHITE | COLORS::BLACK);
+    let (mut reachable, mut zone_attacks) = (0, 0);
+    let mut count = |target: usize| {
+        reachable += (game.board[target] & own_color == 0) as i32;
+        zone_attacks += (enemy_king_zone >> target & 1) as i32;
+    };
+    if piece & TYPES::KNIGHT > 0 {
+        let (row, file) = ((tile / 8) as i8, (tile % 8) as i8);
+        for (row_offset, file_offset) in KNIGHT_JUMPS.iter() {
+            let (target_row, target_file) = (row + row_offset, file + file_offset);
+            if (0..8).contains(&target_row) && (0..8).contains(&target_file) {
+                count((target_row * 8 + target_file) as usize);
+            }
+        }
+        return (reachable, zone_attacks);
+    }
+    let directions = if piece & TYPES::BISHOP > 0 { 4..8 } else if piece & TYPES::ROOK > 0 { 0..4 } else { 0..8 };
+    for index in directions {
+        for distance in 1..=game.computed_distances[tile][index] as i8 {
+            let target = (tile as i8 + SLIDING_OFFSETS[index] * distance) as usize;
+            count(target);
+            if game.board[target] != TYPES::NONE {
+                break;
+            }
+        }
+    }
+    (reachable, zone_attacks)
+}
+
+/// Static evaluation in centipawns from the point of view of the side to move. It takes the
+/// walk `evaluate_trace` records, but only sums the terms: no trace, and nothing allocated.
+pub fn evaluate(game: &GAME) -> i32 {
+    let mut total = TermScore::default();
+    let phase = walk_terms(game, &EngineWeights, &mut total);
+    let score = total.tapered(phase);
+    if game.turn == COLORS::WHITE { score } else { -score }
+}
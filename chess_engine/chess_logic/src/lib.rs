@@ -1,13 +1,52 @@
+#[allow(unused_imports)]
 use std::ptr::read_unaligned;
 use std::{collections::HashMap, convert::TryInto};
 use std::cmp;
 
 mod movement;
+mod zobrist;
+mod movegen;
+mod evaluation;
+mod transposition_table;
+mod move_picker;
+mod time_manager;
+mod notation;
+mod search;
+mod polyglot;
+mod pgn;
+mod syzygy;
+mod retrograde;
+mod epd;
+mod skill;
+mod problem;
+mod tactics;
+#[cfg(feature = "nnue")]
+mod nnue;
 
 use movement::available_moves_for_piece;
 use movement::get_all_attacked_squares;
+use movegen::UndoInfo;
+
+pub use movegen::{Move, generate_legal_moves, make_move, unmake_move, move_from_uci, move_history, starting_position, perft};
+pub use evaluation::{evaluate, evaluate_trace, evaluate_trace_with, EvalTrace, TermScore, EvalParams, DEFAULT_EVAL_PARAMS};
+pub use notation::{move_to_san, move_from_san, pv_to_san, pv_to_movetext};
+pub use move_picker::{MovePicker, HistoryTable, SEE_VALUES, see};
+pub use search::{Searcher, SearchOptions, SearchLimits, SearchResult, PvLine, Score, InfoHandler, MATE_SCORE};
+pub use time_manager::{Clock, WallClock, MockClock, TimeManager};
+pub use retrograde::{EndgameTable, EndgameTables, Dtm};
+pub use syzygy::{Tablebase, Wdl, ProbeError, material_name, filter_root_moves};
+pub use pgn::{PgnGame, Outcome, parse_pgn, game_outcome};
+pub use epd::{EpdRecord, parse_epd};
+pub use skill::Skill;
+pub use problem::{SolutionNode, MateSolution, HelpmateSolution, solve_mate, solve_selfmate, solve_helpmate};
+pub use movement::{is_square_attacked, attackers_of};
+pub use tactics::{Tactics, HangingPiece, Pin, Skewer, DiscoveredAttack, Fork, find_tactics};
+#[cfg(feature = "nnue")]
+pub use nnue::{Network, NnueEvaluator};
+pub use polyglot::{OpeningBook, BookEntry, BookMove, BookBuilder, MoveStats, polyglot_key, encode_book_move, decode_book_move};
 
 #[non_exhaustive]
+#[allow(clippy::upper_case_acronyms)]
 struct TYPES;
 
 impl TYPES {
@@ -20,8 +59,10 @@ impl TYPES {
     pub const KING: u8 = 32;
 }
 
+/// The color bits of a piece, also naming a side in the attack queries.
 #[non_exhaustive]
-struct COLORS;
+#[allow(clippy::upper_case_acronyms)]
+pub struct COLORS;
 
 impl COLORS {
     pub const WHITE: u8 = 64;
@@ -41,9 +82,15 @@ pub struct GAME {
     draw: bool,
     check_mate: bool,
     promoting: u8,
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    hash: u64,
+    king_tiles: [u8; 2],                                        // white, black
+    history: Vec<UndoInfo>,
 }
 
 impl GAME {
+    #[allow(clippy::unnecessary_cast)]
     fn tiles_to_the_edge() -> [[u8; 8]; 64] {
         let mut precomputed_distances = [[0u8; 8]; 64];
     
@@ -94,6 +141,7 @@ impl GAME {
         self.draw
     }
 
+    #[allow(clippy::needless_return)]
     pub fn is_whites_turn(&self) -> bool {
         if self.turn & COLORS::WHITE > 0 {
             return true
@@ -107,6 +155,7 @@ impl GAME {
     }
 }
 
+#[allow(clippy::needless_late_init, clippy::cmp_owned, clippy::suspicious_else_formatting)]
 pub fn promote_pawn(new_type: &str, game: &mut GAME) -> bool {
     let mut if_valid_move = false;
     if game.promoting <= 63 {
@@ -142,6 +191,7 @@ pub fn promote_pawn(new_type: &str, game: &mut GAME) -> bool {
     if_valid_move
 }
 
+#[allow(clippy::needless_bool_assign)]
 pub fn move_piece_from_to(from_tile: &str, to_tile: &str, game: &mut GAME) -> bool {
     println!("gameturn: {}", game.turn);
     let from_tile = algebraic_notation_to_memory_location(from_tile);
@@ -218,6 +268,7 @@ fn update_chastling_ability(game: &mut GAME, from_tile: usize, to_tile: usize) {
     }
 }
 
+#[allow(clippy::if_same_then_else, clippy::needless_bool_assign)]
 fn piece_is_correct_color(game: &mut GAME, piece_to_move: u8, mut if_valid_move: bool) -> bool {
     if if_valid_move {
         if ((game.turn & COLORS::WHITE) > 0) & ((piece_to_move & COLORS::WHITE) > 0) {
@@ -231,6 +282,7 @@ fn piece_is_correct_color(game: &mut GAME, piece_to_move: u8, mut if_valid_move:
     if_valid_move
 }
 
+#[allow(clippy::needless_bool_assign)]
 fn is_legal_move_for_piece(game: &mut GAME, piece_to_move: u8, from_tile: usize, to_tile: usize, mut if_valid_move: bool) -> bool {
     let available_moves_for_piece = available_moves_for_piece(piece_to_move, from_tile, game);
 
@@ -242,6 +294,7 @@ fn is_legal_move_for_piece(game: &mut GAME, piece_to_move: u8, from_tile: usize,
     if_valid_move
 }
 
+#[allow(clippy::manual_range_contains, clippy::unnecessary_cast)]
 fn handle_un_passant_logic(game: &mut GAME, piece_to_move: u8, from_tile: usize, to_tile: usize) {
     if piece_to_move & TYPES::PAWN > 0 {
         if (from_tile >= 16 && from_tile <= 23) || (from_tile >= 40 && from_tile <= 47) {
@@ -266,6 +319,7 @@ fn handle_un_passant_logic(game: &mut GAME, piece_to_move: u8, from_tile: usize,
     } 
 }
 
+#[allow(clippy::collapsible_if, clippy::manual_range_contains)]
 fn handle_promote_logic(game: &mut GAME, piece_to_move: u8, to_tile: usize) {
     if piece_to_move & TYPES::PAWN > 0 {
         if piece_to_move & COLORS::WHITE > 0 {
@@ -282,6 +336,7 @@ fn handle_promote_logic(game: &mut GAME, piece_to_move: u8, to_tile: usize) {
     }
 }
 
+#[allow(clippy::needless_bool_assign)]
 fn check_if_allied_king_is_checked(game_clone: &mut GAME, game: &mut GAME) {
     let board = game_clone.board;
     let turn = game.turn;
@@ -309,6 +364,7 @@ fn check_if_allied_king_is_checked(game_clone: &mut GAME, game: &mut GAME) {
     }
 }
 
+#[allow(clippy::needless_bool_assign)]
 fn check_if_enemy_king_is_checked(game: &mut GAME) {
     let board = game.board;
     let turn = game.turn;
@@ -359,6 +415,12 @@ fn swap_turn(game: &mut GAME) {
 
 
 pub fn init_game() -> GAME {
+    init_game_from_fen(STARTINGFEN).unwrap()
+}
+
+pub fn init_game_from_fen(fen: &str) -> Result<GAME, String> {
+    validate_fen(fen)?;
+
     let mut game = GAME {
         computed_distances: GAME::tiles_to_the_edge(),
         board: GAME::generate_board_array(),
@@ -371,6 +433,11 @@ pub fn init_game() -> GAME {
         draw: false,
         check_mate: false,
         promoting: 100,
+        halfmove_clock: 0,
+        fullmove_number: 1,
+        hash: 0,
+        king_tiles: [100, 100],
+        history: Vec::new(),
     };
     let mut piece_type_from_symbol = HashMap::new();
 
@@ -381,10 +448,145 @@ pub fn init_game() -> GAME {
     piece_type_from_symbol.insert('r', TYPES::ROOK);
     piece_type_from_symbol.insert('q', TYPES::QUEEN);
 
-    let (loaded_board, un_passant_default) = load_position_from_fen(STARTINGFEN, &mut game, &mut piece_type_from_symbol);
+    // Only the piece placement goes through load_position_from_fen, load_state_from_fen reads the rest
+    let placement = fen.split_whitespace().next().unwrap_or("");
+    let (loaded_board, _) = load_position_from_fen(placement, &mut game, &mut piece_type_from_symbol);
     game.board = loaded_board;
-    game.tile_available_to_un_passant = un_passant_default;
-    game
+    load_state_from_fen(fen, &mut game);
+    movegen::sync_derived_state(&mut game);
+    Ok(game)
+}
+
+// Side to move, castling ability, en passant tile and the move counters, the fields after the
+// piece placement
+fn load_state_from_fen(fen: &str, game: &mut GAME) {
+    for (parts_index, part) in fen.split_whitespace().enumerate() {
+        if parts_index == 1 {
+            if part == "w" {
+                game.turn = COLORS::WHITE;
+            } else if part == "b" {
+                game.turn = COLORS::BLACK;
+            }
+        } else if parts_index == 2 {
+            for char in part.chars() {
+                if char == 'K' {
+                    game.chastling_ability[0] = true;
+                } else if char == 'Q' {
+                    game.chastling_ability[1] = true;
+                } else if char == 'k' {
+                    game.chastling_ability[2] = true;
+                } else if char == 'q' {
+                    game.chastling_ability[3] = true;
+                }
+            }
+        } else if parts_index == 3 && !part.contains('-') {
+            game.tile_available_to_un_passant = algebraic_notation_to_memory_location(part) as u8;
+        } else if parts_index == 4 {
+            game.halfmove_clock = part.parse::<u16>().unwrap_or(0);
+        } else if parts_index == 5 {
+            game.fullmove_number = part.parse::<u16>().unwrap_or(1);
+        }
+    }
+}
+
+/// The position as a FEN string, the inverse of init_game_from_fen.
+///```
+/// use chess_logic::*;
+/// let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+/// assert_eq!(game_to_fen(&init_game_from_fen(fen).unwrap()), fen);
+/// let mut game = init_game();
+/// let played = move_from_uci(&game, "e2e4").unwrap();
+/// make_move(&mut game, played);
+/// assert_eq!(game_to_fen(&game), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+///```
+pub fn game_to_fen(game: &GAME) -> String {
+    let mut fen = String::new();
+    for rank in 0..8 {
+        let mut empty_tiles = 0;
+        for file in 0..8 {
+            let piece = game.board[rank * 8 + file];
+            if piece == TYPES::NONE {
+                empty_tiles += 1;
+                continue;
+            }
+            if empty_tiles > 0 {
+                fen.push_str(&empty_tiles.to_string());
+                empty_tiles = 0;
+            }
+            let symbol = ['p', 'n', 'b', 'r', 'q', 'k'][piece_type_index(piece)];
+            fen.push(if piece & COLORS::WHITE > 0 { symbol.to_ascii_uppercase() } else { symbol });
+        }
+        if empty_tiles > 0 {
+            fen.push_str(&empty_tiles.to_string());
+        }
+        if rank < 7 {
+            fen.push('/');
+        }
+    }
+
+    fen.push_str(if game.turn == COLORS::WHITE { " w " } else { " b " });
+    let castling: String = "KQkq".chars()
+        .zip(game.chastling_ability.iter())
+        .filter(|(_, allowed)| **allowed)
+        .map(|(symbol, _)| symbol)
+        .collect();
+    fen.push_str(if castling.is_empty() { "-" } else { &castling });
+    fen.push(' ');
+    if game.tile_available_to_un_passant < 64 {
+        fen.push_str(&memory_location_to_algebraic_notation(game.tile_available_to_un_passant as usize));
+    } else {
+        fen.push('-');
+    }
+    fen.push_str(&format!(" {} {}", game.halfmove_clock, game.fullmove_number));
+    fen
+}
+
+fn validate_fen(fen: &str) -> Result<(), String> {
+    let mut fen_parts = fen.split_whitespace();
+    let positions = fen_parts.next().ok_or_else(|| "Empty FEN".to_string())?;
+
+    let ranks: Vec<&str> = positions.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(format!("Expected 8 ranks in FEN, found {}", ranks.len()));
+    }
+    for rank in ranks {
+        let mut files = 0;
+        for character in rank.chars() {
+            if let Some(empty_tiles) = character.to_digit(10) {
+                files += empty_tiles;
+            } else if "pnbrqkPNBRQK".contains(character) {
+                files += 1;
+            } else {
+                return Err(format!("Invalid piece '{}' in FEN", character));
+            }
+        }
+        if files != 8 {
+            return Err(format!("Rank '{}' does not have 8 files", rank));
+        }
+    }
+    if positions.matches('K').count() != 1 || positions.matches('k').count() != 1 {
+        return Err("FEN must contain exactly one king of each color".to_string());
+    }
+
+    if let Some(turn) = fen_parts.next() {
+        if turn != "w" && turn != "b" {
+            return Err(format!("Invalid side to move '{}'", turn));
+        }
+    }
+    if let Some(castling_ability) = fen_parts.next() {
+        if !castling_ability.chars().all(|character| "KQkq-".contains(character)) {
+            return Err(format!("Invalid castling ability '{}'", castling_ability));
+        }
+    }
+    if let Some(un_passant) = fen_parts.next() {
+        let characters: Vec<char> = un_passant.chars().collect();
+        let valid = un_passant == "-"
+            || (characters.len() == 2 && ('a'..='h').contains(&characters[0]) && (characters[1] == '3' || characters[1] == '6'));
+        if !valid {
+            return Err(format!("Invalid en passant tile '{}'", un_passant));
+        }
+    }
+    Ok(())
 }
 
 // const STARTINGFEN: &str = "rnbqkbnr/pppppppp/8/6P/6p/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
@@ -404,13 +606,13 @@ const STARTINGFEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -
 /// piece_type_from_symbol.insert('b', 4);
 /// piece_type_from_symbol.insert('r', 8);
 /// piece_type_from_symbol.insert('q', 16);
-/// let empty_board = [0u8; 64];
+/// let mut game = init_game_from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
 /// let STARTINGFEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
-/// let board = chess_logic::load_position_from_fen(STARTINGFEN, empty_board, &mut piece_type_from_symbol);
+/// let (board, _) = chess_logic::load_position_from_fen(STARTINGFEN, &mut game, &mut piece_type_from_symbol);
 /// let expected_output = [136, 130, 132, 144, 160, 132, 130, 136, 129, 129, 129, 129, 129, 129, 129, 129, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 65, 65, 65, 65, 65, 65, 65, 65, 72, 66, 68, 80, 96, 68, 66, 72];
 /// assert_eq!(board, expected_output);
 ///```
-
+#[allow(unused_variables, unused_assignments, clippy::partialeq_to_none, clippy::unnecessary_cast, clippy::is_digit_ascii_radix)]
 pub fn load_position_from_fen(fen: &str, game: &mut GAME, piece_type_from_symbol: &mut HashMap<char, u8>) ->  ([u8; 64], u8) {
 
     let mut board = game.board;
@@ -494,6 +696,10 @@ pub fn load_position_from_fen(fen: &str, game: &mut GAME, piece_type_from_symbol
     (board, tile_available_to_un_passant)
 }
 
+/// A tile of the board, from 0 for a8 to 63 for h1.
+pub type Square = usize;
+
+#[allow(clippy::useless_vec, clippy::is_digit_ascii_radix, clippy::unnecessary_cast)]
 pub fn algebraic_notation_to_memory_location(algebraic_notation: &str) -> usize {
     let alphabet_to_index = vec!['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h'];
     let mut rank: usize = 0;
@@ -511,7 +717,25 @@ pub fn algebraic_notation_to_memory_location(algebraic_notation: &str) -> usize
     (rank * 8 + file) as usize
 }
 
+pub fn memory_location_to_algebraic_notation(tile: usize) -> String {
+    let alphabet_to_index = ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h'];
+    format!("{}{}", alphabet_to_index[tile % 8], 8 - tile / 8)
+}
+
+fn piece_type_index(piece: u8) -> usize {                      // pawn = 0 ... king = 5
+    (piece & 63).trailing_zeros() as usize
+}
+
+fn piece_color_index(piece: u8) -> usize {                     // white = 0, black = 1
+    if piece & COLORS::WHITE > 0 { 0 } else { 1 }
+}
+
+fn enemy_color(color: u8) -> u8 {
+    if color == COLORS::WHITE { COLORS::BLACK } else { COLORS::WHITE }
+}
+
 
+#[allow(clippy::needless_return)]
 pub fn is_black_king(piece: u8) -> bool {
     if (piece & TYPES::KING > 0) & (piece & COLORS::BLACK > 0) {
         return true;
@@ -519,6 +743,7 @@ pub fn is_black_king(piece: u8) -> bool {
         false
     }
 }
+#[allow(clippy::needless_return)]
 pub fn is_black_queen(piece: u8) -> bool {
     if (piece & TYPES::QUEEN > 0) & (piece & COLORS::BLACK > 0) {
         return true;
@@ -526,6 +751,7 @@ pub fn is_black_queen(piece: u8) -> bool {
         false
     }
 }
+#[allow(clippy::needless_return)]
 pub fn is_black_rook(piece: u8) -> bool {
     if (piece & TYPES::ROOK > 0) & (piece & COLORS::BLACK > 0) {
         return true;
@@ -533,6 +759,7 @@ pub fn is_black_rook(piece: u8) -> bool {
         false
     }
 }
+#[allow(clippy::needless_return)]
 pub fn is_black_bishop(piece: u8) -> bool {
     if (piece & TYPES::BISHOP > 0) & (piece & COLORS::BLACK > 0) {
         return true;
@@ -540,6 +767,7 @@ pub fn is_black_bishop(piece: u8) -> bool {
         false
     }
 }
+#[allow(clippy::needless_return)]
 pub fn is_black_knight(piece: u8) -> bool {
     if (piece & TYPES::KNIGHT > 0) & (piece & COLORS::BLACK > 0) {
         return true;
@@ -547,6 +775,7 @@ pub fn is_black_knight(piece: u8) -> bool {
         false
     }
 }
+#[allow(clippy::needless_return)]
 pub fn is_black_pawn(piece: u8) -> bool {
     if (piece & TYPES::PAWN > 0) & (piece & COLORS::BLACK > 0) {
         return true;
@@ -554,6 +783,7 @@ pub fn is_black_pawn(piece: u8) -> bool {
         false
     }
 }
+#[allow(clippy::needless_return)]
 pub fn is_white_king(piece: u8) -> bool {
     if (piece & TYPES::KING > 0) & (piece & COLORS::WHITE > 0) {
         return true;
@@ -561,6 +791,7 @@ pub fn is_white_king(piece: u8) -> bool {
         false
     }
 }
+#[allow(clippy::needless_return)]
 pub fn is_white_queen(piece: u8) -> bool {
     if (piece & TYPES::QUEEN > 0) & (piece & COLORS::WHITE > 0) {
         return true;
@@ -568,6 +799,7 @@ pub fn is_white_queen(piece: u8) -> bool {
         false
     }
 }
+#[allow(clippy::needless_return)]
 pub fn is_white_rook(piece: u8) -> bool {
     if (piece & TYPES::ROOK > 0) & (piece & COLORS::WHITE > 0) {
         return true;
@@ -575,6 +807,7 @@ pub fn is_white_rook(piece: u8) -> bool {
         false
     }
 }
+#[allow(clippy::needless_return)]
 pub fn is_white_bishop(piece: u8) -> bool {
     if (piece & TYPES::BISHOP > 0) & (piece & COLORS::WHITE > 0) {
         return true;
@@ -582,6 +815,7 @@ pub fn is_white_bishop(piece: u8) -> bool {
         false
     }
 }
+#[allow(clippy::needless_return)]
 pub fn is_white_knight(piece: u8) -> bool {
     if (piece & TYPES::KNIGHT > 0) & (piece & COLORS::WHITE > 0) {
         return true;
@@ -589,6 +823,7 @@ pub fn is_white_knight(piece: u8) -> bool {
         false
     }
 }
+#[allow(clippy::needless_return)]
 pub fn is_white_pawn(piece: u8) -> bool {
     if (piece & TYPES::PAWN > 0) & (piece & COLORS::WHITE > 0) {
         return true;
@@ -0,0 +1,273 @@
+use crate::TYPES;
+use crate::GAME;
+use crate::movegen::{self, GenerationKind, Move};
+use crate::movement::least_valuable_attacker;
+use crate::{enemy_color, piece_color_index, piece_type_index};
+
+// Exchange values indexed by piece_type_index: pawn, knight, bishop, rook, queen, king
+pub const SEE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 20000];
+
+pub type HistoryTable = [[[i32; 64]; 64]; 2];                  // [color][from][to]
+
+/// Static exchange evaluation: the material balance for the side to move after all
+/// captures on the target tile, each side always recapturing with its least valuable piece.
+/// # Known exchanges
+///```
+/// use chess_logic::*;
+/// // PxN, and the pawn that recaptures only gets a pawn back
+/// let game = init_game_from_fen("4k3/8/2p5/3n4/4P3/8/8/4K3 w - - 0 1").unwrap();
+/// assert_eq!(see(&game, move_from_uci(&game, "e4d5").unwrap()), SEE_VALUES[1] - SEE_VALUES[0]);
+/// // RxP defended by a rook loses the exchange for a pawn
+/// let game = init_game_from_fen("3rk3/8/8/3p4/8/8/8/3RK3 w - - 0 1").unwrap();
+/// assert_eq!(see(&game, move_from_uci(&game, "d1d5").unwrap()), SEE_VALUES[0] - SEE_VALUES[3]);
+/// // En passant takes the pawn beside the capturing one
+/// let game = init_game_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+/// assert_eq!(see(&game, move_from_uci(&game, "e5d6").unwrap()), SEE_VALUES[0]);
+/// // A quiet move onto an attacked tile loses the piece
+/// let game = init_game_from_fen("4k3/8/2p5/8/8/8/8/3RK3 w - - 0 1").unwrap();
+/// assert_eq!(see(&game, move_from_uci(&game, "d1d5").unwrap()), -SEE_VALUES[3]);
+///```
+pub fn see(game: &GAME, played: Move) -> i32 {
+    let mut board = game.board;
+    let from = played.from as usize;
+    let to = played.to as usize;
+    let mut gains = [0i32; 32];
+
+    if board[to] == TYPES::NONE {
+        if board[from] & TYPES::PAWN > 0 && played.to == game.tile_available_to_un_passant {
+            let captured_tile = if to < from { to + 8 } else { to - 8 };
+            board[captured_tile] = TYPES::NONE;
+            gains[0] = SEE_VALUES[0];
+        }
+    } else {
+        gains[0] = SEE_VALUES[piece_type_index(board[to])];
+    }
+
+    let mut value_on_tile = SEE_VALUES[piece_type_index(board[from])];
+    board[to] = board[from];
+    board[from] = TYPES::NONE;
+
+    let mut side = enemy_color(game.turn);
+    let mut depth = 0;
+    while let Some(attacker_tile) = least_valuable_attacker(&board, to, side, &game.computed_distances) {
+        depth += 1;
+        gains[depth] = value_on_tile - gains[depth - 1];
+        if depth == gains.len() - 1 || (-gains[depth - 1]).max(gains[depth]) < 0 {
+            break;
+        }
+        value_on_tile = SEE_VALUES[piece_type_index(board[attacker_tile])];
+        board[to] = board[attacker_tile];
+        board[attacker_tile] = TYPES::NONE;
+        side = enemy_color(side);
+    }
+    while depth > 0 {
+        gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+        depth -= 1;
+    }
+    gains[0]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GenerateNoisy,
+    GoodCaptures,
+    Promotions,
+    Killers,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Unordered,
+    Done,
+}
+
+/// Hands out moves one stage at a time so that a beta cutoff on an early move
+/// saves generating and sorting the rest: hash move, captures winning material
+/// (MVV-LVA), promotions, killers, quiets by history score and losing captures last.
+/// # Stage order
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("4k3/P7/4p3/3p2n1/8/5N2/8/3RK3 w - - 0 1").unwrap();
+/// let hash_move = move_from_uci(&game, "e1e2").unwrap();
+/// let killer = move_from_uci(&game, "d1d2").unwrap();
+/// let history: HistoryTable = [[[0; 64]; 64]; 2];
+/// let mut picker = MovePicker::new(&game, hash_move, [killer, Move::NONE], true);
+/// let mut order = Vec::new();
+/// while let Some(played) = picker.next(&game, &history) {
+///     order.push(played.to_uci());
+/// }
+/// // Hash move, the winning capture, promotions queen first, the killer, and RxP losing the
+/// // rook to the pawn on e6 after all the quiets
+/// assert_eq!(order[..7], ["e1e2", "f3g5", "a7a8q", "a7a8n", "a7a8r", "a7a8b", "d1d2"]);
+/// assert_eq!(order.last().unwrap(), "d1d5");
+/// let mut unique = order.clone();
+/// unique.sort();
+/// unique.dedup();
+/// assert_eq!(unique.len(), order.len());
+///```
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Move,
+    killers: [Move; 2],
+    killer_index: usize,
+    noisy_only: bool,
+    good_captures: Vec<(Move, i32)>,
+    promotions: Vec<(Move, i32)>,
+    bad_captures: Vec<(Move, i32)>,
+    quiets: Vec<(Move, i32)>,
+    unordered: Vec<Move>,
+}
+
+impl MovePicker {
+    pub fn new(game: &GAME, hash_move: Move, killers: [Move; 2], ordered: bool) -> MovePicker {
+        let mut picker = MovePicker {
+            stage: Stage::HashMove,
+            hash_move,
+            killers,
+            killer_index: 0,
+            noisy_only: false,
+            good_captures: Vec::new(),
+            promotions: Vec::new(),
+            bad_captures: Vec::new(),
+            quiets: Vec::new(),
+            unordered: Vec::new(),
+        };
+        if !ordered {
+            movegen::generate_moves(game, GenerationKind::All, &mut picker.unordered);
+            picker.unordered.reverse();
+            picker.stage = Stage::Unordered;
+        }
+        picker
+    }
+
+    /// Captures and queen promotions only, with losing captures left out, for the quiescence search.
+    pub fn new_noisy(game: &GAME) -> MovePicker {
+        let mut picker = MovePicker::new(game, Move::NONE, [Move::NONE; 2], true);
+        picker.noisy_only = true;
+        picker.stage = Stage::GenerateNoisy;
+        picker
+    }
+
+    pub fn next(&mut self, game: &GAME, history: &HistoryTable) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateNoisy;
+                    if movegen::is_pseudo_legal(game, self.hash_move) {
+                        return Some(self.hash_move);
+                    }
+                }
+                Stage::GenerateNoisy => {
+                    self.score_noisy_moves(game);
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => {
+                    if let Some(played) = pick_best(&mut self.good_captures) {
+                        return Some(played);
+                    }
+                    self.stage = Stage::Promotions;
+                }
+                Stage::Promotions => {
+                    if let Some(played) = pick_best(&mut self.promotions) {
+                        return Some(played);
+                    }
+                    self.stage = if self.noisy_only { Stage::Done } else { Stage::Killers };
+                }
+                Stage::Killers => {
+                    while self.killer_index < self.killers.len() {
+                        let killer = self.killers[self.killer_index];
+                        self.killer_index += 1;
+                        if killer != self.hash_move
+                            && killer.promotion == 0
+                            && !movegen::is_capture(game, killer)
+                            && movegen::is_pseudo_legal(game, killer) {
+                            return Some(killer);
+                        }
+                    }
+                    self.stage = Stage::GenerateQuiets;
+                }
+                Stage::GenerateQuiets => {
+                    self.score_quiet_moves(game, history);
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => {
+                    if let Some(played) = pick_best(&mut self.quiets) {
+                        return Some(played);
+                    }
+                    self.stage = Stage::BadCaptures;
+                }
+                Stage::BadCaptures => {
+                    if let Some(played) = pick_best(&mut self.bad_captures) {
+                        return Some(played);
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::Unordered => {
+                    if let Some(played) = self.unordered.pop() {
+                        return Some(played);
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    fn score_noisy_moves(&mut self, game: &GAME) {
+        let mut moves = Vec::new();
+        movegen::generate_moves(game, GenerationKind::Noisy, &mut moves);
+        for played in moves {
+            if played == self.hash_move {
+                continue;
+            }
+            let victim = game.board[played.to as usize];
+            let victim_rank = if victim != TYPES::NONE {
+                piece_type_index(victim) as i32 + 1
+            } else if played.promotion == 0 {
+                1                                               // en passant captures a pawn
+            } else {
+                0
+            };
+            let attacker_index = piece_type_index(game.board[played.from as usize]) as i32;
+            let mvv_lva = victim_rank * 8 - attacker_index;
+
+            if played.promotion != 0 {
+                if self.noisy_only && played.promotion != TYPES::QUEEN {
+                    continue;
+                }
+                let promotion_order = match played.promotion {
+                    TYPES::QUEEN => 400,
+                    TYPES::KNIGHT => 300,
+                    TYPES::ROOK => 200,
+                    _ => 100,
+                };
+                self.promotions.push((played, promotion_order + mvv_lva));
+            } else if see(game, played) >= 0 {
+                self.good_captures.push((played, mvv_lva));
+            } else if !self.noisy_only {
+                self.bad_captures.push((played, mvv_lva));
+            }
+        }
+    }
+
+    fn score_quiet_moves(&mut self, game: &GAME, history: &HistoryTable) {
+        let mut moves = Vec::new();
+        movegen::generate_moves(game, GenerationKind::Quiet, &mut moves);
+        let color_index = piece_color_index(game.turn);
+        for played in moves {
+            if played == self.hash_move || self.killers.contains(&played) {
+                continue;
+            }
+            let score = history[color_index][played.from as usize][played.to as usize];
+            self.quiets.push((played, score));
+        }
+    }
+}
+
+fn pick_best(moves: &mut Vec<(Move, i32)>) -> Option<Move> {
+    let best_index = moves.iter()
+        .enumerate()
+        .max_by_key(|(_, (_, score))| *score)
+        .map(|(index, _)| index)?;
+    Some(moves.swap_remove(best_index).0)
+}
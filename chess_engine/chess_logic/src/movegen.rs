@@ -0,0 +1,514 @@
+use std::fmt;
+
+use crate::COLORS;
+use crate::TYPES;
+use crate::GAME;
+use crate::movement::tile_is_attacked_by;
+use crate::zobrist;
+use crate::{enemy_color, memory_location_to_algebraic_notation, piece_color_index, update_chastling_ability};
+
+pub(crate) const SLIDING_OFFSETS: [i8; 8] = [-8, 8, -1, 1, -9, 9, -7, 7];
+pub(crate) const KNIGHT_JUMPS: [(i8, i8); 8] = [(-2, 1), (-1, 2), (1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1)];     // (rank, file)
+const PROMOTION_TYPES: [u8; 4] = [TYPES::QUEEN, TYPES::KNIGHT, TYPES::ROOK, TYPES::BISHOP];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: u8,
+    pub to: u8,
+    pub promotion: u8,                                          // piece type, 0 when not promoting
+}
+
+impl Move {
+    pub const NONE: Move = Move { from: 0, to: 0, promotion: 0 };
+
+    pub fn new(from: usize, to: usize, promotion: u8) -> Move {
+        Move { from: from as u8, to: to as u8, promotion }
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.from == self.to
+    }
+
+    pub fn to_uci(&self) -> String {
+        let mut notation = memory_location_to_algebraic_notation(self.from as usize);
+        notation.push_str(&memory_location_to_algebraic_notation(self.to as usize));
+        if self.promotion == TYPES::QUEEN {
+            notation.push('q');
+        } else if self.promotion == TYPES::ROOK {
+            notation.push('r');
+        } else if self.promotion == TYPES::BISHOP {
+            notation.push('b');
+        } else if self.promotion == TYPES::KNIGHT {
+            notation.push('n');
+        }
+        notation
+    }
+
+    pub(crate) fn pack(&self) -> u16 {
+        let promotion_code = if self.promotion == 0 { 0 } else { self.promotion.trailing_zeros() as u16 };
+        self.from as u16 | (self.to as u16) << 6 | promotion_code << 12
+    }
+
+    pub(crate) fn unpack(packed: u16) -> Move {
+        let promotion_code = packed >> 12;
+        Move {
+            from: (packed & 63) as u8,
+            to: ((packed >> 6) & 63) as u8,
+            promotion: if promotion_code == 0 { 0 } else { 1 << promotion_code },
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_uci())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct UndoInfo {
+    played: Move,
+    moved_piece: u8,
+    captured_piece: u8,
+    captured_tile: u8,
+    tile_available_to_un_passant: u8,
+    chastling_ability: [bool; 4],
+    halfmove_clock: u16,
+    hash: u64,
+    check: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GenerationKind {
+    All,
+    Noisy,                                                      // captures and promotions
+    Quiet,
+}
+
+pub fn sync_derived_state(game: &mut GAME) {
+    for (tile, piece) in game.board.iter().enumerate() {
+        if piece & TYPES::KING > 0 {
+            game.king_tiles[piece_color_index(*piece)] = tile as u8;
+        }
+    }
+    game.hash = zobrist::compute_hash(game);
+    game.check = side_to_move_in_check(game);
+}
+
+pub fn side_to_move_in_check(game: &GAME) -> bool {
+    let king_tile = game.king_tiles[piece_color_index(game.turn)] as usize;
+    tile_is_attacked_by(&game.board, king_tile, enemy_color(game.turn), &game.computed_distances)
+}
+
+pub fn generate_moves(game: &GAME, kind: GenerationKind, moves: &mut Vec<Move>) {
+    for tile in 0..64 {
+        if game.board[tile] & game.turn > 0 {
+            generate_moves_from_tile(game, tile, kind, moves);
+        }
+    }
+}
+
+pub fn generate_moves_from_tile(game: &GAME, tile: usize, kind: GenerationKind, moves: &mut Vec<Move>) {
+    let piece = game.board[tile];
+    if piece & TYPES::PAWN > 0 {
+        generate_pawn_moves(game, tile, kind, moves);
+    } else if piece & TYPES::KNIGHT > 0 {
+        generate_knight_moves(game, tile, kind, moves);
+    } else if piece & TYPES::BISHOP > 0 {
+        generate_sliding_moves(game, tile, 4..8, kind, moves);
+    } else if piece & TYPES::ROOK > 0 {
+        generate_sliding_moves(game, tile, 0..4, kind, moves);
+    } else if piece & TYPES::QUEEN > 0 {
+        generate_sliding_moves(game, tile, 0..8, kind, moves);
+    } else if piece & TYPES::KING > 0 {
+        generate_king_moves(game, tile, kind, moves);
+    }
+}
+
+fn push_move(game: &GAME, from: usize, to: usize, kind: GenerationKind, moves: &mut Vec<Move>) {
+    let capture = game.board[to] != TYPES::NONE;
+    if (capture && kind != GenerationKind::Quiet) || (!capture && kind != GenerationKind::Noisy) {
+        moves.push(Move::new(from, to, 0));
+    }
+}
+
+fn generate_sliding_moves(game: &GAME, tile: usize, directions: std::ops::Range<usize>, kind: GenerationKind, moves: &mut Vec<Move>) {
+    let distances_to_edge = game.computed_distances[tile];
+    for index in directions {
+        for sliding_factor in 1..distances_to_edge[index] + 1 {
+            let target_tile = (tile as i8 + SLIDING_OFFSETS[index] * sliding_factor as i8) as usize;
+            let target = game.board[target_tile];
+            if target & game.turn > 0 {
+                break;
+            }
+            push_move(game, tile, target_tile, kind, moves);
+            if target != TYPES::NONE {
+                break;
+            }
+        }
+    }
+}
+
+fn generate_knight_moves(game: &GAME, tile: usize, kind: GenerationKind, moves: &mut Vec<Move>) {
+    let rank = (tile / 8) as i8;
+    let file = (tile % 8) as i8;
+    for (rank_offset, file_offset) in KNIGHT_JUMPS.iter() {
+        let target_rank = rank + rank_offset;
+        let target_file = file + file_offset;
+        if (0..8).contains(&target_rank) && (0..8).contains(&target_file) {
+            let target_tile = (target_rank * 8 + target_file) as usize;
+            if game.board[target_tile] & game.turn == 0 {
+                push_move(game, tile, target_tile, kind, moves);
+            }
+        }
+    }
+}
+
+fn generate_king_moves(game: &GAME, tile: usize, kind: GenerationKind, moves: &mut Vec<Move>) {
+    let distances_to_edge = game.computed_distances[tile];
+    for (index, offset) in SLIDING_OFFSETS.iter().enumerate() {
+        if distances_to_edge[index] > 0 {
+            let target_tile = (tile as i8 + offset) as usize;
+            if game.board[target_tile] & game.turn == 0 {
+                push_move(game, tile, target_tile, kind, moves);
+            }
+        }
+    }
+
+    if kind == GenerationKind::Noisy || game.check {
+        return;
+    }
+    let board = &game.board;
+    let enemy = enemy_color(game.turn);
+    let attacked = |tile: usize| tile_is_attacked_by(board, tile, enemy, &game.computed_distances);
+    if game.turn == COLORS::WHITE && tile == 60 {
+        if game.chastling_ability[0] && board[63] == TYPES::ROOK + COLORS::WHITE      // King-side white chastling
+            && board[61] == 0 && board[62] == 0 && !attacked(61) && !attacked(62) {
+            moves.push(Move::new(60, 62, 0));
+        }
+        if game.chastling_ability[1] && board[56] == TYPES::ROOK + COLORS::WHITE      // Queen-side white chastling
+            && board[57] == 0 && board[58] == 0 && board[59] == 0 && !attacked(59) && !attacked(58) {
+            moves.push(Move::new(60, 58, 0));
+        }
+    } else if game.turn == COLORS::BLACK && tile == 4 {
+        if game.chastling_ability[2] && board[7] == TYPES::ROOK + COLORS::BLACK       // King-side black chastling
+            && board[5] == 0 && board[6] == 0 && !attacked(5) && !attacked(6) {
+            moves.push(Move::new(4, 6, 0));
+        }
+        if game.chastling_ability[3] && board[0] == TYPES::ROOK + COLORS::BLACK       // Queen-side black chastling
+            && board[1] == 0 && board[2] == 0 && board[3] == 0 && !attacked(3) && !attacked(2) {
+            moves.push(Move::new(4, 2, 0));
+        }
+    }
+}
+
+fn generate_pawn_moves(game: &GAME, tile: usize, kind: GenerationKind, moves: &mut Vec<Move>) {
+    let board = &game.board;
+    let white = game.turn == COLORS::WHITE;
+    let forward: i8 = if white { -8 } else { 8 };
+    let on_starting_rank = if white { (48..56).contains(&tile) } else { (8..16).contains(&tile) };
+    let capture_directions: [usize; 2] = if white { [4, 6] } else { [7, 5] };
+    let promotes = |target_tile: usize| !(8..56).contains(&target_tile);
+
+    let target_tile = (tile as i8 + forward) as usize;
+    if board[target_tile] == TYPES::NONE {
+        if promotes(target_tile) {
+            if kind != GenerationKind::Quiet {
+                for promotion in PROMOTION_TYPES.iter() {
+                    moves.push(Move::new(tile, target_tile, *promotion));
+                }
+            }
+        } else if kind != GenerationKind::Noisy {
+            moves.push(Move::new(tile, target_tile, 0));
+            let double_step_tile = (target_tile as i8 + forward) as usize;
+            if on_starting_rank && board[double_step_tile] == TYPES::NONE {
+                moves.push(Move::new(tile, double_step_tile, 0));
+            }
+        }
+    }
+
+    if kind == GenerationKind::Quiet {
+        return;
+    }
+    let enemy = enemy_color(game.turn);
+    for index in capture_directions.iter() {
+        if game.computed_distances[tile][*index] == 0 {
+            continue;
+        }
+        let target_tile = (tile as i8 + SLIDING_OFFSETS[*index]) as usize;
+        if board[target_tile] & enemy > 0 || target_tile as u8 == game.tile_available_to_un_passant {
+            if promotes(target_tile) {
+                for promotion in PROMOTION_TYPES.iter() {
+                    moves.push(Move::new(tile, target_tile, *promotion));
+                }
+            } else {
+                moves.push(Move::new(tile, target_tile, 0));
+            }
+        }
+    }
+}
+
+pub fn is_capture(game: &GAME, played: Move) -> bool {
+    game.board[played.to as usize] != TYPES::NONE
+        || (game.board[played.from as usize] & TYPES::PAWN > 0 && played.to == game.tile_available_to_un_passant)
+}
+
+pub fn is_pseudo_legal(game: &GAME, played: Move) -> bool {
+    if played.is_none() || game.board[played.from as usize] & game.turn == 0 {
+        return false;
+    }
+    let mut moves = Vec::new();
+    generate_moves_from_tile(game, played.from as usize, GenerationKind::All, &mut moves);
+    moves.contains(&played)
+}
+
+/// Plays a pseudo-legal move. Returns false and leaves the game untouched
+/// if the move would leave the own king in check.
+pub fn make_move(game: &mut GAME, played: Move) -> bool {
+    let from = played.from as usize;
+    let to = played.to as usize;
+    let moved_piece = game.board[from];
+    let color = game.turn;
+    let enemy = enemy_color(color);
+
+    let mut captured_tile = to;
+    let mut captured_piece = game.board[to];
+    if moved_piece & TYPES::PAWN > 0 && to as u8 == game.tile_available_to_un_passant && captured_piece == TYPES::NONE {
+        captured_tile = if color == COLORS::WHITE { to + 8 } else { to - 8 };
+        captured_piece = game.board[captured_tile];
+    }
+
+    game.history.push(UndoInfo {
+        played,
+        moved_piece,
+        captured_piece,
+        captured_tile: captured_tile as u8,
+        tile_available_to_un_passant: game.tile_available_to_un_passant,
+        chastling_ability: game.chastling_ability,
+        halfmove_clock: game.halfmove_clock,
+        hash: game.hash,
+        check: game.check,
+    });
+
+    let mut hash = game.hash;
+    if game.tile_available_to_un_passant < 64 {
+        hash ^= zobrist::un_passant_key(game.tile_available_to_un_passant);
+    }
+    for (castling_index, allowed) in game.chastling_ability.iter().enumerate() {
+        if *allowed {
+            hash ^= zobrist::castling_key(castling_index);
+        }
+    }
+
+    if captured_piece != TYPES::NONE {
+        game.board[captured_tile] = TYPES::NONE;
+        hash ^= zobrist::piece_key(captured_piece, captured_tile);
+    }
+    let placed_piece = if played.promotion != 0 { played.promotion + color } else { moved_piece };
+    game.board[from] = TYPES::NONE;
+    game.board[to] = placed_piece;
+    hash ^= zobrist::piece_key(moved_piece, from) ^ zobrist::piece_key(placed_piece, to);
+
+    if moved_piece & TYPES::KING > 0 {
+        game.king_tiles[piece_color_index(color)] = to as u8;
+        if to == from + 2 || from == to + 2 {
+            let (rook_from, rook_to) = if to > from { (from + 3, from + 1) } else { (from - 4, from - 1) };
+            let rook = game.board[rook_from];
+            game.board[rook_from] = TYPES::NONE;
+            game.board[rook_to] = rook;
+            hash ^= zobrist::piece_key(rook, rook_from) ^ zobrist::piece_key(rook, rook_to);
+        }
+    }
+
+    game.tile_available_to_un_passant = 100;
+    if moved_piece & TYPES::PAWN > 0 && (from == to + 16 || to == from + 16) {
+        game.tile_available_to_un_passant = ((from + to) / 2) as u8;
+        hash ^= zobrist::un_passant_key(game.tile_available_to_un_passant);
+    }
+
+    update_chastling_ability(game, from, to);
+    for (castling_index, allowed) in game.chastling_ability.iter().enumerate() {
+        if *allowed {
+            hash ^= zobrist::castling_key(castling_index);
+        }
+    }
+
+    if moved_piece & TYPES::PAWN > 0 || captured_piece != TYPES::NONE {
+        game.halfmove_clock = 0;
+    } else {
+        game.halfmove_clock += 1;
+    }
+    if color == COLORS::BLACK {
+        game.fullmove_number += 1;
+    }
+    game.turn = enemy;
+    game.hash = hash ^ zobrist::black_to_move_key();
+    game.moves.push([played.from, played.to]);
+
+    let king_tile = game.king_tiles[piece_color_index(color)] as usize;
+    if tile_is_attacked_by(&game.board, king_tile, enemy, &game.computed_distances) {
+        unmake_move(game);
+        return false;
+    }
+    game.check = side_to_move_in_check(game);
+    true
+}
+
+pub fn unmake_move(game: &mut GAME) {
+    let undo = match game.history.pop() {
+        Some(undo) => undo,
+        None => return,
+    };
+    let from = undo.played.from as usize;
+    let to = undo.played.to as usize;
+    let color = enemy_color(game.turn);
+
+    game.board[to] = TYPES::NONE;
+    game.board[from] = undo.moved_piece;
+    if undo.captured_piece != TYPES::NONE {
+        game.board[undo.captured_tile as usize] = undo.captured_piece;
+    }
+    if undo.moved_piece & TYPES::KING > 0 {
+        game.king_tiles[piece_color_index(color)] = from as u8;
+        if to == from + 2 || from == to + 2 {
+            let (rook_from, rook_to) = if to > from { (from + 3, from + 1) } else { (from - 4, from - 1) };
+            game.board[rook_from] = game.board[rook_to];
+            game.board[rook_to] = TYPES::NONE;
+        }
+    }
+
+    game.tile_available_to_un_passant = undo.tile_available_to_un_passant;
+    game.chastling_ability = undo.chastling_ability;
+    game.halfmove_clock = undo.halfmove_clock;
+    game.hash = undo.hash;
+    game.check = undo.check;
+    if color == COLORS::BLACK {
+        game.fullmove_number -= 1;
+    }
+    game.turn = color;
+    game.moves.pop();
+}
+
+/// Passes the turn without moving, used by null move pruning. Never call it while in check.
+pub fn make_null_move(game: &mut GAME) {
+    game.history.push(UndoInfo {
+        played: Move::NONE,
+        moved_piece: TYPES::NONE,
+        captured_piece: TYPES::NONE,
+        captured_tile: 0,
+        tile_available_to_un_passant: game.tile_available_to_un_passant,
+        chastling_ability: game.chastling_ability,
+        halfmove_clock: game.halfmove_clock,
+        hash: game.hash,
+        check: game.check,
+    });
+    if game.tile_available_to_un_passant < 64 {
+        game.hash ^= zobrist::un_passant_key(game.tile_available_to_un_passant);
+        game.tile_available_to_un_passant = 100;
+    }
+    game.hash ^= zobrist::black_to_move_key();
+    game.halfmove_clock = 0;                                    // repetitions can not reach back past a null move
+    game.turn = enemy_color(game.turn);
+}
+
+pub fn unmake_null_move(game: &mut GAME) {
+    if let Some(undo) = game.history.pop() {
+        game.tile_available_to_un_passant = undo.tile_available_to_un_passant;
+        game.halfmove_clock = undo.halfmove_clock;
+        game.hash = undo.hash;
+        game.check = undo.check;
+        game.turn = enemy_color(game.turn);
+    }
+}
+
+pub fn last_move_was_null(game: &GAME) -> bool {
+    game.history.last().is_some_and(|undo| undo.played.is_none())
+}
+
+pub fn is_repetition(game: &GAME) -> bool {
+    let reversible_plies = (game.halfmove_clock as usize).min(game.history.len());
+    game.history.iter()
+        .rev()
+        .take(reversible_plies)
+        .skip(1)
+        .step_by(2)
+        .any(|undo| undo.hash == game.hash)
+}
+
+// How often the current position occurred before, looking back as far as the halfmove clock allows
+pub(crate) fn repetition_count(game: &GAME) -> usize {
+    let reversible_plies = (game.halfmove_clock as usize).min(game.history.len());
+    game.history.iter()
+        .rev()
+        .take(reversible_plies)
+        .skip(1)
+        .step_by(2)
+        .filter(|undo| undo.hash == game.hash)
+        .count()
+}
+
+/// Moves played with make_move since the game was set up, oldest first.
+pub fn move_history(game: &GAME) -> Vec<Move> {
+    game.history.iter().map(|undo| undo.played).filter(|played| !played.is_none()).collect()
+}
+
+/// The position the game was set up from, before the moves of move_history.
+pub fn starting_position(game: &GAME) -> GAME {
+    let mut start = game.clone();
+    while let Some(undo) = start.history.last() {
+        if undo.played.is_none() {
+            unmake_null_move(&mut start);
+        } else {
+            unmake_move(&mut start);
+        }
+    }
+    start
+}
+
+pub fn generate_legal_moves(game: &GAME) -> Vec<Move> {
+    let mut game = game.clone();
+    sync_derived_state(&mut game);                              // games moved with move_piece_from_to do not keep it up to date
+    let mut moves = Vec::new();
+    generate_moves(&game, GenerationKind::All, &mut moves);
+    moves.retain(|played| {
+        let legal = make_move(&mut game, *played);
+        if legal {
+            unmake_move(&mut game);
+        }
+        legal
+    });
+    moves
+}
+
+pub fn move_from_uci(game: &GAME, notation: &str) -> Option<Move> {
+    generate_legal_moves(game).into_iter().find(|played| played.to_uci() == notation)
+}
+
+/// # Counting leaf nodes of the move generator
+///```
+/// use chess_logic::*;
+/// let game = init_game();
+/// assert_eq!(perft(&game, 3), 8902);
+/// let kiwipete = init_game_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+/// assert_eq!(perft(&kiwipete, 2), 2039);
+///```
+pub fn perft(game: &GAME, depth: u8) -> u64 {
+    let mut game = game.clone();
+    perft_recursive(&mut game, depth)
+}
+
+fn perft_recursive(game: &mut GAME, depth: u8) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut moves = Vec::new();
+    generate_moves(game, GenerationKind::All, &mut moves);
+    let mut nodes = 0;
+    for played in moves {
+        if make_move(game, played) {
+            nodes += perft_recursive(game, depth - 1);
+            unmake_move(game);
+        }
+    }
+    nodes
+}
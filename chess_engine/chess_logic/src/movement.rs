@@ -1,7 +1,10 @@
 use crate::COLORS;
 use crate::TYPES;
 use crate::GAME;
+use crate::Square;
+use crate::movegen::{KNIGHT_JUMPS, SLIDING_OFFSETS};
 
+#[allow(unused_variables, unused_assignments, clippy::needless_return)]
 pub fn king_attacks_from_tile(game: &mut GAME, piece: u8, tile: usize) -> [bool; 64] {
     let precomputed_distances = game.computed_distances;
     let board = game.board;
@@ -31,6 +34,7 @@ pub fn king_attacks_from_tile(game: &mut GAME, piece: u8, tile: usize) -> [bool;
     return available_moves_board
 }
 
+#[allow(clippy::collapsible_if, clippy::needless_return)]
 pub fn king_movement_from_tile(game: &mut GAME, piece: u8, tile: usize) -> [bool; 64] {
     let precomputed_distances = game.computed_distances;
     let board = game.board;
@@ -94,6 +98,7 @@ pub fn king_movement_from_tile(game: &mut GAME, piece: u8, tile: usize) -> [bool
     return available_moves_board
 }
 
+#[allow(clippy::needless_return)]
 fn queen_movement_from_tile(board: [u8; 64], piece: u8, tile: usize, precomputed_distances: [[u8; 8]; 64]) -> [bool; 64] {
     let mut available_moves_board = [false; 64];
     let piece_color: u8;
@@ -125,6 +130,7 @@ fn queen_movement_from_tile(board: [u8; 64], piece: u8, tile: usize, precomputed
     return available_moves_board
 }
 
+#[allow(clippy::needless_return)]
 fn rook_movement_from_tile(board: [u8; 64], piece: u8, tile: usize, precomputed_distances: [[u8; 8]; 64]) -> [bool; 64] {
     let mut available_moves_board = [false; 64];
     let piece_color: u8;
@@ -156,6 +162,7 @@ fn rook_movement_from_tile(board: [u8; 64], piece: u8, tile: usize, precomputed_
     return available_moves_board
 }
 
+#[allow(clippy::needless_return)]
 fn bishop_movement_from_tile(board: [u8; 64], piece: u8, tile: usize, precomputed_distances: [[u8; 8]; 64]) -> [bool; 64] {
     let mut available_moves_board = [false; 64];
     let piece_color: u8;
@@ -187,6 +194,7 @@ fn bishop_movement_from_tile(board: [u8; 64], piece: u8, tile: usize, precompute
     return available_moves_board
 }
 
+#[allow(clippy::needless_late_init, clippy::collapsible_if, clippy::needless_return)]
 fn knight_movement_from_tile(board: [u8; 64], piece: u8, tile: usize, precomputed_distances: [[u8; 8]; 64]) -> [bool; 64] {
     let mut available_moves_board = [false; 64];
     let piece_color: u8;
@@ -272,6 +280,7 @@ fn knight_movement_from_tile(board: [u8; 64], piece: u8, tile: usize, precompute
     return available_moves_board
 }
 
+#[allow(clippy::collapsible_if, clippy::needless_return)]
 fn pawn_movement_from_tile(game: &mut GAME, piece: u8, tile: usize) -> [bool; 64] {
     let mut available_moves_board = [false; 64];
     let precomputed_distances = game.computed_distances;
@@ -394,64 +403,17 @@ pub fn available_moves_for_piece(piece_to_move: u8, from_tile: usize, game: &mut
 }
 
 
-fn pawn_attack_from_tile(game: &mut GAME, piece: u8, tile: usize) -> [bool; 64] {
-    let mut available_moves_board = [false; 64];
-    let precomputed_distances = game.computed_distances;
-    let un_passant_tile = game.tile_available_to_un_passant;
-    let board = game.board;
-    let enemy_piece_color: u8;
-    let offsets: [i8; 4] = [-9, -7, 9, 7];
-    let precomputed_distances_to_edge = [precomputed_distances[tile][4], 
-                                                precomputed_distances[tile][6],
-                                                precomputed_distances[tile][5],
-                                                precomputed_distances[tile][7]];
-    if piece & COLORS::WHITE > 0 {
-        enemy_piece_color = COLORS::BLACK;
-        for (index, offset) in offsets[0..2].iter().enumerate() {
-            let target_tile: i8 = tile as i8 + offset;
-            if index == 0 {
-                if precomputed_distances_to_edge[index] > 0 {       // tile diagonaly left from white pawn
-                    if (board[target_tile as usize] & enemy_piece_color > 0) || target_tile as u8 == un_passant_tile || board[target_tile as usize] == 0 {
-                        available_moves_board[target_tile as usize] = true;
-                    } else {
-                        continue;
-                    }
-                }
-            } else if index == 1 {
-                if precomputed_distances_to_edge[index] > 0 {       // tile diagonaly right from white pawn
-                    if (board[target_tile as usize] & enemy_piece_color > 0) || target_tile as u8 == un_passant_tile || board[target_tile as usize] == 0 {
-                        available_moves_board[target_tile as usize] = true;
-                    } else {
-                        continue;
-                    }
-                }
-            }
-        }
-    } else {
-        enemy_piece_color = COLORS::WHITE;
-        for (index, offset) in offsets[2..4].iter().enumerate() {
-            let target_tile: i8 = tile as i8 + offset;
-            if index == 0 {
-                if precomputed_distances_to_edge[index + 2] > 0 {       // tile diagonaly left from black pawn
-                    if (board[target_tile as usize] & enemy_piece_color > 0) || target_tile as u8 == un_passant_tile || board[target_tile as usize] == 0 {
-                        available_moves_board[target_tile as usize] = true;
-                    } else {
-                        continue;
-                    }
-                }
-            } else if index == 1 {
-                if precomputed_distances_to_edge[index + 2] > 0 {       // tile diagonaly right from black pawn
-                    if (board[target_tile as usize] & enemy_piece_color > 0) || target_tile as u8 == un_passant_tile || board[target_tile as usize] == 0 {
-                        available_moves_board[target_tile as usize] = true;
-                    } else {
-                        continue;
-                    }
-                }
-            }
+// A pawn attacks both tiles diagonally in front of it, whatever stands there: an enemy piece
+// it can take, an own piece it defends or an empty tile the enemy king may not step on
+fn pawn_attack_from_tile(piece: u8, tile: usize, precomputed_distances: &[[u8; 8]; 64]) -> [bool; 64] {
+    let mut attacked_tiles = [false; 64];
+    let directions: [usize; 2] = if piece & COLORS::WHITE > 0 { [4, 6] } else { [5, 7] };
+    for index in directions.iter() {
+        if precomputed_distances[tile][*index] > 0 {
+            attacked_tiles[(tile as i8 + SLIDING_OFFSETS[*index]) as usize] = true;
         }
     }
-    // draw_movement_board(available_moves_board);
-    return available_moves_board
+    attacked_tiles
 }
 
 pub fn available_attacks_for_piece(piece_to_move: u8, from_tile: usize, game: &mut GAME) -> [bool; 64] {
@@ -467,11 +429,12 @@ pub fn available_attacks_for_piece(piece_to_move: u8, from_tile: usize, game: &m
     } else if (piece_to_move & TYPES::KNIGHT) > 0 {
         moves = knight_movement_from_tile(game.board, piece_to_move, from_tile, game.computed_distances);
     } else if (piece_to_move & TYPES::PAWN) > 0 {
-        moves = pawn_attack_from_tile(game, piece_to_move, from_tile);
+        moves = pawn_attack_from_tile(piece_to_move, from_tile, &game.computed_distances);
     }
     moves
 }
 
+#[allow(clippy::clone_on_copy, clippy::needless_bool_assign, clippy::unnecessary_cast, clippy::needless_return)]
 pub fn get_all_attacked_squares(enemy_color: u8, game:&mut GAME) -> [bool; 64] {
     let mut attacked_tiles = [false; 64];
     let board: [u8; 64] = game.board.clone();
@@ -492,7 +455,112 @@ pub fn get_all_attacked_squares(enemy_color: u8, game:&mut GAME) -> [bool; 64] {
     return attacked_tiles
 }
 
+// Walks outwards from the tile and calls found() for every piece of by_color attacking it,
+// stopping as soon as found() returns true. Returns whether it stopped early.
+fn scan_attackers<F: FnMut(usize) -> bool>(board: &[u8; 64], tile: usize, by_color: u8, precomputed_distances: &[[u8; 8]; 64], mut found: F) -> bool {
+    let distances_to_edge = precomputed_distances[tile];
+
+    let pawn_directions: [usize; 2] = if by_color == COLORS::WHITE { [5, 7] } else { [4, 6] };
+    for index in pawn_directions.iter() {
+        if distances_to_edge[*index] > 0 {
+            let target_tile = (tile as i8 + SLIDING_OFFSETS[*index]) as usize;
+            if board[target_tile] == TYPES::PAWN + by_color && found(target_tile) {
+                return true;
+            }
+        }
+    }
+
+    let rank = (tile / 8) as i8;
+    let file = (tile % 8) as i8;
+    for (rank_offset, file_offset) in KNIGHT_JUMPS.iter() {
+        let target_rank = rank + rank_offset;
+        let target_file = file + file_offset;
+        if (0..8).contains(&target_rank) && (0..8).contains(&target_file) {
+            let target_tile = (target_rank * 8 + target_file) as usize;
+            if board[target_tile] == TYPES::KNIGHT + by_color && found(target_tile) {
+                return true;
+            }
+        }
+    }
+
+    for (index, offset) in SLIDING_OFFSETS.iter().enumerate() {
+        let sliding_type = if index < 4 { TYPES::ROOK } else { TYPES::BISHOP };
+        for sliding_factor in 1..distances_to_edge[index] + 1 {
+            let target_tile = (tile as i8 + offset * sliding_factor as i8) as usize;
+            let piece = board[target_tile];
+            if piece == TYPES::NONE {
+                continue;
+            }
+            if piece & by_color > 0 {
+                let piece_type = piece & !by_color;
+                let attacks = piece_type == TYPES::QUEEN
+                    || piece_type == sliding_type
+                    || (piece_type == TYPES::KING && sliding_factor == 1);
+                if attacks && found(target_tile) {
+                    return true;
+                }
+            }
+            break;
+        }
+    }
+    false
+}
+
+pub fn tile_is_attacked_by(board: &[u8; 64], tile: usize, by_color: u8, precomputed_distances: &[[u8; 8]; 64]) -> bool {
+    scan_attackers(board, tile, by_color, precomputed_distances, |_| true)
+}
+
+/// Whether any piece of the color `by` attacks the square. Nothing is allocated and the scan
+/// stops at the first attacker, so it is cheap enough for the search. Pawns attack the squares
+/// diagonally in front of them whether they are empty, hold an enemy or hold an own piece.
+/// # Attacks and defences
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("4k3/8/8/3p4/2P5/1B6/8/4K2R w - - 0 1").unwrap();
+/// let square = |name: &str| algebraic_notation_to_memory_location(name);
+/// assert!(is_square_attacked(&game, square("d5"), COLORS::WHITE));
+/// assert!(is_square_attacked(&game, square("b5"), COLORS::WHITE));    // empty, in front of the pawn
+/// assert!(is_square_attacked(&game, square("c4"), COLORS::BLACK));    // the black pawn takes
+/// assert!(is_square_attacked(&game, square("c4"), COLORS::WHITE));    // the bishop defends
+/// assert!(!is_square_attacked(&game, square("c5"), COLORS::WHITE));
+///
+/// assert_eq!(attackers_of(&game, square("d5"), COLORS::WHITE), vec![square("c4")]);    // the pawn hides the bishop
+/// assert_eq!(attackers_of(&game, square("h8"), COLORS::WHITE), vec![square("h1")]);
+/// assert!(attackers_of(&game, square("e3"), COLORS::BLACK).is_empty());
+///```
+pub fn is_square_attacked(game: &GAME, square: Square, by: u8) -> bool {
+    tile_is_attacked_by(&game.board, square, by, &game.computed_distances)
+}
+
+/// Every piece of the color `by` attacking the square: pawns first, then knights, then the
+/// pieces on the lines. Pieces behind another one on the same line are not counted.
+pub fn attackers_of(game: &GAME, square: Square, by: u8) -> Vec<Square> {
+    attackers_of_tile(&game.board, square, by, &game.computed_distances)
+}
+
+pub(crate) fn attackers_of_tile(board: &[u8; 64], tile: usize, by_color: u8, precomputed_distances: &[[u8; 8]; 64]) -> Vec<usize> {
+    let mut attackers = Vec::new();
+    scan_attackers(board, tile, by_color, precomputed_distances, |attacker_tile| {
+        attackers.push(attacker_tile);
+        false
+    });
+    attackers
+}
+
+pub fn least_valuable_attacker(board: &[u8; 64], tile: usize, by_color: u8, precomputed_distances: &[[u8; 8]; 64]) -> Option<usize> {
+    let mut least_valuable: Option<usize> = None;
+    scan_attackers(board, tile, by_color, precomputed_distances, |attacker_tile| {
+        // Piece type bits are ordered by value, pawn = 1 up to king = 32
+        if least_valuable.is_none_or(|current| board[attacker_tile] < board[current]) {
+            least_valuable = Some(attacker_tile);
+        }
+        board[attacker_tile] & TYPES::PAWN > 0
+    });
+    least_valuable
+}
+
 
+#[allow(clippy::explicit_counter_loop, clippy::print_literal)]
 fn draw_movement_board(board: [bool; 64]) {
     let mut rank  = 1;
     for available in board.iter() {
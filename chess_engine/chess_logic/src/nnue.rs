@@ -0,0 +1,347 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{piece_color_index, piece_type_index};
+use crate::{GAME, TYPES};
+
+const MAGIC: &[u8; 4] = b"CENN";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 12;
+const FEATURES: usize = 768;                                    // side, piece type and square
+const MAX_HIDDEN_SIZE: usize = 4096;
+const QA: i32 = 255;                                            // quantisation of the accumulator
+const QB: i32 = 64;                                             // quantisation of the output weights
+const SCALE: i64 = 400;                                         // network output to centipawns
+const MAX_SCORE: i32 = 20_000;                                  // stays clear of the mate scores
+
+/// An efficiently updatable network: 768 piece-square inputs seen from each side, a hidden
+/// layer of clipped ReLU units shared by both sides and one output. The side to move's half
+/// of the hidden layer comes first in the output weights.
+///
+/// Files start with the magic `CENN`, a version byte, three zero bytes and the hidden size
+/// as a little endian u32. Then follow little endian i16s: the feature weights (768 rows of
+/// hidden size), the hidden biases, the 2 × hidden size output weights and the output bias.
+/// Accumulator values are quantised by 255, output weights by 64 and the output bias by
+/// 255 × 64; the output times 400 is the score in centipawns. Inputs are indexed as
+/// `side * 384 + piece type * 64 + square` with pawn = 0 to king = 5, the own side first
+/// and squares from a1 = 0, flipped vertically for black.
+/// # A material counting network
+///```
+/// use chess_logic::*;
+/// // Hidden unit t counts the own pieces of type t, 20 for each
+/// let hidden_size = 16;
+/// let mut feature_weights = vec![0i16; 768 * hidden_size];
+/// for piece_type in 0..5 {
+///     for square in 0..64 {
+///         feature_weights[(piece_type * 64 + square) * hidden_size + piece_type] = 20;
+///     }
+/// }
+/// let mut output_weights = vec![0i16; 2 * hidden_size];
+/// for (piece_type, value) in [100, 300, 300, 500, 900].iter().enumerate() {
+///     output_weights[piece_type] = (value * 255 * 64 / (20 * 400)) as i16;
+///     output_weights[hidden_size + piece_type] = -output_weights[piece_type];
+/// }
+/// let network = Network::from_weights(hidden_size, feature_weights, vec![0; hidden_size], output_weights, 0).unwrap();
+/// assert_eq!(network.evaluate(&init_game()), 0);
+/// let queen_down = init_game_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1").unwrap();
+/// assert!((network.evaluate(&queen_down) + 900).abs() < 10);
+/// let black_to_move = init_game_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR b KQkq - 0 1").unwrap();
+/// assert_eq!(network.evaluate(&black_to_move), -network.evaluate(&queen_down));
+/// assert_eq!(Network::from_bytes(&network.to_bytes()).unwrap().evaluate(&queen_down), network.evaluate(&queen_down));
+/// assert!(Network::from_bytes(&network.to_bytes()[..100]).is_err());
+///```
+#[derive(Clone, Debug)]
+pub struct Network {
+    hidden_size: usize,
+    feature_weights: Vec<i16>,                                  // [feature][hidden unit]
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i16,
+}
+
+impl Network {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Network, String> {
+        let bytes = fs::read(path.as_ref()).map_err(|error| format!("cannot read {}: {}", path.as_ref().display(), error))?;
+        Network::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+        if bytes.len() < HEADER_SIZE || &bytes[..4] != MAGIC {
+            return Err("not a network file".to_string());
+        }
+        if bytes[4] != VERSION {
+            return Err(format!("unsupported network version {}", bytes[4]));
+        }
+        let hidden_size = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize;
+        if hidden_size == 0 || hidden_size > MAX_HIDDEN_SIZE {
+            return Err(format!("unsupported hidden layer size {}", hidden_size));
+        }
+        let expected = HEADER_SIZE + 2 * (FEATURES * hidden_size + hidden_size + 2 * hidden_size + 1);
+        if bytes.len() != expected {
+            return Err(format!("a network with {} hidden units has {} bytes, found {}", hidden_size, expected, bytes.len()));
+        }
+        let mut values = bytes[HEADER_SIZE..].chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+        let mut take = |count: usize| values.by_ref().take(count).collect::<Vec<i16>>();
+        let feature_weights = take(FEATURES * hidden_size);
+        let feature_biases = take(hidden_size);
+        let output_weights = take(2 * hidden_size);
+        let output_bias = take(1)[0];
+        Network::from_weights(hidden_size, feature_weights, feature_biases, output_weights, output_bias)
+    }
+
+    /// A network from quantised weights laid out as in the file.
+    pub fn from_weights(hidden_size: usize, feature_weights: Vec<i16>, feature_biases: Vec<i16>, output_weights: Vec<i16>, output_bias: i16) -> Result<Network, String> {
+        if hidden_size == 0 || hidden_size > MAX_HIDDEN_SIZE {
+            return Err(format!("unsupported hidden layer size {}", hidden_size));
+        }
+        if feature_weights.len() != FEATURES * hidden_size || feature_biases.len() != hidden_size || output_weights.len() != 2 * hidden_size {
+            return Err(format!("the weights do not fit a hidden layer of {} units", hidden_size));
+        }
+        Ok(Network { hidden_size, feature_weights, feature_biases, output_weights, output_bias })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[VERSION, 0, 0, 0]);
+        bytes.extend_from_slice(&(self.hidden_size as u32).to_le_bytes());
+        let weights = self.feature_weights.iter().chain(self.feature_biases.iter()).chain(self.output_weights.iter()).chain(std::iter::once(&self.output_bias));
+        for weight in weights {
+            bytes.extend_from_slice(&weight.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden_size
+    }
+
+    /// The score for the side to move, computed from scratch.
+    pub fn evaluate(&self, game: &GAME) -> i32 {
+        let accumulator = Accumulator::refreshed(self, &game.board);
+        self.output(&accumulator, piece_color_index(game.turn))
+    }
+
+    fn row(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden_size..(feature + 1) * self.hidden_size]
+    }
+
+    fn output(&self, accumulator: &Accumulator, side_to_move: usize) -> i32 {
+        let sum = output_sum(&accumulator.values[side_to_move], &accumulator.values[1 - side_to_move], &self.output_weights) as i64;
+        ((sum + self.output_bias as i64) * SCALE / (QA * QB) as i64).clamp(-MAX_SCORE as i64, MAX_SCORE as i64) as i32
+    }
+}
+
+// Input of a piece on a board tile from the side of `perspective` (white = 0, black = 1)
+fn feature(perspective: usize, piece: u8, tile: usize) -> usize {
+    let square = if perspective == 0 { tile ^ 56 } else { tile };  // the board starts at a8
+    (piece_color_index(piece) ^ perspective) * 384 + piece_type_index(piece) * 64 + square
+}
+
+#[derive(Clone)]
+struct Accumulator {
+    values: [Vec<i16>; 2],                                      // hidden layer before activation, white and black side
+    board: [u8; 64],                                            // the position it belongs to
+}
+
+impl Accumulator {
+    fn refreshed(network: &Network, board: &[u8; 64]) -> Accumulator {
+        let mut accumulator = Accumulator { values: [network.feature_biases.clone(), network.feature_biases.clone()], board: *board };
+        for (tile, piece) in board.iter().enumerate() {
+            if *piece != TYPES::NONE {
+                for perspective in 0..2 {
+                    add_row(&mut accumulator.values[perspective], network.row(feature(perspective, *piece, tile)));
+                }
+            }
+        }
+        accumulator
+    }
+
+    // Moves from the position of `previous` to `board`, only touching the squares that changed
+    fn update_from(&mut self, network: &Network, previous: &Accumulator, board: &[u8; 64]) {
+        for perspective in 0..2 {
+            self.values[perspective].copy_from_slice(&previous.values[perspective]);
+        }
+        for (tile, (before, after)) in previous.board.iter().copied().zip(board.iter().copied()).enumerate() {
+            if before == after {
+                continue;
+            }
+            for perspective in 0..2 {
+                if before != TYPES::NONE {
+                    sub_row(&mut self.values[perspective], network.row(feature(perspective, before, tile)));
+                }
+                if after != TYPES::NONE {
+                    add_row(&mut self.values[perspective], network.row(feature(perspective, after, tile)));
+                }
+            }
+        }
+        self.board = *board;
+    }
+}
+
+/// Evaluates the positions of a search with a network, keeping one accumulator per ply.
+/// Call `refresh` at the root, `push` after each move is made and `pop` when it is taken
+/// back; only the squares a move changed are updated.
+/// # Incremental updates
+///```
+/// use chess_logic::*;
+/// use std::sync::Arc;
+/// let hidden_size = 24;
+/// let weight = |index: usize| ((index * 7919) % 61) as i16 - 30;
+/// let network = Network::from_weights(
+///     hidden_size,
+///     (0..768 * hidden_size).map(weight).collect(),
+///     (0..hidden_size).map(|index| weight(index) * 4).collect(),
+///     (0..2 * hidden_size).map(|index| weight(index + 5)).collect(),
+///     17,
+/// ).unwrap();
+/// let network = Arc::new(network);
+/// let mut game = init_game_from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+/// let mut evaluator = NnueEvaluator::new(network.clone());
+/// evaluator.refresh(&game);
+/// for notation in ["e5d6", "e8g8", "b7b8q", "f8b8", "e1g1"] {
+///     let played = move_from_uci(&game, notation).unwrap();
+///     make_move(&mut game, played);
+///     evaluator.push(&game);
+///     assert_eq!(evaluator.evaluate(&game), network.evaluate(&game));
+/// }
+/// unmake_move(&mut game);
+/// unmake_move(&mut game);
+/// evaluator.pop();
+/// evaluator.pop();
+/// assert_eq!(evaluator.evaluate(&game), network.evaluate(&game));
+///```
+#[derive(Clone)]
+pub struct NnueEvaluator {
+    network: Arc<Network>,
+    stack: Vec<Accumulator>,
+    ply: usize,
+}
+
+impl NnueEvaluator {
+    pub fn new(network: Arc<Network>) -> NnueEvaluator {
+        NnueEvaluator { stack: vec![Accumulator::refreshed(&network, &[TYPES::NONE; 64])], network, ply: 0 }
+    }
+
+    pub fn network(&self) -> &Arc<Network> {
+        &self.network
+    }
+
+    /// Starts over from the given position.
+    pub fn refresh(&mut self, game: &GAME) {
+        self.stack[0] = Accumulator::refreshed(&self.network, &game.board);
+        self.ply = 0;
+    }
+
+    /// Follows a move that was just made in `game`.
+    pub fn push(&mut self, game: &GAME) {
+        if self.stack.len() == self.ply + 1 {
+            let copy = self.stack[self.ply].clone();
+            self.stack.push(copy);
+        }
+        let (done, ahead) = self.stack.split_at_mut(self.ply + 1);
+        ahead[0].update_from(&self.network, &done[self.ply], &game.board);
+        self.ply += 1;
+    }
+
+    /// Follows a move that was taken back.
+    pub fn pop(&mut self) {
+        self.ply = self.ply.saturating_sub(1);
+    }
+
+    /// The score for the side to move of the position of the last `refresh` or `push`.
+    pub fn evaluate(&self, game: &GAME) -> i32 {
+        self.network.output(&self.stack[self.ply], piece_color_index(game.turn))
+    }
+}
+
+fn add_row(values: &mut [i16], row: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        unsafe { avx2::add_row(values, row) };
+        return;
+    }
+    for (value, weight) in values.iter_mut().zip(row) {
+        *value = value.wrapping_add(*weight);
+    }
+}
+
+fn sub_row(values: &mut [i16], row: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        unsafe { avx2::sub_row(values, row) };
+        return;
+    }
+    for (value, weight) in values.iter_mut().zip(row) {
+        *value = value.wrapping_sub(*weight);
+    }
+}
+
+// Clipped ReLU of both halves of the hidden layer times the output weights
+fn output_sum(us: &[i16], them: &[i16], weights: &[i16]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        return unsafe { avx2::output_sum(us, them, weights) };
+    }
+    let (our_weights, their_weights) = weights.split_at(us.len());
+    scalar_dot(us, our_weights) + scalar_dot(them, their_weights)
+}
+
+fn scalar_dot(values: &[i16], weights: &[i16]) -> i32 {
+    values.iter().zip(weights).map(|(value, weight)| (*value as i32).clamp(0, QA) * *weight as i32).sum()
+}
+
+// The same operations on 16 values at a time, the last values of a hidden layer that is no
+// multiple of 16 go through the scalar code
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    const LANES: usize = 16;
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn add_row(values: &mut [i16], row: &[i16]) {
+        let full = values.len() / LANES * LANES;
+        for start in (0..full).step_by(LANES) {
+            let target = values.as_mut_ptr().add(start) as *mut __m256i;
+            let sum = _mm256_add_epi16(_mm256_loadu_si256(target), _mm256_loadu_si256(row.as_ptr().add(start) as *const __m256i));
+            _mm256_storeu_si256(target, sum);
+        }
+        for (value, weight) in values[full..].iter_mut().zip(&row[full..]) {
+            *value = value.wrapping_add(*weight);
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sub_row(values: &mut [i16], row: &[i16]) {
+        let full = values.len() / LANES * LANES;
+        for start in (0..full).step_by(LANES) {
+            let target = values.as_mut_ptr().add(start) as *mut __m256i;
+            let difference = _mm256_sub_epi16(_mm256_loadu_si256(target), _mm256_loadu_si256(row.as_ptr().add(start) as *const __m256i));
+            _mm256_storeu_si256(target, difference);
+        }
+        for (value, weight) in values[full..].iter_mut().zip(&row[full..]) {
+            *value = value.wrapping_sub(*weight);
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn output_sum(us: &[i16], them: &[i16], weights: &[i16]) -> i32 {
+        let (floor, ceiling) = (_mm256_setzero_si256(), _mm256_set1_epi16(super::QA as i16));
+        let mut sums = _mm256_setzero_si256();
+        let mut total = 0;
+        for (half, values) in [us, them].iter().enumerate() {
+            let weights = &weights[half * values.len()..(half + 1) * values.len()];
+            let full = values.len() / LANES * LANES;
+            for start in (0..full).step_by(LANES) {
+                let clipped = _mm256_min_epi16(_mm256_max_epi16(_mm256_loadu_si256(values.as_ptr().add(start) as *const __m256i), floor), ceiling);
+                // Pairs of 16 bit products added into 32 bit lanes
+                sums = _mm256_add_epi32(sums, _mm256_madd_epi16(clipped, _mm256_loadu_si256(weights.as_ptr().add(start) as *const __m256i)));
+            }
+            total += super::scalar_dot(&values[full..], &weights[full..]);
+        }
+        let mut lanes = [0i32; 8];
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sums);
+        total + lanes.iter().sum::<i32>()
+    }
+}
@@ -0,0 +1,203 @@
+use crate::COLORS;
+use crate::TYPES;
+use crate::GAME;
+use crate::memory_location_to_algebraic_notation;
+use crate::movegen::{self, Move};
+
+fn piece_letter(piece: u8) -> Option<char> {
+    match piece & 63 {
+        TYPES::KNIGHT => Some('N'),
+        TYPES::BISHOP => Some('B'),
+        TYPES::ROOK => Some('R'),
+        TYPES::QUEEN => Some('Q'),
+        TYPES::KING => Some('K'),
+        _ => None,
+    }
+}
+
+/// Standard algebraic notation of a legal move, with + or # when it gives check or mate.
+/// # Writing SAN
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+/// assert_eq!(move_to_san(&game, move_from_uci(&game, "e1g1").unwrap()), "O-O");
+/// assert_eq!(move_to_san(&game, move_from_uci(&game, "e5f7").unwrap()), "Nxf7");
+/// assert_eq!(move_to_san(&game, move_from_uci(&game, "c3b5").unwrap()), "Nb5");
+/// assert_eq!(move_to_san(&game, move_from_uci(&game, "d2c1").unwrap()), "Bc1");
+/// assert_eq!(move_to_san(&game, move_from_uci(&game, "a1d1").unwrap()), "Rd1");
+/// assert_eq!(move_to_san(&game, move_from_uci(&game, "d5e6").unwrap()), "dxe6");
+/// let mate = init_game_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+/// assert_eq!(move_to_san(&mate, move_from_uci(&mate, "a1a8").unwrap()), "Ra8#");
+///```
+pub fn move_to_san(game: &GAME, played: Move) -> String {
+    let from = played.from as usize;
+    let to = played.to as usize;
+    let piece = game.board[from];
+    let mut san = String::new();
+
+    if piece & TYPES::KING > 0 && (from as i32 - to as i32).abs() == 2 {
+        san.push_str(if to > from { "O-O" } else { "O-O-O" });
+    } else {
+        let capture = movegen::is_capture(game, played);
+        match piece_letter(piece) {
+            Some(letter) => {
+                san.push(letter);
+                // Name the file, the rank or both when another piece of the same kind could go there too
+                let rivals: Vec<Move> = movegen::generate_legal_moves(game).into_iter()
+                    .filter(|other| other.to == played.to && other.from != played.from && game.board[other.from as usize] == piece)
+                    .collect();
+                if !rivals.is_empty() {
+                    let square = memory_location_to_algebraic_notation(from);
+                    let same_file = rivals.iter().any(|other| other.from % 8 == played.from % 8);
+                    let same_rank = rivals.iter().any(|other| other.from / 8 == played.from / 8);
+                    if !same_file {
+                        san.push_str(&square[..1]);
+                    } else if !same_rank {
+                        san.push_str(&square[1..]);
+                    } else {
+                        san.push_str(&square);
+                    }
+                }
+            }
+            None if capture => san.push_str(&memory_location_to_algebraic_notation(from)[..1]),
+            None => {}
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&memory_location_to_algebraic_notation(to));
+        if let Some(letter) = piece_letter(played.promotion) {
+            san.push('=');
+            san.push(letter);
+        }
+    }
+
+    let mut after = game.clone();
+    if movegen::make_move(&mut after, played) && after.check {
+        san.push(if movegen::generate_legal_moves(&after).is_empty() { '#' } else { '+' });
+    }
+    san
+}
+
+/// Reads a move in standard algebraic notation. Check marks, annotations like `!?`, captures
+/// and castling written with zeros are accepted, the move has to be legal and unambiguous.
+/// # Reading SAN
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+/// assert_eq!(move_from_san(&game, "O-O").unwrap().to_uci(), "e1g1");
+/// assert_eq!(move_from_san(&game, "0-0-0").unwrap().to_uci(), "e1c1");
+/// assert_eq!(move_from_san(&game, "Nxf7!?").unwrap().to_uci(), "e5f7");
+/// assert_eq!(move_from_san(&game, "dxe6").unwrap().to_uci(), "d5e6");
+/// assert_eq!(move_from_san(&game, "Qxh3+").unwrap().to_uci(), "f3h3");
+/// let rooks = init_game_from_fen("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1").unwrap();
+/// assert_eq!(move_from_san(&rooks, "Rd1"), None);                 // both rooks reach d1
+/// assert_eq!(move_from_san(&rooks, "Rfd1").unwrap().to_uci(), "f1d1");
+/// let promotion = init_game_from_fen("8/4P1k1/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+/// assert_eq!(move_from_san(&promotion, "e8=N").unwrap().to_uci(), "e7e8n");
+/// assert_eq!(move_from_san(&promotion, "e8Q").unwrap().to_uci(), "e7e8q");
+///```
+pub fn move_from_san(game: &GAME, san: &str) -> Option<Move> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let legal_moves = movegen::generate_legal_moves(game);
+    let king_tile = game.king_tiles[if game.turn == COLORS::WHITE { 0 } else { 1 }];
+
+    let castling_to = match san {
+        "O-O" | "0-0" => Some(king_tile + 2),
+        "O-O-O" | "0-0-0" => Some(king_tile.wrapping_sub(2)),
+        _ => None,
+    };
+    if let Some(to) = castling_to {
+        return legal_moves.into_iter().find(|played| played.from == king_tile && played.to == to);
+    }
+
+    // Promotion comes last, with or without the equals sign
+    let mut san = san.replace('x', "");
+    let mut promotion = TYPES::NONE;
+    if let Some(letter) = san.chars().last().filter(|letter| letter.is_ascii_uppercase()) {
+        promotion = piece_type_from_letter(letter)?;
+        san.pop();
+        if san.ends_with('=') {
+            san.pop();
+        }
+    }
+    let piece_type = match san.chars().next().filter(|letter| letter.is_ascii_uppercase()) {
+        Some(letter) => {
+            san.remove(0);
+            piece_type_from_letter(letter)?
+        }
+        None => TYPES::PAWN,
+    };
+    let destination = san.get(san.len().saturating_sub(2)..).filter(|square| square.len() == 2)?.as_bytes();
+    if !(b'a'..=b'h').contains(&destination[0]) || !(b'1'..=b'8').contains(&destination[1]) {
+        return None;
+    }
+    let to = crate::algebraic_notation_to_memory_location(&san[san.len() - 2..]);
+    let hint = &san[..san.len() - 2];                           // file, rank or square of the moving piece
+
+    let mut candidates = legal_moves.into_iter().filter(|played| {
+        let square = memory_location_to_algebraic_notation(played.from as usize);
+        played.to as usize == to
+            && game.board[played.from as usize] & 63 == piece_type
+            && played.promotion == promotion
+            && hint.chars().all(|symbol| square.contains(symbol))
+    });
+    let played = candidates.next()?;
+    match candidates.next() {
+        Some(_) => None,
+        None => Some(played),
+    }
+}
+
+fn piece_type_from_letter(letter: char) -> Option<u8> {
+    match letter {
+        'N' => Some(TYPES::KNIGHT),
+        'B' => Some(TYPES::BISHOP),
+        'R' => Some(TYPES::ROOK),
+        'Q' => Some(TYPES::QUEEN),
+        'K' => Some(TYPES::KING),
+        _ => None,
+    }
+}
+
+/// The moves of a principal variation in SAN, stopping at the first move that is not legal.
+pub fn pv_to_san(game: &GAME, pv: &[Move]) -> Vec<String> {
+    let mut game = game.clone();
+    movegen::sync_derived_state(&mut game);
+    let mut notation = Vec::new();
+    for played in pv {
+        if !movegen::generate_legal_moves(&game).contains(played) {
+            break;
+        }
+        notation.push(move_to_san(&game, *played));
+        movegen::make_move(&mut game, *played);
+    }
+    notation
+}
+
+/// A principal variation as numbered movetext, e.g. `1. e4 e5 2. Nf3` or `12... Qxd5 13. Nc3`.
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+/// let pv = [Move::new(12, 28, 0), Move::new(62, 45, 0), Move::new(1, 18, 0)];     // e7e5 g1f3 b8c6
+/// assert_eq!(pv_to_movetext(&game, &pv), "1... e5 2. Nf3 Nc6");
+///```
+pub fn pv_to_movetext(game: &GAME, pv: &[Move]) -> String {
+    let mut fullmove_number = game.fullmove_number;
+    let mut white_to_move = game.turn == COLORS::WHITE;
+    let mut movetext = String::new();
+    for (index, san) in pv_to_san(game, pv).iter().enumerate() {
+        if white_to_move {
+            movetext.push_str(&format!("{}. ", fullmove_number));
+        } else if index == 0 {
+            movetext.push_str(&format!("{}... ", fullmove_number));
+        }
+        movetext.push_str(san);
+        movetext.push(' ');
+        if !white_to_move {
+            fullmove_number += 1;
+        }
+        white_to_move = !white_to_move;
+    }
+    movetext.trim_end().to_string()
+}
@@ -0,0 +1,271 @@
+use crate::GAME;
+use crate::movegen::{self, Move};
+use crate::notation::{move_from_san, pv_to_movetext};
+use crate::syzygy::is_insufficient_material;
+
+/// How a game ended, as written in the result tag and at the end of the movetext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unfinished,
+}
+
+impl Outcome {
+    pub fn from_pgn(result: &str) -> Option<Outcome> {
+        match result {
+            "1-0" => Some(Outcome::WhiteWins),
+            "0-1" => Some(Outcome::BlackWins),
+            "1/2-1/2" => Some(Outcome::Draw),
+            "*" => Some(Outcome::Unfinished),
+            _ => None,
+        }
+    }
+
+    pub fn to_pgn(&self) -> &'static str {
+        match self {
+            Outcome::WhiteWins => "1-0",
+            Outcome::BlackWins => "0-1",
+            Outcome::Draw => "1/2-1/2",
+            Outcome::Unfinished => "*",
+        }
+    }
+}
+
+/// A game read from PGN: its tags, the position it starts from and the moves of the main line.
+#[derive(Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub start: GAME,
+    pub moves: Vec<Move>,
+    pub outcome: Outcome,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    /// The game as PGN text: the tags in their order, a FEN tag when the game does not
+    /// start from the initial position, and movetext wrapped at 80 columns.
+    /// # Writing a game
+    ///```
+    /// use chess_logic::*;
+    /// let game = parse_pgn("[White \"A\"]\n[Black \"B\"]\n\n1. f3 e5 2. g4 Qh4# 0-1").remove(0).unwrap();
+    /// let text = game.to_pgn();
+    /// assert!(text.starts_with("[White \"A\"]\n[Black \"B\"]\n[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1"));
+    /// let again = parse_pgn(&text).remove(0).unwrap();
+    /// assert_eq!(again.moves, game.moves);
+    /// assert_eq!(again.outcome, Outcome::BlackWins);
+    ///```
+    pub fn to_pgn(&self) -> String {
+        let mut text = String::new();
+        let mut tags = self.tags.clone();
+        match tags.iter_mut().find(|(name, _)| name == "Result") {
+            Some((_, result)) => *result = self.outcome.to_pgn().to_string(),
+            None => tags.push(("Result".to_string(), self.outcome.to_pgn().to_string())),
+        }
+        let fen = crate::game_to_fen(&self.start);
+        if fen != crate::game_to_fen(&crate::init_game()) && !tags.iter().any(|(name, _)| name == "FEN") {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), fen));
+        }
+        for (name, value) in tags.iter() {
+            text += &format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""));
+        }
+        text.push('\n');
+
+        let movetext = format!("{} {}", pv_to_movetext(&self.start, &self.moves), self.outcome.to_pgn());
+        let mut line_length = 0;
+        for word in movetext.split_whitespace() {
+            if line_length > 0 && line_length + 1 + word.len() > 80 {
+                text.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                text.push(' ');
+                line_length += 1;
+            }
+            text += word;
+            line_length += word.len();
+        }
+        text += "\n\n";
+        text
+    }
+}
+
+/// Whether the rules end the game here, and why: checkmate, stalemate, the fifty-move rule,
+/// threefold repetition or material that cannot mate.
+/// # Games that are over
+///```
+/// use chess_logic::*;
+/// assert_eq!(game_outcome(&init_game()), None);
+/// let mated = init_game_from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+/// assert_eq!(game_outcome(&mated), Some((Outcome::BlackWins, "checkmate")));
+/// let kings = init_game_from_fen("8/8/4k3/8/8/3NK3/8/8 w - - 0 1").unwrap();
+/// assert_eq!(game_outcome(&kings), Some((Outcome::Draw, "insufficient material")));
+///
+/// let mut shuffling = init_game();
+/// for notation in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"].iter() {
+///     let played = move_from_uci(&shuffling, notation).unwrap();
+///     make_move(&mut shuffling, played);
+/// }
+/// assert_eq!(game_outcome(&shuffling), Some((Outcome::Draw, "threefold repetition")));
+///```
+pub fn game_outcome(game: &GAME) -> Option<(Outcome, &'static str)> {
+    if movegen::generate_legal_moves(game).is_empty() {
+        let mut game = game.clone();
+        movegen::sync_derived_state(&mut game);
+        return if !game.check {
+            Some((Outcome::Draw, "stalemate"))
+        } else if game.turn == crate::COLORS::WHITE {
+            Some((Outcome::BlackWins, "checkmate"))
+        } else {
+            Some((Outcome::WhiteWins, "checkmate"))
+        };
+    }
+    if game.halfmove_clock >= 100 {
+        return Some((Outcome::Draw, "fifty-move rule"));
+    }
+    if movegen::repetition_count(game) >= 2 {
+        return Some((Outcome::Draw, "threefold repetition"));
+    }
+    if is_insufficient_material(&game.board) {
+        return Some((Outcome::Draw, "insufficient material"));
+    }
+    None
+}
+
+/// Reads every game of a PGN text. Comments, variations and annotation glyphs are skipped,
+/// a FEN tag sets the start position. A game with a move that cannot be read is returned
+/// as an error and the games after it are still read.
+/// # Reading a collection
+///```
+/// use chess_logic::*;
+/// let pgn = r#"[Event "Casual"]
+/// [Result "1-0"]
+///
+/// 1. e4 e5 2. Bc4 {aiming at f7} Nc6 (2... Nf6 3. d3) 3. Qh5 Nf6?? 4. Qxf7# 1-0
+///
+/// [FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+///
+/// 1. e4 Kd7 2.e5 $1 1/2-1/2
+///
+/// 1. e4 Nf3 *
+/// "#;
+/// let games = parse_pgn(pgn);
+/// assert_eq!(games.len(), 3);
+/// let scholars_mate = games[0].as_ref().unwrap();
+/// assert_eq!(scholars_mate.tag("Event"), Some("Casual"));
+/// assert_eq!(scholars_mate.moves.len(), 7);
+/// assert_eq!(scholars_mate.outcome, Outcome::WhiteWins);
+/// let ending = games[1].as_ref().unwrap();
+/// assert_eq!(ending.moves.len(), 3);
+/// assert_eq!(ending.outcome, Outcome::Draw);
+/// assert!(matches!(&games[2], Err(error) if error.contains("Nf3")));
+///```
+pub fn parse_pgn(text: &str) -> Vec<Result<PgnGame, String>> {
+    let mut games = Vec::new();
+    let mut tags: Vec<(String, String)> = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut variation_depth = 0;
+    let mut chars = text.chars();
+
+    while let Some(symbol) = chars.next() {
+        if !token.is_empty() && (symbol.is_whitespace() || "[{;()".contains(symbol)) {
+            tokens.push(std::mem::take(&mut token));
+            if variation_depth == 0 && Outcome::from_pgn(tokens.last().unwrap()).is_some() {
+                games.push(read_game(std::mem::take(&mut tags), std::mem::take(&mut tokens)));
+            }
+        }
+        match symbol {
+            '{' => for closing in chars.by_ref() {
+                if closing == '}' {
+                    break;
+                }
+            },
+            ';' => for closing in chars.by_ref() {
+                if closing == '\n' {
+                    break;
+                }
+            },
+            '(' => variation_depth += 1,
+            ')' => variation_depth = usize::max(variation_depth, 1) - 1,
+            '[' if variation_depth == 0 => {
+                // A tag after movetext without a result starts the next game
+                if !tokens.is_empty() {
+                    games.push(read_game(std::mem::take(&mut tags), std::mem::take(&mut tokens)));
+                }
+                let mut tag = String::new();
+                let mut in_quotes = false;
+                let mut escaped = false;
+                for inside in chars.by_ref() {
+                    match inside {
+                        ']' if !in_quotes => break,
+                        '"' if !escaped => in_quotes = !in_quotes,
+                        _ => {}
+                    }
+                    escaped = inside == '\\' && !escaped;
+                    tag.push(inside);
+                }
+                if let Some(pair) = parse_tag(&tag) {
+                    tags.push(pair);
+                }
+            }
+            symbol if symbol.is_whitespace() => {}
+            symbol if variation_depth == 0 => token.push(symbol),
+            _ => {}
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    if !tokens.is_empty() || !tags.is_empty() {
+        games.push(read_game(tags, tokens));
+    }
+    games
+}
+
+fn parse_tag(tag: &str) -> Option<(String, String)> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace)?;
+    let value = tag[name_end..].trim();
+    let value = value.strip_prefix('"')?.strip_suffix('"')?;
+    Some((tag[..name_end].to_string(), value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+fn read_game(tags: Vec<(String, String)>, tokens: Vec<String>) -> Result<PgnGame, String> {
+    let start = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => crate::init_game_from_fen(fen).map_err(|error| format!("invalid FEN tag: {}", error))?,
+        None => crate::init_game(),
+    };
+    let mut game = start.clone();
+    let mut moves = Vec::new();
+    let mut outcome = tags.iter().find(|(name, _)| name == "Result").and_then(|(_, result)| Outcome::from_pgn(result)).unwrap_or(Outcome::Unfinished);
+
+    for token in tokens.iter() {
+        if let Some(result) = Outcome::from_pgn(token) {
+            outcome = result;
+            continue;
+        }
+        // Move numbers may stick to the move that follows them: 12.e4 or 12...e5
+        let number_length = token.find(|symbol: char| !symbol.is_ascii_digit()).unwrap_or(token.len());
+        let san = if token[number_length..].starts_with('.') || number_length == token.len() {
+            token.trim_start_matches(|symbol: char| symbol.is_ascii_digit() || symbol == '.')
+        } else {
+            token.as_str()                                      // castling written with zeros
+        };
+        if san.is_empty() || san.starts_with('$') {
+            continue;
+        }
+        match move_from_san(&game, san) {
+            Some(played) => {
+                movegen::make_move(&mut game, played);
+                moves.push(played);
+            }
+            None => return Err(format!("cannot read move {} at ply {}", san, moves.len() + 1)),
+        }
+    }
+    Ok(PgnGame { tags, start, moves, outcome })
+}
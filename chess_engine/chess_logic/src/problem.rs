@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+
+use crate::movegen::{self, Move};
+use crate::notation::move_to_san;
+use crate::GAME;
+
+/// A move of a solution together with every move that can follow it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SolutionNode {
+    pub played: Move,
+    pub replies: Vec<SolutionNode>,
+}
+
+/// The answer to "the side to move plays and mates in n moves", or for a selfmate "the side
+/// to move plays and forces the other side to mate it in n moves".
+///
+/// Every first move that forces the mate in n moves or fewer is a key, and each comes with the
+/// full tree: all defences, and after each defence every move that still forces the mate in
+/// time, so duals show up as defences with more than one continuation. More than one key is a
+/// cook. A problem is sound when it has exactly one key and no mate in fewer moves exists. The
+/// fifty move rule and repetitions play no part.
+/// # A mate in two
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("k7/8/2K5/8/8/8/8/1R6 w - - 0 1").unwrap();
+/// let solution = solve_mate(&game, 2);
+/// assert_eq!(solution.shortest, Some(2));
+/// let keys: Vec<String> = solution.keys.iter().map(|key| key.played.to_uci()).collect();
+/// assert!(keys.contains(&"c6c7".to_string()));
+/// // After 1.Kc7 black can only play Ka7, and 2.Ra1 is the one mate
+/// let key = solution.keys.iter().find(|key| key.played.to_uci() == "c6c7").unwrap();
+/// assert_eq!(key.replies.len(), 1);
+/// assert_eq!(key.replies[0].replies.len(), 1);
+/// assert!(solution.to_text(&game).contains("1...Ka7 2.Ra1#"));
+///
+/// assert!(solution.is_sound());
+/// assert!(solve_mate(&game, 1).keys.is_empty());
+///```
+/// # Short mates and cooks
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("7k/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap();
+/// let solution = solve_mate(&game, 2);
+/// assert_eq!(solution.shortest, Some(1));
+/// assert!(solution.keys.len() > 1);
+/// assert!(!solution.is_sound());
+///```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MateSolution {
+    pub moves: u8,
+    pub shortest: Option<u8>,                                   // fewest moves any mate needs, when there is one in time
+    pub keys: Vec<SolutionNode>,
+    pub nodes: u64,
+}
+
+impl MateSolution {
+    /// One key and no shorter mate.
+    pub fn is_sound(&self) -> bool {
+        self.keys.len() == 1 && self.shortest == Some(self.moves)
+    }
+
+    /// Places where the attacker has more than one way to go on after a defence.
+    pub fn dual_count(&self) -> usize {
+        self.keys.iter().map(|key| key.replies.iter().map(attacker_duals).sum::<usize>()).sum()
+    }
+
+    /// The tree as numbered lines in SAN, one line per defence. Continuations that mate at
+    /// once are written on the line of their defence, several of them are duals.
+    pub fn to_text(&self, game: &GAME) -> String {
+        let mut game = game.clone();
+        movegen::sync_derived_state(&mut game);
+        let mut lines = Vec::new();
+        for key in self.keys.iter() {
+            write_attacker_move(&mut game, key, 1, "!", 0, &mut lines);
+        }
+        lines.join("\n")
+    }
+}
+
+// Duals below a defence: its continuations beyond the first, and the ones further down
+fn attacker_duals(defence: &SolutionNode) -> usize {
+    let below: usize = defence.replies.iter().flat_map(|continuation| continuation.replies.iter()).map(attacker_duals).sum();
+    defence.replies.len().saturating_sub(1) + below
+}
+
+fn write_attacker_move(game: &mut GAME, node: &SolutionNode, number: u32, mark: &str, indent: usize, lines: &mut Vec<String>) {
+    lines.push(format!("{:indent$}{}.{}{}", "", number, move_to_san(game, node.played), mark, indent = indent));
+    movegen::make_move(game, node.played);
+    for defence in node.replies.iter() {
+        let mut line = format!("{:indent$}{}...{}", "", number, move_to_san(game, defence.played), indent = indent + 2);
+        movegen::make_move(game, defence.played);
+        if defence.replies.iter().all(|reply| reply.replies.is_empty()) {
+            for mate in defence.replies.iter() {
+                line.push_str(&format!(" {}.{}", number + 1, move_to_san(game, mate.played)));
+            }
+            lines.push(line);
+        } else {
+            lines.push(line);
+            for continuation in defence.replies.iter() {
+                write_attacker_move(game, continuation, number + 1, "", indent + 4, lines);
+            }
+        }
+        movegen::unmake_move(game);
+    }
+    movegen::unmake_move(game);
+}
+
+/// Proves or refutes a mate in `moves` for the side to move by trying every move of both
+/// sides, with a table of positions already decided.
+pub fn solve_mate(game: &GAME, moves: u8) -> MateSolution {
+    MateSolver { table: HashMap::new(), nodes: 0, selfmate: false }.solve(game, moves)
+}
+
+/// Proves or refutes a selfmate in `moves`: the side to move forces the other side to mate it.
+/// The defending side only plays a mating move when all its moves mate.
+/// # A selfmate in one
+///```
+/// use chess_logic::*;
+/// // 1.Rd4 takes the squares of the black king and leaves black only g2, which mates
+/// let game = init_game_from_fen("3R4/8/8/8/8/6pk/8/6BK w - - 0 1").unwrap();
+/// let solution = solve_selfmate(&game, 1);
+/// assert!(solution.is_sound());
+/// assert_eq!(solution.to_text(&game), "1.Rd4!\n  1...g2#");
+/// assert_eq!(solve_selfmate(&game, 2).shortest, Some(1));
+/// assert!(solve_mate(&game, 1).keys.is_empty());
+///```
+pub fn solve_selfmate(game: &GAME, moves: u8) -> MateSolution {
+    MateSolver { table: HashMap::new(), nodes: 0, selfmate: true }.solve(game, moves)
+}
+
+struct MateSolver {
+    table: HashMap<(u64, u8), bool>,                            // (position, moves left) to whether the attacker succeeds
+    nodes: u64,
+    selfmate: bool,                                             // the defender has to give the mate instead of getting it
+}
+
+impl MateSolver {
+    fn solve(mut self, game: &GAME, moves: u8) -> MateSolution {
+        let mut game = game.clone();
+        movegen::sync_derived_state(&mut game);
+        let shortest = (1..=moves).find(|&n| self.attacker_mates(&mut game, n));
+        let keys = match shortest {
+            Some(_) => movegen::generate_legal_moves(&game).into_iter()
+                .filter(|played| self.mates_after(&mut game, *played, moves))
+                .collect::<Vec<Move>>()
+                .into_iter()
+                .map(|key| self.attacker_tree(&mut game, key, moves))
+                .collect(),
+            None => Vec::new(),
+        };
+        MateSolution { moves, shortest, keys, nodes: self.nodes }
+    }
+
+    // Whether the side to move reaches the mate in at most n moves
+    fn attacker_mates(&mut self, game: &mut GAME, n: u8) -> bool {
+        if let Some(known) = self.table.get(&(game.hash, n)) {
+            return *known;
+        }
+        let mates = movegen::generate_legal_moves(game).into_iter().any(|played| self.mates_after(game, played, n));
+        self.table.insert((game.hash, n), mates);
+        mates
+    }
+
+    // Whether the attacker's move leaves a mate in at most n moves, counting itself
+    fn mates_after(&mut self, game: &mut GAME, played: Move, n: u8) -> bool {
+        self.nodes += 1;
+        movegen::make_move(game, played);
+        let defences = self.defences(game);
+        let mates = match defences {
+            Defences::Mated | Defences::Forced(_) => true,
+            Defences::Moves(defences) if n > 1 => defences.into_iter().all(|defence| {
+                self.nodes += 1;
+                movegen::make_move(game, defence);
+                let mates = self.attacker_mates(game, n - 1);
+                movegen::unmake_move(game);
+                mates
+            }),
+            _ => false,
+        };
+        movegen::unmake_move(game);
+        mates
+    }
+
+    // What the defender can do after the attacker's move. In a selfmate the defender only
+    // mates when it has to, so its mating moves are no defences.
+    fn defences(&mut self, game: &mut GAME) -> Defences {
+        let moves = movegen::generate_legal_moves(game);
+        if !self.selfmate {
+            return match (moves.is_empty(), game.check) {
+                (true, true) => Defences::Mated,
+                (true, false) => Defences::Stalemate,
+                (false, _) => Defences::Moves(moves),
+            };
+        }
+        if moves.is_empty() {
+            return Defences::Stalemate;                         // mated or stalemated, either way no selfmate
+        }
+        let (mating, defences): (Vec<Move>, Vec<Move>) = moves.into_iter().partition(|defence| gives_mate(game, *defence));
+        if defences.is_empty() {
+            Defences::Forced(mating)
+        } else {
+            Defences::Moves(defences)
+        }
+    }
+
+    // The tree below an attacker's move already known to mate in n
+    fn attacker_tree(&mut self, game: &mut GAME, played: Move, n: u8) -> SolutionNode {
+        movegen::make_move(game, played);
+        let replies = match self.defences(game) {
+            Defences::Forced(mating) => mating.into_iter().map(|played| SolutionNode { played, replies: Vec::new() }).collect(),
+            Defences::Moves(defences) if n > 1 => defences.into_iter().map(|defence| {
+                movegen::make_move(game, defence);
+                let continuations: Vec<Move> = movegen::generate_legal_moves(game).into_iter()
+                    .filter(|continuation| self.mates_after(game, *continuation, n - 1))
+                    .collect();
+                let continuations = continuations.into_iter().map(|continuation| self.attacker_tree(game, continuation, n - 1)).collect();
+                movegen::unmake_move(game);
+                SolutionNode { played: defence, replies: continuations }
+            }).collect(),
+            _ => Vec::new(),
+        };
+        movegen::unmake_move(game);
+        SolutionNode { played, replies }
+    }
+}
+
+enum Defences {
+    Mated,
+    Stalemate,
+    Forced(Vec<Move>),                                          // selfmate: every move of the defender mates
+    Moves(Vec<Move>),
+}
+
+fn gives_mate(game: &mut GAME, played: Move) -> bool {
+    movegen::make_move(game, played);
+    let mate = game.check && movegen::generate_legal_moves(game).is_empty();
+    movegen::unmake_move(game);
+    mate
+}
+
+/// Every way the side to move and its opponent can play together so that the side to move is
+/// mated by the opponent's n-th move, the side to move starting. Both sides look for the mate,
+/// so this enumerates cooperative lines instead of searching for a forced win.
+///
+/// The solutions are a tree of the side to move's moves, each followed by every mating side
+/// move that still reaches the mate in exactly n moves. Lines that only differ in the mating
+/// side's moves are duals, more lines than the composer intended are cooks.
+/// # Helpmates with and without duals
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("8/8/8/8/8/2k5/8/K1R5 b - - 0 1").unwrap();
+/// let solution = solve_helpmate(&game, 3);
+/// assert_eq!(solution.to_text(&game), "1.Kb4 Kb2 2.Ka4 Kc3 3.Ka3 Ra1#");
+/// assert_eq!(solution.dual_count(), 0);
+/// assert!(solve_helpmate(&game, 2).solutions.is_empty());
+///
+/// // After 1.Kb8 white has many waiting moves before 2.Ka8 Rh8#
+/// let game = init_game_from_fen("k7/8/1K6/8/8/8/8/7R b - - 0 1").unwrap();
+/// assert_eq!(solve_helpmate(&game, 1).to_text(&game), "1.Kb8 Rh8#");
+/// let solution = solve_helpmate(&game, 2);
+/// assert!(solution.lines().len() > 1);
+/// assert!(solution.dual_count() > 0);
+///```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HelpmateSolution {
+    pub moves: u8,
+    pub solutions: Vec<SolutionNode>,
+    pub nodes: u64,
+}
+
+impl HelpmateSolution {
+    /// Every line from the first move to the mate.
+    pub fn lines(&self) -> Vec<Vec<Move>> {
+        let mut lines = Vec::new();
+        for solution in self.solutions.iter() {
+            collect_lines(solution, &mut Vec::new(), &mut lines);
+        }
+        lines
+    }
+
+    /// Places where the mating side has more than one move that reaches the mate in time.
+    pub fn dual_count(&self) -> usize {
+        self.solutions.iter().map(mating_side_duals).sum()
+    }
+
+    /// One line per solution in SAN, numbered from the helping side's first move.
+    pub fn to_text(&self, game: &GAME) -> String {
+        let mut game = game.clone();
+        movegen::sync_derived_state(&mut game);
+        let mut text = Vec::new();
+        for line in self.lines() {
+            let mut written = Vec::new();
+            for (ply, played) in line.iter().enumerate() {
+                let san = move_to_san(&game, *played);
+                written.push(if ply % 2 == 0 { format!("{}.{}", ply / 2 + 1, san) } else { san });
+                movegen::make_move(&mut game, *played);
+            }
+            for _ in line.iter() {
+                movegen::unmake_move(&mut game);
+            }
+            text.push(written.join(" "));
+        }
+        text.join("\n")
+    }
+}
+
+fn collect_lines(node: &SolutionNode, line: &mut Vec<Move>, lines: &mut Vec<Vec<Move>>) {
+    line.push(node.played);
+    if node.replies.is_empty() {
+        lines.push(line.clone());
+    }
+    for reply in node.replies.iter() {
+        collect_lines(reply, line, lines);
+    }
+    line.pop();
+}
+
+// Duals below a move of the helping side
+fn mating_side_duals(helper: &SolutionNode) -> usize {
+    let below: usize = helper.replies.iter().flat_map(|mating| mating.replies.iter()).map(mating_side_duals).sum();
+    helper.replies.len().saturating_sub(1) + below
+}
+
+/// Finds every helpmate in exactly `moves` for the side to move.
+pub fn solve_helpmate(game: &GAME, moves: u8) -> HelpmateSolution {
+    let mut game = game.clone();
+    movegen::sync_derived_state(&mut game);
+    let mut solver = HelpmateSolver { table: HashMap::new(), nodes: 0 };
+    let solutions = if moves > 0 { solver.helper_tree(&mut game, moves) } else { Vec::new() };
+    HelpmateSolution { moves, solutions, nodes: solver.nodes }
+}
+
+struct HelpmateSolver {
+    table: HashMap<(u64, u8), bool>,                            // (position, moves left) to whether a helpmate exists
+    nodes: u64,
+}
+
+impl HelpmateSolver {
+    // Whether the side to move can be mated by the other side's n-th move
+    fn helped(&mut self, game: &mut GAME, n: u8) -> bool {
+        if let Some(known) = self.table.get(&(game.hash, n)) {
+            return *known;
+        }
+        let mut found = false;
+        for helper in movegen::generate_legal_moves(game) {
+            self.nodes += 1;
+            movegen::make_move(game, helper);
+            found = movegen::generate_legal_moves(game).into_iter().any(|mating| self.mate_follows(game, mating, n));
+            movegen::unmake_move(game);
+            if found {
+                break;
+            }
+        }
+        self.table.insert((game.hash, n), found);
+        found
+    }
+
+    // Whether the mating side's move is the mate, or leaves a helpmate in n - 1
+    fn mate_follows(&mut self, game: &mut GAME, mating: Move, n: u8) -> bool {
+        self.nodes += 1;
+        if n == 1 {
+            return gives_mate(game, mating);
+        }
+        movegen::make_move(game, mating);
+        let found = self.helped(game, n - 1);
+        movegen::unmake_move(game);
+        found
+    }
+
+    fn helper_tree(&mut self, game: &mut GAME, n: u8) -> Vec<SolutionNode> {
+        let mut solutions = Vec::new();
+        for helper in movegen::generate_legal_moves(game) {
+            movegen::make_move(game, helper);
+            let matings: Vec<Move> = movegen::generate_legal_moves(game).into_iter().filter(|mating| self.mate_follows(game, *mating, n)).collect();
+            let replies: Vec<SolutionNode> = matings.into_iter().map(|mating| {
+                if n == 1 {
+                    return SolutionNode { played: mating, replies: Vec::new() };
+                }
+                movegen::make_move(game, mating);
+                let replies = self.helper_tree(game, n - 1);
+                movegen::unmake_move(game);
+                SolutionNode { played: mating, replies }
+            }).collect();
+            movegen::unmake_move(game);
+            if !replies.is_empty() {
+                solutions.push(SolutionNode { played: helper, replies });
+            }
+        }
+        solutions
+    }
+}
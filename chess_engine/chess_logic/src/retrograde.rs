@@ -0,0 +1,638 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::movegen::{self, GenerationKind, Move};
+use crate::search::Score;
+use crate::syzygy::{self, board_material_name, is_insufficient_material};
+use crate::{COLORS, GAME, TYPES};
+
+const MAGIC: &[u8; 4] = b"CETB";
+const VERSION: u8 = 1;
+const MAX_PIECES: usize = 4;
+const DRAW: u8 = 0;                                             // other values are plies to mate + 1
+const INVALID: u8 = 255;                                        // illegal or not the canonical form of a position
+
+/// Distance to mate in plies for the side to move, ignoring the fifty move rule.
+/// `Loss(0)` is a position where the side to move is already mated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtm {
+    Win(u16),
+    Loss(u16),
+    Draw,
+}
+
+impl Dtm {
+    fn from_value(value: u8) -> Option<Dtm> {
+        match value {
+            INVALID => None,
+            DRAW => Some(Dtm::Draw),
+            value if (value - 1) % 2 == 1 => Some(Dtm::Win(value as u16 - 1)),
+            value => Some(Dtm::Loss(value as u16 - 1)),
+        }
+    }
+
+    /// The result in the form searches report it, mate in moves or a draw.
+    pub fn score(self) -> Score {
+        match self {
+            Dtm::Win(plies) => Score::Mate((plies as i32 + 1) / 2),
+            Dtm::Loss(plies) => Score::Mate(-(plies as i32) / 2),
+            Dtm::Draw => Score::Centipawns(0),
+        }
+    }
+
+    // Ordering of results for the side to move, larger is better
+    fn rank(self) -> i32 {
+        match self {
+            Dtm::Win(plies) => 1000 - plies as i32,
+            Dtm::Draw => 0,
+            Dtm::Loss(plies) => -1000 + plies as i32,
+        }
+    }
+}
+
+impl fmt::Display for Dtm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Dtm::Win(plies) => write!(f, "mate in {} ({} plies)", plies.div_ceil(2), plies),
+            Dtm::Loss(0) => write!(f, "mated"),
+            Dtm::Loss(plies) => write!(f, "mated in {} ({} plies)", plies / 2, plies),
+            Dtm::Draw => write!(f, "draw"),
+        }
+    }
+}
+
+/// Distance to mate of every position of one material signature, both sides to move.
+///
+/// Positions are indexed by the squares of their pieces, white king first, after the board
+/// is turned so that the white king lands on one of the squares that represent its symmetry
+/// class: ten without pawns (all eight symmetries), thirty two with pawns (mirroring files).
+/// En passant and castling are not part of the positions.
+pub struct EndgameTable {
+    signature: String,
+    pieces: Vec<u8>,                                            // white king, white pieces, black king, black pieces
+    pawns: bool,
+    king_slots: [u8; 64],                                       // slot of a canonical white king square, 255 otherwise
+    slot_count: usize,
+    values: Vec<u8>,
+}
+
+impl EndgameTable {
+    fn new(signature: &str) -> Result<EndgameTable, String> {
+        let sides: Vec<&str> = signature.split('v').collect();
+        if sides.len() != 2 || sides.iter().any(|side| !side.starts_with('K') || side[1..].contains('K')) {
+            return Err(format!("{} is not a material signature like KQvK", signature));
+        }
+        let mut pieces = Vec::new();
+        for (side, color) in sides.iter().zip([COLORS::WHITE, COLORS::BLACK]) {
+            for letter in side.chars() {
+                let piece_type = match letter {
+                    'K' => TYPES::KING,
+                    'Q' => TYPES::QUEEN,
+                    'R' => TYPES::ROOK,
+                    'B' => TYPES::BISHOP,
+                    'N' => TYPES::KNIGHT,
+                    'P' => TYPES::PAWN,
+                    _ => return Err(format!("unknown piece {} in {}", letter, signature)),
+                };
+                pieces.push(piece_type | color);
+            }
+        }
+        if pieces.len() > MAX_PIECES {
+            return Err(format!("{} has more than {} pieces", signature, MAX_PIECES));
+        }
+        let pawns = pieces.iter().any(|piece| piece & TYPES::PAWN != 0);
+
+        let mut table = EndgameTable { signature: signature.to_string(), pieces, pawns, king_slots: [255; 64], slot_count: 0, values: Vec::new() };
+        for tile in 0..64u8 {
+            if table.transforms().map(|transform| transform_tile(tile, transform)).min() == Some(tile) {
+                table.king_slots[tile as usize] = table.slot_count as u8;
+                table.slot_count += 1;
+            }
+        }
+        table.values = vec![INVALID; table.size()];
+        Ok(table)
+    }
+
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    fn transforms(&self) -> std::ops::Range<u8> {
+        if self.pawns { 0..2 } else { 0..8 }
+    }
+
+    fn size(&self) -> usize {
+        2 * self.slot_count * 64usize.pow(self.pieces.len() as u32 - 1)
+    }
+
+    /// Distance to mate of a position with this material, either colour may hold the
+    /// stronger side. None for other material or positions that cannot occur.
+    pub fn probe(&self, game: &GAME) -> Option<Dtm> {
+        self.probe_board(&game.board, game.turn)
+    }
+
+    fn probe_board(&self, board: &[u8; 64], turn: u8) -> Option<Dtm> {
+        let index = self.index_of_board(board, turn)?;
+        Dtm::from_value(self.values[index])
+    }
+
+    // Index of a board with this material in either colour
+    fn index_of_board(&self, board: &[u8; 64], turn: u8) -> Option<usize> {
+        if let Some(mut squares) = self.squares_of_board(board) {
+            return Some(self.index(&mut squares, turn));
+        }
+        let mut squares = self.squares_of_board(&flip_colors(board))?;
+        Some(self.index(&mut squares, turn ^ (COLORS::WHITE | COLORS::BLACK)))
+    }
+
+    // The square of every piece in table order, None when the material differs
+    fn squares_of_board(&self, board: &[u8; 64]) -> Option<Vec<u8>> {
+        let mut squares = vec![255u8; self.pieces.len()];
+        for (tile, piece) in board.iter().enumerate().filter(|(_, piece)| **piece != TYPES::NONE) {
+            let slot = (0..self.pieces.len()).find(|slot| self.pieces[*slot] == *piece && squares[*slot] == 255)?;
+            squares[slot] = tile as u8;
+        }
+        if squares.contains(&255) { None } else { Some(squares) }
+    }
+
+    // Turns the squares into their canonical form and returns its index
+    fn index(&self, squares: &mut [u8], turn: u8) -> usize {
+        self.canonicalize(squares);
+        let side = if turn == COLORS::WHITE { 0 } else { 1 };
+        let mut index = side * self.slot_count + self.king_slots[squares[0] as usize] as usize;
+        for square in squares[1..].iter() {
+            index = index * 64 + *square as usize;
+        }
+        index
+    }
+
+    fn canonicalize(&self, squares: &mut [u8]) {
+        let count = squares.len();
+        let mut best = [u8::MAX; MAX_PIECES];
+        for transform in self.transforms() {
+            let mut candidate = [0u8; MAX_PIECES];
+            for (turned, square) in candidate.iter_mut().zip(squares.iter()) {
+                *turned = transform_tile(*square, transform);
+            }
+            self.sort_identical_pieces(&mut candidate[..count]);
+            if candidate[..count] < best[..count] {
+                best = candidate;
+            }
+        }
+        squares.copy_from_slice(&best[..count]);
+    }
+
+    fn sort_identical_pieces(&self, squares: &mut [u8]) {
+        let mut start = 0;
+        while start < squares.len() {
+            let end = start + self.pieces[start..].iter().take_while(|piece| **piece == self.pieces[start]).count();
+            squares[start..end].sort_unstable();
+            start = end;
+        }
+    }
+
+    // Squares and side to move of an index, whether or not it is canonical
+    fn squares_of_index(&self, mut index: usize) -> (Vec<u8>, u8) {
+        let mut squares = vec![0u8; self.pieces.len()];
+        for square in squares[1..].iter_mut().rev() {
+            *square = (index % 64) as u8;
+            index /= 64;
+        }
+        let slot = index % self.slot_count;
+        squares[0] = self.king_slots.iter().position(|king_slot| *king_slot as usize == slot).unwrap() as u8;
+        let turn = if index / self.slot_count == 0 { COLORS::WHITE } else { COLORS::BLACK };
+        (squares, turn)
+    }
+
+    /// Builds the table by retrograde analysis. Tables for the material left after captures
+    /// and promotions are built on the way and dropped, use `EndgameTables::generate` to keep them.
+    /// # Known longest mates
+    ///```
+    /// use chess_logic::*;
+    /// let table = EndgameTable::generate("KQvK").unwrap();
+    /// assert_eq!(table.longest_win().unwrap().1, Dtm::Win(19));      // mate in 10
+    /// let mated = init_game_from_fen("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+    /// assert_eq!(table.probe(&mated), Some(Dtm::Loss(0)));
+    /// let mate_in_one = init_game_from_fen("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+    /// assert_eq!(table.probe(&mate_in_one), Some(Dtm::Win(1)));
+    /// // Colours reversed and board turned, still the same table
+    /// let mirrored = init_game_from_fen("6q1/8/8/8/8/1k6/8/K7 b - - 0 1").unwrap();
+    /// assert_eq!(table.probe(&mirrored), Some(Dtm::Win(1)));
+    /// assert_eq!(table.probe(&init_game_from_fen("6r1/8/8/8/8/1k6/8/K7 b - - 0 1").unwrap()), None);
+    ///
+    /// // Written to disk and read back as part of a set
+    /// let directory = std::env::temp_dir().join("endgame_table_doctest");
+    /// std::fs::create_dir_all(&directory).unwrap();
+    /// table.save(&directory).unwrap();
+    /// let tables = EndgameTables::open(&directory).unwrap();
+    /// let (best_move, result) = tables.best_move(&mate_in_one).unwrap();
+    /// assert_eq!(result, Dtm::Win(1));
+    /// assert_eq!(best_move.to_uci(), "g1g8");
+    ///```
+    pub fn generate(signature: &str) -> Result<EndgameTable, String> {
+        let mut tables = EndgameTables::new();
+        tables.generate(signature)?;
+        Ok(tables.tables.remove(signature).unwrap())
+    }
+
+    /// The position with the longest win, as FEN, and its distance to mate.
+    pub fn longest_win(&self) -> Option<(String, Dtm)> {
+        let (index, value) = self.values.iter().enumerate()
+            .filter(|(_, value)| matches!(Dtm::from_value(**value), Some(Dtm::Win(_))))
+            .max_by_key(|(index, value)| (**value, std::cmp::Reverse(*index)))?;
+        let (squares, turn) = self.squares_of_index(index);
+        let mut game = empty_game();
+        self.set_position(&mut game, &squares, turn);
+        Some((crate::game_to_fen(&game), Dtm::from_value(*value)?))
+    }
+
+    /// Number of wins, draws and losses for the side to move over all positions.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let mut counts = (0, 0, 0);
+        for value in self.values.iter().filter_map(|value| Dtm::from_value(*value)) {
+            match value {
+                Dtm::Win(_) => counts.0 += 1,
+                Dtm::Draw => counts.1 += 1,
+                Dtm::Loss(_) => counts.2 += 1,
+            }
+        }
+        counts
+    }
+
+    // Puts the pieces on the board of a game made by `empty_game`
+    fn set_position(&self, game: &mut GAME, squares: &[u8], turn: u8) {
+        game.board = [TYPES::NONE; 64];
+        for (square, piece) in squares.iter().zip(self.pieces.iter()) {
+            game.board[*square as usize] = *piece;
+        }
+        game.turn = turn;
+        movegen::sync_derived_state(game);
+    }
+
+    // A position the index stands for: canonical, pieces on distinct squares, pawns off the
+    // back ranks and the side that just moved not in check
+    fn is_valid(&self, game: &mut GAME, squares: &[u8], turn: u8) -> bool {
+        for (slot, square) in squares.iter().enumerate() {
+            if squares[..slot].contains(square) {
+                return false;
+            }
+            if self.pieces[slot] & TYPES::PAWN != 0 && (*square < 8 || *square >= 56) {
+                return false;
+            }
+        }
+        let mut canonical = squares.to_vec();
+        self.canonicalize(&mut canonical);
+        if canonical != squares {
+            return false;
+        }
+        self.set_position(game, squares, turn ^ (COLORS::WHITE | COLORS::BLACK));
+        !game.check
+    }
+
+    /// Writes the table to a file named after its signature, `KQvK.cetb`, in a directory.
+    pub fn save<P: AsRef<Path>>(&self, directory: P) -> Result<(), String> {
+        let path = directory.as_ref().join(format!("{}.cetb", self.signature));
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(self.signature.len() as u8);
+        bytes.extend_from_slice(self.signature.as_bytes());
+        bytes.extend_from_slice(&self.values);
+        fs::write(&path, bytes).map_err(|error| format!("cannot write {}: {}", path.display(), error))
+    }
+
+    /// Writes the table in the Syzygy format, `KQvK.rtbw` and `KQvK.rtbz`, for `Tablebase` and
+    /// other programs that probe Syzygy tables. Only three piece endings without pawns can be
+    /// written on their own, `EndgameTables::save_syzygy` also writes KPvK. The test tables in
+    /// `testdata/syzygy` are made this way.
+    /// # The test tables
+    ///```
+    /// use chess_logic::*;
+    /// let directory = std::env::temp_dir().join("syzygy_writer_doctest");
+    /// std::fs::create_dir_all(&directory).unwrap();
+    /// let table = EndgameTable::generate("KRvK").unwrap();
+    /// table.save_syzygy(&directory).unwrap();
+    /// for file in ["KRvK.rtbw", "KRvK.rtbz"] {
+    ///     let written = std::fs::read(directory.join(file)).unwrap();
+    ///     assert!(written == std::fs::read(std::path::Path::new("testdata/syzygy").join(file)).unwrap());
+    /// }
+    ///```
+    pub fn save_syzygy<P: AsRef<Path>>(&self, directory: P) -> Result<(), String> {
+        syzygy::write_tables(&self.signature, |board, turn| self.probe_board(board, turn), directory.as_ref())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<EndgameTable, String> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+        if bytes.len() < 6 || &bytes[..4] != MAGIC || bytes[4] != VERSION {
+            return Err(format!("{} is not an endgame table", path.display()));
+        }
+        let signature_end = 6 + bytes[5] as usize;
+        let signature = bytes.get(6..signature_end).and_then(|signature| std::str::from_utf8(signature).ok()).ok_or(format!("{} is truncated", path.display()))?;
+        let mut table = EndgameTable::new(signature)?;
+        if bytes.len() - signature_end != table.size() {
+            return Err(format!("{} has {} positions, {} expected", path.display(), bytes.len() - signature_end, table.size()));
+        }
+        table.values = bytes[signature_end..].to_vec();
+        Ok(table)
+    }
+}
+
+// The eight symmetries of the board, the first two keep pawns moving the right way
+fn transform_tile(tile: u8, transform: u8) -> u8 {
+    let (mut file, mut row) = (tile % 8, tile / 8);
+    if transform & 4 != 0 {
+        std::mem::swap(&mut file, &mut row);
+    }
+    if transform & 1 != 0 {
+        file = 7 - file;
+    }
+    if transform & 2 != 0 {
+        row = 7 - row;
+    }
+    row * 8 + file
+}
+
+// No castling rights and no en passant square, for positions set up piece by piece
+fn empty_game() -> GAME {
+    crate::init_game_from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap()
+}
+
+fn flip_colors(board: &[u8; 64]) -> [u8; 64] {
+    let mut flipped = [TYPES::NONE; 64];
+    for (tile, piece) in board.iter().enumerate().filter(|(_, piece)| **piece != TYPES::NONE) {
+        flipped[(7 - tile / 8) * 8 + tile % 8] = piece ^ (COLORS::WHITE | COLORS::BLACK);
+    }
+    flipped
+}
+
+/// A set of endgame tables, looked up by the material of a position.
+/// # Probing a king and pawn ending
+///```no_run
+/// use chess_logic::*;
+/// let mut tables = EndgameTables::new();
+/// tables.generate("KPvK").unwrap();                                  // also builds KQvK and KRvK
+/// assert_eq!(tables.signatures(), ["KPvK", "KQvK", "KRvK"]);
+/// assert_eq!(tables.get("KPvK").unwrap().longest_win().unwrap().1, Dtm::Win(55));
+/// let opposition = init_game_from_fen("8/8/8/8/8/4k3/4P3/4K3 w - - 0 1").unwrap();
+/// assert_eq!(tables.probe(&opposition), Some(Dtm::Draw));
+/// let mut game = init_game_from_fen("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1").unwrap();
+/// let (best_move, result) = tables.best_move(&game).unwrap();
+/// assert_eq!(result, Dtm::Win(21));
+/// make_move(&mut game, best_move);
+/// assert_eq!(tables.probe(&game), Some(Dtm::Loss(20)));
+///```
+#[derive(Default)]
+pub struct EndgameTables {
+    tables: HashMap<String, EndgameTable>,
+}
+
+impl EndgameTables {
+    pub fn new() -> EndgameTables {
+        EndgameTables::default()
+    }
+
+    /// Every `.cetb` table in a directory.
+    pub fn open<P: AsRef<Path>>(directory: P) -> Result<EndgameTables, String> {
+        let directory = directory.as_ref();
+        let mut tables = EndgameTables::new();
+        let listing = fs::read_dir(directory).map_err(|error| format!("cannot read {}: {}", directory.display(), error))?;
+        for path in listing.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            if path.extension().and_then(|extension| extension.to_str()) == Some("cetb") {
+                tables.insert(EndgameTable::load(&path)?);
+            }
+        }
+        Ok(tables)
+    }
+
+    pub fn insert(&mut self, table: EndgameTable) {
+        self.tables.insert(table.signature.clone(), table);
+    }
+
+    pub fn get(&self, signature: &str) -> Option<&EndgameTable> {
+        self.tables.get(signature)
+    }
+
+    pub fn signatures(&self) -> Vec<&str> {
+        let mut signatures: Vec<&str> = self.tables.keys().map(|signature| signature.as_str()).collect();
+        signatures.sort_unstable();
+        signatures
+    }
+
+    /// Distance to mate of a position whose material has a table, without mating material it is a draw.
+    pub fn probe(&self, game: &GAME) -> Option<Dtm> {
+        self.probe_board(&game.board, game.turn)
+    }
+
+    fn probe_board(&self, board: &[u8; 64], turn: u8) -> Option<Dtm> {
+        if is_insufficient_material(board) {
+            return Some(Dtm::Draw);
+        }
+        let name = board_material_name(board);
+        self.tables.get(&name).or_else(|| self.tables.get(&mirrored_signature(&name)))?.probe_board(board, turn)
+    }
+
+    /// The move that wins fastest, holds the draw or resists longest, with the result of the position.
+    pub fn best_move(&self, game: &GAME) -> Option<(Move, Dtm)> {
+        let root = self.probe(game)?;
+        let mut best: Option<(Move, i32)> = None;
+        for played in movegen::generate_legal_moves(game) {
+            let mut child = game.clone();
+            movegen::make_move(&mut child, played);
+            let rank = match self.probe(&child) {
+                Some(Dtm::Win(plies)) => Dtm::Loss(plies + 1).rank(),
+                Some(Dtm::Loss(plies)) => Dtm::Win(plies + 1).rank(),
+                Some(Dtm::Draw) => 0,
+                None => continue,
+            };
+            if best.is_none_or(|(_, best_rank)| rank > best_rank) {
+                best = Some((played, rank));
+            }
+        }
+        best.map(|(played, _)| (played, root))
+    }
+
+    /// Writes the table of a signature in the Syzygy format, like `EndgameTable::save_syzygy`.
+    /// A pawn ending also needs the tables of the material its captures and promotions leave,
+    /// which `generate` builds.
+    /// # The king and pawn test tables
+    ///```no_run
+    /// use chess_logic::*;
+    /// let directory = std::env::temp_dir().join("syzygy_pawn_writer_doctest");
+    /// std::fs::create_dir_all(&directory).unwrap();
+    /// let mut tables = EndgameTables::new();
+    /// tables.generate("KPvK").unwrap();
+    /// tables.save_syzygy("KPvK", &directory).unwrap();
+    /// for file in ["KPvK.rtbw", "KPvK.rtbz"] {
+    ///     let written = std::fs::read(directory.join(file)).unwrap();
+    ///     assert!(written == std::fs::read(std::path::Path::new("testdata/syzygy").join(file)).unwrap());
+    /// }
+    ///```
+    pub fn save_syzygy<P: AsRef<Path>>(&self, signature: &str, directory: P) -> Result<(), String> {
+        if self.get(signature).is_none() {
+            return Err(format!("no table for {}", signature));
+        }
+        syzygy::write_tables(signature, |board, turn| self.probe_board(board, turn), directory.as_ref())
+    }
+
+    /// Builds the table for a signature, and first those for the material its captures and
+    /// promotions lead to. Tables already in the set are reused.
+    pub fn generate(&mut self, signature: &str) -> Result<(), String> {
+        if self.tables.contains_key(signature) {
+            return Ok(());
+        }
+        let mut table = EndgameTable::new(signature)?;
+        let size = table.size();
+
+        // The moves of every position: other positions of this table by index, or the
+        // final result of a position with other material
+        let mut child_start = vec![0u32; size + 1];
+        let mut children: Vec<u32> = Vec::new();
+        let mut converted: Vec<(u32, Dtm)> = Vec::new();
+        let mut remaining = vec![0u8; size];
+        let mut mated = Vec::new();
+        let mut pseudo_moves = Vec::new();
+        let mut game = empty_game();
+        for index in 0..size {
+            child_start[index] = children.len() as u32;
+            let (squares, turn) = table.squares_of_index(index);
+            if !table.is_valid(&mut game, &squares, turn) {
+                continue;
+            }
+            table.values[index] = DRAW;
+            table.set_position(&mut game, &squares, turn);
+            pseudo_moves.clear();
+            movegen::generate_moves(&game, GenerationKind::All, &mut pseudo_moves);
+            let mut legal_moves = 0;
+            for played in pseudo_moves.iter() {
+                if !movegen::make_move(&mut game, *played) {
+                    continue;
+                }
+                legal_moves += 1;
+                let child_board = game.board;
+                let child_turn = game.turn;
+                movegen::unmake_move(&mut game);
+
+                match table.index_of_board(&child_board, child_turn) {
+                    Some(child) => children.push(child as u32),
+                    None => match self.converted_result(&child_board, child_turn)? {
+                        Dtm::Draw => {}
+                        result => converted.push((index as u32, result)),
+                    },
+                }
+            }
+            remaining[index] = legal_moves;
+            if legal_moves == 0 && game.check {
+                table.values[index] = 1;
+                mated.push(index as u32);
+            }
+        }
+        child_start[size] = children.len() as u32;
+
+        // Who can reach each position, one entry per move
+        let mut parent_start = vec![0u32; size + 1];
+        for child in children.iter() {
+            parent_start[*child as usize + 1] += 1;
+        }
+        for index in 0..size {
+            parent_start[index + 1] += parent_start[index];
+        }
+        let mut parents = vec![0u32; children.len()];
+        let mut filled = parent_start.clone();
+        for index in 0..size {
+            for child in children[child_start[index] as usize..child_start[index + 1] as usize].iter() {
+                parents[filled[*child as usize] as usize] = index as u32;
+                filled[*child as usize] += 1;
+            }
+        }
+        drop(children);
+
+        // Positions become known in the order of their distance: a move to a lost position
+        // wins, a position whose every move reaches a won one is lost
+        let mut converted_by_plies: Vec<Vec<(u32, Dtm)>> = Vec::new();
+        for (parent, result) in converted {
+            let plies = match result {
+                Dtm::Win(plies) | Dtm::Loss(plies) => plies as usize,
+                Dtm::Draw => continue,
+            };
+            if converted_by_plies.len() <= plies {
+                converted_by_plies.resize(plies + 1, Vec::new());
+            }
+            converted_by_plies[plies].push((parent, result));
+        }
+        let mut frontier = mated;
+        let mut plies = 0;
+        while !frontier.is_empty() || plies < converted_by_plies.len() {
+            let mut next = Vec::new();
+            let mut resolve = |parent: u32, child_lost: bool, values: &mut Vec<u8>| {
+                let parent = parent as usize;
+                if values[parent] != DRAW {
+                    return;
+                }
+                if !child_lost {
+                    remaining[parent] -= 1;
+                    if remaining[parent] > 0 {
+                        return;
+                    }
+                }
+                values[parent] = (plies + 2) as u8;
+                next.push(parent as u32);
+            };
+            for child in frontier.iter() {
+                let child = *child as usize;
+                for parent in parents[parent_start[child] as usize..parent_start[child + 1] as usize].iter() {
+                    resolve(*parent, plies % 2 == 0, &mut table.values);
+                }
+            }
+            if let Some(events) = converted_by_plies.get(plies) {
+                for (parent, result) in events.iter() {
+                    resolve(*parent, matches!(result, Dtm::Loss(_)), &mut table.values);
+                }
+            }
+            if plies + 2 >= INVALID as usize {
+                return Err(format!("{} has mates longer than the table format can store", signature));
+            }
+            frontier = next;
+            plies += 1;
+        }
+
+        self.tables.insert(signature.to_string(), table);
+        Ok(())
+    }
+
+    // Result after a capture or promotion, building the table it needs first
+    fn converted_result(&mut self, board: &[u8; 64], turn: u8) -> Result<Dtm, String> {
+        if let Some(result) = self.probe_board(board, turn) {
+            return Ok(result);
+        }
+        let name = board_material_name(board);
+        let mirrored = mirrored_signature(&name);
+        // The side with more material becomes white
+        let signature = if material_value(&name) >= material_value(&mirrored) { name } else { mirrored };
+        self.generate(&signature)?;
+        self.probe_board(board, turn).ok_or(format!("no result for {} after generating it", signature))
+    }
+}
+
+fn mirrored_signature(signature: &str) -> String {
+    let mut sides = signature.split('v');
+    let white = sides.next().unwrap_or("");
+    let black = sides.next().unwrap_or("");
+    format!("{}v{}", black, white)
+}
+
+// White's material minus black's in pawns, to orient generated tables
+fn material_value(signature: &str) -> i32 {
+    let mut sides = signature.split('v');
+    let value = |side: &str| -> i32 {
+        side.chars().map(|letter| match letter {
+            'Q' => 9,
+            'R' => 5,
+            'B' | 'N' => 3,
+            'P' => 1,
+            _ => 0,
+        }).sum()
+    };
+    value(sides.next().unwrap_or("")) - value(sides.next().unwrap_or(""))
+}
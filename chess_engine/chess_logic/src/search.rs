@@ -0,0 +1,971 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::COLORS;
+use crate::TYPES;
+use crate::GAME;
+use crate::evaluation::evaluate;
+use crate::movegen::{self, Move};
+#[cfg(feature = "nnue")]
+use crate::nnue::{Network, NnueEvaluator};
+use crate::notation::pv_to_san;
+use crate::move_picker::{HistoryTable, MovePicker};
+use crate::piece_color_index;
+use crate::skill::{next_random, Skill};
+use crate::time_manager::{Clock, TimeManager, WallClock};
+use crate::transposition_table::{Bound, TranspositionTable};
+
+pub const MATE_SCORE: i32 = 30000;
+const INFINITY: i32 = 32000;
+const MAX_PLY: usize = 128;
+const HISTORY_LIMIT: i32 = 16384;
+const ASPIRATION_WINDOW: i32 = 25;
+const REVERSE_FUTILITY_DEPTH: i32 = 6;
+const REVERSE_FUTILITY_MARGIN: i32 = 80;
+const FUTILITY_DEPTH: i32 = 3;
+const FUTILITY_MARGIN: i32 = 110;
+const TIME_CHECK_INTERVAL: u64 = 1024;                          // nodes between clock readings
+const SKILL_LINES: usize = 4;                                   // root lines a limited skill chooses from
+
+/// Every selective technique can be switched off on its own to measure what it is worth.
+/// # Pruning and reductions
+///```
+/// use chess_logic::{init_game_from_fen, Searcher, SearchOptions, SearchLimits};
+/// let game = init_game_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+/// let limits = SearchLimits { depth: Some(4), ..Default::default() };
+/// let plain = Searcher::new(SearchOptions::without_selectivity()).search(&game, limits);
+/// let selective = Searcher::new(SearchOptions::default()).search(&game, limits);
+/// assert!(selective.nodes < plain.nodes);
+///```
+#[derive(Clone, Copy, Debug)]
+pub struct SearchOptions {
+    pub move_ordering: bool,                                    // off searches main search moves in generation order
+    pub hash_size_mb: usize,
+    pub null_move_pruning: bool,
+    pub late_move_reductions: bool,
+    pub futility_pruning: bool,
+    pub reverse_futility_pruning: bool,
+    pub check_extensions: bool,
+    pub aspiration_windows: bool,
+    pub threads: usize,                                         // more than one runs lazy SMP helper searches
+    pub multi_pv: usize,                                        // number of best root moves searched with full lines
+    pub skill: Skill,
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions {
+            move_ordering: true,
+            hash_size_mb: 16,
+            null_move_pruning: true,
+            late_move_reductions: true,
+            futility_pruning: true,
+            reverse_futility_pruning: true,
+            check_extensions: true,
+            aspiration_windows: true,
+            threads: 1,
+            multi_pv: 1,
+            skill: Skill::default(),
+        }
+    }
+}
+
+impl SearchOptions {
+    /// Plain alpha-beta with move ordering, no pruning, reductions or extensions.
+    pub fn without_selectivity() -> SearchOptions {
+        SearchOptions {
+            null_move_pruning: false,
+            late_move_reductions: false,
+            futility_pruning: false,
+            reverse_futility_pruning: false,
+            check_extensions: false,
+            aspiration_windows: false,
+            ..SearchOptions::default()
+        }
+    }
+}
+
+/// Without any limit the search runs until the maximum depth. Clock times are
+/// those of the UCI `go` command, only the side to move's are used.
+/// # Short on time
+///```
+/// use chess_logic::*;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// let game = init_game_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+/// let clock = MockClock::with_tick(Duration::from_millis(1));
+/// let mut searcher = Searcher::new(SearchOptions::default());
+/// searcher.set_clock(Arc::new(clock.clone()));
+/// let limits = SearchLimits {
+///     white_time: Some(Duration::from_millis(100)),
+///     white_increment: Some(Duration::from_millis(10)),
+///     ..Default::default()
+/// };
+/// let result = searcher.search(&game, limits);
+/// assert!(result.best_move.is_some());
+/// assert!(clock.now() < Duration::from_millis(100));
+///```
+/// # Single legal move
+///```
+/// use chess_logic::*;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// let game = init_game_from_fen("k7/8/8/8/8/8/8/1R5K b - - 0 1").unwrap();
+/// let mut searcher = Searcher::new(SearchOptions::default());
+/// searcher.set_clock(Arc::new(MockClock::new()));
+/// let result = searcher.search(&game, SearchLimits { black_time: Some(Duration::from_secs(60)), ..Default::default() });
+/// assert_eq!(result.best_move.unwrap().to_uci(), "a8a7");
+/// assert_eq!(result.depth, 1);
+///```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub move_time: Option<Duration>,
+    pub white_time: Option<Duration>,
+    pub black_time: Option<Duration>,
+    pub white_increment: Option<Duration>,
+    pub black_increment: Option<Duration>,
+    pub moves_to_go: Option<u32>,
+    pub mate: Option<u8>,                                       // stop once a mate in this many moves is found
+}
+
+impl SearchLimits {
+    fn time_manager(&self, clock: Arc<dyn Clock>, turn: u8) -> Option<TimeManager> {
+        let (time_left, increment) = if turn == COLORS::WHITE {
+            (self.white_time, self.white_increment)
+        } else {
+            (self.black_time, self.black_increment)
+        };
+        if let Some(move_time) = self.move_time {
+            Some(TimeManager::for_move_time(clock, move_time))
+        } else {
+            time_left.map(|time_left| TimeManager::new(clock, time_left, increment.unwrap_or_default(), self.moves_to_go))
+        }
+    }
+}
+
+/// A search score as reported to the user, mates are counted in moves and negative when getting mated.
+///```
+/// use chess_logic::{Score, MATE_SCORE};
+/// assert_eq!(Score::from_search(35), Score::Centipawns(35));
+/// assert_eq!(Score::from_search(MATE_SCORE - 3), Score::Mate(2));
+/// assert_eq!(Score::from_search(-MATE_SCORE + 2), Score::Mate(-1));
+/// assert_eq!(Score::from_search(MATE_SCORE - 1).to_string(), "mate 1");
+///```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    Mate(i32),
+}
+
+impl Score {
+    pub fn from_search(score: i32) -> Score {
+        if score >= MATE_SCORE - MAX_PLY as i32 {
+            Score::Mate((MATE_SCORE - score + 1) / 2)
+        } else if score <= -MATE_SCORE + MAX_PLY as i32 {
+            Score::Mate(-(MATE_SCORE + score) / 2)
+        } else {
+            Score::Centipawns(score)
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Score::Centipawns(centipawns) => write!(f, "cp {}", centipawns),
+            Score::Mate(moves) => write!(f, "mate {}", moves),
+        }
+    }
+}
+
+/// One ranked root move of a MultiPV search and the line expected to follow it.
+/// # Several lines
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+/// let mut searcher = Searcher::new(SearchOptions { multi_pv: 3, ..Default::default() });
+/// let result = searcher.search(&game, SearchLimits { depth: Some(4), ..Default::default() });
+/// assert_eq!(result.lines.len(), 3);
+/// assert_eq!(result.lines[0].score(), Score::Mate(1));
+/// assert_eq!(result.lines[0].san(&game), vec!["Ra8#"]);
+/// assert!(result.lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+/// assert_ne!(result.lines[1].pv[0], result.lines[2].pv[0]);
+///```
+#[derive(Clone, Debug, Default)]
+pub struct PvLine {
+    pub score: i32,
+    pub depth: u8,
+    pub nodes: u64,                                             // searched when this line was finished
+    pub pv: Vec<Move>,
+}
+
+impl PvLine {
+    pub fn score(&self) -> Score {
+        Score::from_search(self.score)
+    }
+
+    pub fn uci(&self) -> Vec<String> {
+        self.pv.iter().map(|played| played.to_uci()).collect()
+    }
+
+    pub fn san(&self, game: &GAME) -> Vec<String> {
+        pv_to_san(game, &self.pv)
+    }
+}
+
+/// The best line is repeated in the top-level fields, `lines` holds all of them best first.
+#[derive(Clone, Debug, Default)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: i32,                                             // centipawns for the side to move, mates near MATE_SCORE
+    pub depth: u8,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+    pub lines: Vec<PvLine>,
+}
+
+pub type InfoHandler = Box<dyn FnMut(&SearchResult) + Send>;
+
+/// Iterative deepening alpha-beta search. With more than one thread the extra
+/// threads search the same position in the background and only share what they
+/// find through the transposition table, the result always comes from the main thread.
+/// # Threads
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+/// let limits = SearchLimits { depth: Some(4), ..Default::default() };
+/// let single = Searcher::new(SearchOptions::default()).search(&game, limits);
+/// assert_eq!(Searcher::new(SearchOptions::default()).search(&game, limits).nodes, single.nodes);
+///
+/// let mut searcher = Searcher::new(SearchOptions { threads: 3, ..Default::default() });
+/// let result = searcher.search(&game, limits);
+/// assert_eq!(result.depth, 4);
+/// assert!(generate_legal_moves(&game).contains(&result.best_move.unwrap()));
+///```
+pub struct Searcher {
+    pub options: SearchOptions,
+    table: Arc<TranspositionTable>,
+    helpers: Vec<Searcher>,
+    stop: Arc<AtomicBool>,                                      // set from outside, only the caller clears it
+    finished: Arc<AtomicBool>,                                  // set by the main thread to stop the helpers
+    ponder_hit: Arc<AtomicBool>,                                // set from outside when the predicted move was played
+    ponder_limits: Option<(SearchLimits, u8)>,                  // clock of a ponder search, started on the ponder hit
+    history: Box<HistoryTable>,
+    killers: [[Move; 2]; MAX_PLY],
+    pv_table: Vec<[Move; MAX_PLY]>,
+    pv_length: [usize; MAX_PLY],
+    reductions: Vec<[i32; 64]>,                                 // [depth][move number]
+    excluded_root_moves: Vec<Move>,                             // best moves of the MultiPV lines already found
+    search_moves: Vec<Move>,                                    // when not empty only these root moves are searched
+    info_handler: Option<InfoHandler>,
+    clock: Arc<dyn Clock>,
+    time: Option<TimeManager>,
+    nodes: u64,
+    node_limit: u64,
+    mate_limit: Option<u8>,
+    stopped: bool,
+    random: u64,                                                // for the choices of a limited skill
+    #[cfg(feature = "nnue")]
+    network: Option<NnueEvaluator>,                             // replaces the handcrafted evaluation when set
+}
+
+impl Searcher {
+    pub fn new(options: SearchOptions) -> Searcher {
+        let table = Arc::new(TranspositionTable::new(options.hash_size_mb));
+        Searcher::with_shared_state(options, table, Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)))
+    }
+
+    fn with_shared_state(options: SearchOptions, table: Arc<TranspositionTable>, stop: Arc<AtomicBool>, finished: Arc<AtomicBool>) -> Searcher {
+        Searcher {
+            options,
+            table,
+            helpers: Vec::new(),
+            stop,
+            finished,
+            ponder_hit: Arc::new(AtomicBool::new(false)),
+            ponder_limits: None,
+            history: Box::new([[[0; 64]; 64]; 2]),
+            killers: [[Move::NONE; 2]; MAX_PLY],
+            pv_table: vec![[Move::NONE; MAX_PLY]; MAX_PLY],
+            pv_length: [0; MAX_PLY],
+            reductions: late_move_reduction_table(),
+            excluded_root_moves: Vec::new(),
+            search_moves: Vec::new(),
+            info_handler: None,
+            clock: Arc::new(WallClock::new()),
+            time: None,
+            nodes: 0,
+            node_limit: u64::MAX,
+            mate_limit: None,
+            stopped: false,
+            random: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_nanos() as u64) | 1,
+            #[cfg(feature = "nnue")]
+            network: None,
+        }
+    }
+
+    /// Forgets everything learned from earlier searches, call it when a new game starts.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        *self.history = [[[0; 64]; 64]; 2];
+        self.killers = [[Move::NONE; 2]; MAX_PLY];
+        self.helpers.clear();                                   // recreated with empty statistics on the next search
+    }
+
+    /// Setting the returned flag makes a running search return as soon as possible, e.g. on a UCI `stop`.
+    /// The flag stays set, so searches return at once until the caller clears it again.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// Setting the returned flag tells a running `ponder` search that the predicted move was played,
+    /// like the UCI `ponderhit`. Like the stop flag it is only cleared by the caller.
+    pub fn ponder_hit_handle(&self) -> Arc<AtomicBool> {
+        self.ponder_hit.clone()
+    }
+
+    /// Replaces the wall clock used for timed searches, tests use a MockClock.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Restricts the root to the given moves, like the UCI `go searchmoves`. An empty list searches every move.
+    pub fn set_search_moves(&mut self, moves: &[Move]) {
+        self.search_moves = moves.to_vec();
+    }
+
+    /// Makes the choices of a limited skill repeatable.
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.random = seed | 1;                                 // xorshift never leaves zero
+    }
+
+    /// Called with the result of every completed iteration, so progress can be reported while searching.
+    pub fn set_info_handler(&mut self, handler: InfoHandler) {
+        self.info_handler = Some(handler);
+    }
+
+    /// Evaluates with the network instead of the handcrafted evaluation, or with the
+    /// handcrafted evaluation again for None.
+    /// # A network that only knows queens
+    ///```
+    /// use chess_logic::*;
+    /// use std::sync::Arc;
+    /// let hidden_size = 16;
+    /// let mut feature_weights = vec![0i16; 768 * hidden_size];
+    /// for square in 0..64 {
+    ///     feature_weights[(4 * 64 + square) * hidden_size] = 100;
+    /// }
+    /// let mut output_weights = vec![0i16; 2 * hidden_size];
+    /// output_weights[0] = 300;
+    /// output_weights[hidden_size] = -300;
+    /// let network = Network::from_weights(hidden_size, feature_weights, vec![0; hidden_size], output_weights, 0).unwrap();
+    ///
+    /// let game = init_game_from_fen("4k3/8/8/1q6/8/8/8/1Q2K3 w - - 0 1").unwrap();
+    /// let mut searcher = Searcher::new(SearchOptions { threads: 2, ..Default::default() });
+    /// searcher.set_network(Some(Arc::new(network)));
+    /// let result = searcher.search(&game, SearchLimits { depth: Some(3), ..Default::default() });
+    /// assert_eq!(result.best_move.unwrap().to_uci(), "b1b5");
+    /// assert_eq!(result.score, 735);
+    ///```
+    #[cfg(feature = "nnue")]
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.network = network.map(NnueEvaluator::new);
+        self.helpers.clear();                                   // recreated with the new evaluation on the next search
+    }
+
+    /// Iterative deepening search of the position, returning the result of the deepest completed iteration.
+    ///```
+    /// use chess_logic::*;
+    /// let game = init_game_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    /// let mut searcher = Searcher::new(SearchOptions::default());
+    /// let result = searcher.search(&game, SearchLimits { depth: Some(3), ..Default::default() });
+    /// assert_eq!(result.best_move.unwrap().to_uci(), "a1a8");
+    /// assert_eq!(result.score, MATE_SCORE - 1);
+    ///```
+    pub fn search(&mut self, game: &GAME, limits: SearchLimits) -> SearchResult {
+        let mut game = game.clone();
+        movegen::sync_derived_state(&mut game);
+        let skill = self.options.skill;
+        let multi_pv = self.options.multi_pv;
+        let limits = if skill.is_limited() {
+            self.options.multi_pv = multi_pv.max(SKILL_LINES);
+            SearchLimits {
+                depth: Some(limits.depth.map_or(skill.depth_limit(), |depth| depth.min(skill.depth_limit()))),
+                nodes: Some(limits.nodes.map_or(skill.node_limit(), |nodes| nodes.min(skill.node_limit()))),
+                ..limits
+            }
+        } else {
+            limits
+        };
+        self.finished.store(false, Ordering::Relaxed);
+        self.prepare(&limits, game.turn);
+
+        let helper_count = self.options.threads.max(1) - 1;
+        if self.helpers.len() != helper_count {
+            let options = SearchOptions { multi_pv: 1, ..self.options };
+            self.helpers = (0..helper_count)
+                .map(|_| Searcher::with_shared_state(options, self.table.clone(), self.stop.clone(), self.finished.clone()))
+                .collect();
+            #[cfg(feature = "nnue")]
+            for helper in self.helpers.iter_mut() {
+                helper.network = self.network.clone();
+            }
+        }
+        // A mate in n moves needs 2n - 1 plies, one more lets the search see the mate itself
+        let max_depth = limits.depth.or(limits.mate.map(|moves| moves.saturating_mul(2))).unwrap_or(MAX_PLY as u8 - 1).clamp(1, MAX_PLY as u8 - 1);
+        // Helpers are stopped by the main thread, so they only keep its depth limit
+        let helper_limits = SearchLimits { depth: limits.depth, ..Default::default() };
+
+        let mut helpers = std::mem::take(&mut self.helpers);
+        let mut result = thread::scope(|scope| {
+            for (index, helper) in helpers.iter_mut().enumerate() {
+                let mut helper_game = game.clone();
+                helper.search_moves = self.search_moves.clone();
+                scope.spawn(move || {
+                    helper.prepare(&helper_limits, helper_game.turn);
+                    // Every other helper starts one ply deeper so the threads spread over different depths
+                    helper.iterative_deepening(&mut helper_game, 1 + (index as u8 + 1) % 2, max_depth);
+                });
+            }
+            let result = self.iterative_deepening(&mut game, 1, max_depth);
+            self.finished.store(true, Ordering::Relaxed);
+            result
+        });
+        result.nodes += helpers.iter().map(|helper| helper.nodes).sum::<u64>();
+        self.helpers = helpers;
+        self.options.multi_pv = multi_pv;
+        if skill.is_limited() && !result.lines.is_empty() {
+            self.play_weaker(&mut game, &mut result, skill);
+        }
+        result
+    }
+
+    /// Searches the position after the predicted move while the opponent is still thinking. Until
+    /// the ponder hit flag is set the clock limits are ignored and only stop, depth, nodes or mate
+    /// end the search. From the ponder hit on the clock limits apply as if the search had just
+    /// started, so the time spent pondering is not taken from the engine's own clock.
+    /// # Pondering
+    ///```
+    /// use chess_logic::*;
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::Ordering;
+    /// use std::time::Duration;
+    /// let game = init_game_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    /// let clock = MockClock::with_tick(Duration::from_millis(1));
+    /// let mut searcher = Searcher::new(SearchOptions::default());
+    /// searcher.set_clock(Arc::new(clock.clone()));
+    /// let limits = SearchLimits { depth: Some(4), move_time: Some(Duration::from_millis(20)), ..Default::default() };
+    /// // Still the opponent's time, the clock is never read and the search reaches its depth
+    /// assert_eq!(searcher.ponder(&game, limits).depth, 4);
+    /// assert_eq!(clock.now(), Duration::from_millis(0));
+    ///
+    /// // After the ponder hit the move time counts
+    /// searcher.ponder_hit_handle().store(true, Ordering::Relaxed);
+    /// let result = searcher.ponder(&game, SearchLimits { depth: None, ..limits });
+    /// assert!(result.best_move.is_some());
+    /// assert!(clock.now() < Duration::from_millis(100));
+    ///```
+    pub fn ponder(&mut self, game: &GAME, limits: SearchLimits) -> SearchResult {
+        self.ponder_limits = Some((limits, game.turn));
+        let result = self.search(game, SearchLimits { depth: limits.depth, nodes: limits.nodes, mate: limits.mate, ..Default::default() });
+        self.ponder_limits = None;
+        result
+    }
+
+    // Once the predicted move is played the clock of a ponder search starts
+    fn check_ponder_hit(&mut self) {
+        if self.ponder_limits.is_some() && self.ponder_hit.load(Ordering::Relaxed) {
+            let (limits, turn) = self.ponder_limits.take().expect("checked above");
+            self.time = limits.time_manager(self.clock.clone(), turn);
+        }
+    }
+
+    // Replaces the best move by one of the other lines found, or by a small mistake
+    fn play_weaker(&mut self, game: &mut GAME, result: &mut SearchResult, skill: Skill) {
+        let scores: Vec<i32> = result.lines.iter().map(|line| line.score).collect();
+        let chosen = &result.lines[skill.pick_line(&scores, &mut self.random)];
+        result.best_move = Some(chosen.pv[0]);
+        result.score = chosen.score;
+        result.pv = chosen.pv.clone();
+        if next_random(&mut self.random) % 100 < skill.blunder_percent() {
+            if let Some((played, score)) = self.small_mistake(game, skill) {
+                result.best_move = Some(played);
+                result.score = score;
+                result.pv = vec![played];
+            }
+        }
+    }
+
+    // A random move that loses something, but at most the skill's limit by a quiescence
+    // search, and that does not allow a mate in one
+    fn small_mistake(&mut self, game: &mut GAME, skill: Skill) -> Option<(Move, i32)> {
+        self.finished.store(false, Ordering::Relaxed);          // the helpers are done, the search is not
+        self.node_limit = u64::MAX;
+        self.time = None;
+        self.stopped = false;
+        let mut scored = Vec::new();
+        for played in movegen::generate_legal_moves(game) {
+            self.make_move(game, played);
+            let score = -self.quiescence(game, -INFINITY, INFINITY, 1);
+            let allows_mate = allows_mate_in_one(game);
+            self.unmake_move(game);
+            scored.push((played, score, allows_mate));
+        }
+        self.finished.store(true, Ordering::Relaxed);
+        if self.stopped {
+            return None;
+        }
+        let best = scored.iter().map(|(_, score, _)| *score).max()?;
+        let mistakes: Vec<(Move, i32)> = scored.into_iter()
+            .filter(|(_, score, allows_mate)| !allows_mate && *score < best && best - score <= skill.max_blunder_loss())
+            .map(|(played, score, _)| (played, score))
+            .collect();
+        if mistakes.is_empty() {
+            return None;
+        }
+        Some(mistakes[next_random(&mut self.random) as usize % mistakes.len()])
+    }
+
+    fn prepare(&mut self, limits: &SearchLimits, turn: u8) {
+        self.nodes = 0;
+        self.node_limit = limits.nodes.unwrap_or(u64::MAX);
+        self.mate_limit = limits.mate;
+        self.stopped = false;
+        self.time = limits.time_manager(self.clock.clone(), turn);
+        self.check_ponder_hit();
+        self.killers = [[Move::NONE; 2]; MAX_PLY];
+        for value in self.history.iter_mut().flatten().flatten() {
+            *value /= 2;
+        }
+    }
+
+    fn iterative_deepening(&mut self, game: &mut GAME, first_depth: u8, max_depth: u8) -> SearchResult {
+        if game.check && movegen::generate_legal_moves(game).is_empty() {
+            return SearchResult { score: -MATE_SCORE, ..Default::default() };
+        }
+        #[cfg(feature = "nnue")]
+        if let Some(network) = &mut self.network {
+            network.refresh(game);
+        }
+        let mut root_moves = movegen::generate_legal_moves(game);
+        if !self.search_moves.is_empty() {
+            root_moves.retain(|played| self.search_moves.contains(played));
+        }
+        if root_moves.is_empty() {
+            return SearchResult::default();
+        }
+        let line_count = self.options.multi_pv.clamp(1, root_moves.len().max(1));
+        let mut result = SearchResult::default();
+        for depth in first_depth.min(max_depth)..=max_depth {
+            let mut lines: Vec<PvLine> = Vec::new();
+            self.excluded_root_moves.clear();
+            for line_index in 0..line_count {
+                let previous_score = result.lines.get(line_index).map_or(0, |line| line.score);
+                let score = if self.options.aspiration_windows && depth >= 4 && previous_score.abs() < MATE_SCORE - MAX_PLY as i32 {
+                    self.aspiration_search(game, depth as i32, previous_score)
+                } else {
+                    self.negamax(game, depth as i32, -INFINITY, INFINITY, 0)
+                };
+                if self.stopped || self.pv_length[0] == 0 {
+                    break;
+                }
+                self.excluded_root_moves.push(self.pv_table[0][0]);
+                lines.push(PvLine { score, depth, nodes: self.nodes, pv: self.pv_table[0][..self.pv_length[0]].to_vec() });
+            }
+            // A partly searched iteration only counts when nothing better is known
+            if lines.is_empty() || (self.stopped && !result.lines.is_empty()) {
+                break;
+            }
+            lines.sort_by_key(|line| -line.score);
+            result = SearchResult {
+                best_move: Some(lines[0].pv[0]),
+                score: lines[0].score,
+                depth,
+                nodes: self.nodes,
+                pv: lines[0].pv.clone(),
+                lines,
+            };
+            if let Some(handler) = &mut self.info_handler {
+                handler(&result);
+            }
+            let score = result.score;
+            if self.stopped || (line_count == 1 && score.abs() >= MATE_SCORE - depth as i32) {
+                break;
+            }
+            if self.mate_limit.is_some_and(|moves| matches!(result.lines[0].score(), Score::Mate(mate) if mate > 0 && mate <= moves as i32)) {
+                break;
+            }
+            self.check_ponder_hit();
+            if let Some(time) = &mut self.time {
+                if let Some(best_move) = result.best_move {
+                    time.update(best_move, score);
+                }
+                // With a single legal move there is nothing to decide, keep the time for later
+                if root_moves.len() == 1 || time.soft_limit_reached() {
+                    break;
+                }
+            }
+        }
+
+        if result.best_move.is_none() {
+            result.best_move = root_moves.first().copied();
+        }
+        result.nodes = self.nodes;
+        result
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if self.nodes >= self.node_limit || self.stop.load(Ordering::Relaxed) || self.finished.load(Ordering::Relaxed) {
+            self.stopped = true;
+        }
+        if self.nodes.is_multiple_of(TIME_CHECK_INTERVAL) {
+            self.check_ponder_hit();
+            if self.time.as_ref().is_some_and(|time| time.hard_limit_reached()) {
+                self.stopped = true;
+            }
+        }
+        self.stopped
+    }
+
+    // Searches with a narrow window around the previous iteration's score, widening it on a fail
+    fn aspiration_search(&mut self, game: &mut GAME, depth: i32, previous_score: i32) -> i32 {
+        let mut delta = ASPIRATION_WINDOW;
+        let mut alpha = (previous_score - delta).max(-INFINITY);
+        let mut beta = (previous_score + delta).min(INFINITY);
+        loop {
+            let score = self.negamax(game, depth, alpha, beta, 0);
+            if self.stopped {
+                return score;
+            }
+            if score <= alpha {
+                alpha = (score - delta).max(-INFINITY);
+            } else if score >= beta {
+                beta = (score + delta).min(INFINITY);
+            } else {
+                return score;
+            }
+            delta *= 2;
+        }
+    }
+
+    fn negamax(&mut self, game: &mut GAME, mut depth: i32, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        self.pv_length[ply] = ply;
+        if ply > 0 && (game.halfmove_clock >= 100 || movegen::is_repetition(game)) {
+            return 0;
+        }
+        if ply >= MAX_PLY - 1 {
+            return self.evaluate(game);
+        }
+        let in_check = game.check;
+        if in_check && self.options.check_extensions {
+            depth += 1;
+        }
+        if depth <= 0 {
+            return self.quiescence(game, alpha, beta, ply);
+        }
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        let pv_node = beta - alpha > 1;
+        let mut hash_move = Move::NONE;
+        if let Some(entry) = self.table.probe(game.hash) {
+            hash_move = entry.best_move();
+            if !pv_node && entry.depth >= depth {
+                let score = score_from_table(entry.score, ply);
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if cutoff {
+                    return score;
+                }
+            }
+        }
+
+        let static_eval = if in_check { -INFINITY } else { self.evaluate(game) };
+        if !pv_node && !in_check {
+            if self.options.reverse_futility_pruning
+                && depth <= REVERSE_FUTILITY_DEPTH
+                && beta.abs() < MATE_SCORE - MAX_PLY as i32
+                && static_eval - REVERSE_FUTILITY_MARGIN * depth >= beta {
+                return static_eval;
+            }
+
+            // Zugzwang is common with only king and pawns left, there passing is not a safe lower bound
+            if self.options.null_move_pruning
+                && depth >= 3
+                && static_eval >= beta
+                && !movegen::last_move_was_null(game)
+                && has_non_pawn_material(game) {
+                let reduction = 3 + depth / 6;
+                movegen::make_null_move(game);
+                let score = -self.negamax(game, depth - 1 - reduction, -beta, -beta + 1, ply + 1);
+                movegen::unmake_null_move(game);
+                if self.stopped {
+                    return 0;
+                }
+                if score >= beta {
+                    return if score >= MATE_SCORE - MAX_PLY as i32 { beta } else { score };
+                }
+            }
+        }
+        let futile = self.options.futility_pruning
+            && !pv_node
+            && !in_check
+            && depth <= FUTILITY_DEPTH
+            && static_eval + FUTILITY_MARGIN * depth <= alpha;
+
+        let original_alpha = alpha;
+        let mut picker = MovePicker::new(game, hash_move, self.killers[ply], self.options.move_ordering);
+        let mut best_score = -INFINITY;
+        let mut best_move = Move::NONE;
+        let mut legal_moves = 0;
+        let mut quiets_tried: Vec<Move> = Vec::new();
+
+        while let Some(played) = picker.next(game, &self.history) {
+            if ply == 0 && (self.excluded_root_moves.contains(&played) || (!self.search_moves.is_empty() && !self.search_moves.contains(&played))) {
+                continue;
+            }
+            let quiet = played.promotion == 0 && !movegen::is_capture(game, played);
+            if !self.make_move(game, played) {
+                continue;
+            }
+            legal_moves += 1;
+            let gives_check = game.check;
+            if futile && quiet && !gives_check && legal_moves > 1 {
+                self.unmake_move(game);
+                continue;
+            }
+
+            let new_depth = depth - 1;
+            let score = if legal_moves == 1 {
+                -self.negamax(game, new_depth, -beta, -alpha, ply + 1)
+            } else {
+                let mut reduction = 0;
+                if self.options.late_move_reductions && depth >= 3 && legal_moves > 3 && quiet && !in_check && !gives_check {
+                    reduction = self.reductions[depth.min(63) as usize][legal_moves.min(63)];
+                    if pv_node {
+                        reduction -= 1;
+                    }
+                    reduction = reduction.clamp(0, new_depth - 1);
+                }
+                let mut score = -self.negamax(game, new_depth - reduction, -alpha - 1, -alpha, ply + 1);
+                if score > alpha && reduction > 0 {
+                    score = -self.negamax(game, new_depth, -alpha - 1, -alpha, ply + 1);
+                }
+                if score > alpha && score < beta {
+                    score = -self.negamax(game, new_depth, -beta, -alpha, ply + 1);
+                }
+                score
+            };
+            self.unmake_move(game);
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = played;
+                if score > alpha {
+                    alpha = score;
+                    self.update_pv(ply, played);
+                    if alpha >= beta {
+                        if quiet {
+                            self.update_quiet_statistics(game, played, &quiets_tried, depth, ply);
+                        }
+                        break;
+                    }
+                }
+            }
+            if quiet {
+                quiets_tried.push(played);
+            }
+        }
+
+        if legal_moves == 0 {
+            return if in_check { -MATE_SCORE + ply as i32 } else { 0 };
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        if ply > 0 || self.excluded_root_moves.is_empty() {
+            self.table.store(game.hash, depth, score_to_table(best_score, ply), bound, best_move);
+        }
+        best_score
+    }
+
+    fn quiescence(&mut self, game: &mut GAME, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        self.pv_length[ply] = ply;
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+        if ply >= MAX_PLY - 1 {
+            return self.evaluate(game);
+        }
+
+        // The quiescence search is always ordered, without MVV-LVA it explodes in busy positions
+        let in_check = game.check;
+        let mut best_score = -INFINITY;
+        let mut picker = if in_check {
+            // Every evasion has to be tried, standing pat is not an option in check
+            MovePicker::new(game, Move::NONE, [Move::NONE; 2], true)
+        } else {
+            let stand_pat = self.evaluate(game);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            best_score = stand_pat;
+            MovePicker::new_noisy(game)
+        };
+
+        let mut legal_moves = 0;
+        while let Some(played) = picker.next(game, &self.history) {
+            if !self.make_move(game, played) {
+                continue;
+            }
+            legal_moves += 1;
+            let score = -self.quiescence(game, -beta, -alpha, ply + 1);
+            self.unmake_move(game);
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                    self.update_pv(ply, played);
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if in_check && legal_moves == 0 {
+            return -MATE_SCORE + ply as i32;
+        }
+        best_score
+    }
+
+    fn evaluate(&self, game: &GAME) -> i32 {
+        #[cfg(feature = "nnue")]
+        if let Some(network) = &self.network {
+            return network.evaluate(game);
+        }
+        evaluate(game)
+    }
+
+    // Making and taking back moves keeps the network's accumulators in step
+    fn make_move(&mut self, game: &mut GAME, played: Move) -> bool {
+        if !movegen::make_move(game, played) {
+            return false;
+        }
+        #[cfg(feature = "nnue")]
+        if let Some(network) = &mut self.network {
+            network.push(game);
+        }
+        true
+    }
+
+    fn unmake_move(&mut self, game: &mut GAME) {
+        movegen::unmake_move(game);
+        #[cfg(feature = "nnue")]
+        if let Some(network) = &mut self.network {
+            network.pop();
+        }
+    }
+
+    fn update_pv(&mut self, ply: usize, played: Move) {
+        let child_length = self.pv_length[ply + 1].max(ply + 1);
+        self.pv_table[ply][ply] = played;
+        for index in ply + 1..child_length {
+            self.pv_table[ply][index] = self.pv_table[ply + 1][index];
+        }
+        self.pv_length[ply] = child_length;
+    }
+
+    fn update_quiet_statistics(&mut self, game: &GAME, played: Move, quiets_tried: &[Move], depth: i32, ply: usize) {
+        if self.killers[ply][0] != played {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = played;
+        }
+
+        let color_index = piece_color_index(game.turn);
+        let bonus = (depth * depth).min(HISTORY_LIMIT);
+        update_history(&mut self.history[color_index][played.from as usize][played.to as usize], bonus);
+        for tried in quiets_tried {
+            update_history(&mut self.history[color_index][tried.from as usize][tried.to as usize], -bonus);
+        }
+    }
+}
+
+fn allows_mate_in_one(game: &mut GAME) -> bool {
+    movegen::generate_legal_moves(game).into_iter().any(|reply| {
+        movegen::make_move(game, reply);
+        let mated = game.check && movegen::generate_legal_moves(game).is_empty();
+        movegen::unmake_move(game);
+        mated
+    })
+}
+
+fn has_non_pawn_material(game: &GAME) -> bool {
+    let pieces = TYPES::KNIGHT | TYPES::BISHOP | TYPES::ROOK | TYPES::QUEEN;
+    game.board.iter().any(|piece| piece & game.turn > 0 && piece & pieces > 0)
+}
+
+// Later moves at higher depths are reduced more, both grow logarithmically
+fn late_move_reduction_table() -> Vec<[i32; 64]> {
+    let mut reductions = vec![[0; 64]; 64];
+    for (depth, row) in reductions.iter_mut().enumerate().skip(1) {
+        for (move_number, reduction) in row.iter_mut().enumerate().skip(1) {
+            *reduction = (0.75 + (depth as f64).ln() * (move_number as f64).ln() / 2.25) as i32;
+        }
+    }
+    reductions
+}
+
+// Moves the value towards the bonus while keeping it within +-HISTORY_LIMIT
+fn update_history(value: &mut i32, bonus: i32) {
+    *value += bonus - *value * bonus.abs() / HISTORY_LIMIT;
+}
+
+// Mate scores are stored relative to the node, not the root, so they stay valid at other plies
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score > MATE_SCORE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score < -MATE_SCORE + MAX_PLY as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score > MATE_SCORE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score < -MATE_SCORE + MAX_PLY as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
@@ -0,0 +1,140 @@
+/// How well the engine plays, from level 0 to full strength at level 20. Below full strength
+/// the search is capped in depth and nodes, several root lines are searched and one of them
+/// is picked at random with the better ones more likely, and now and then a move that loses
+/// a little is played on purpose. The weaker the level, the shallower the search, the wider
+/// the choice and the more frequent and costly the mistakes.
+///
+/// The Elo of a level comes from games against the engine searching to a fixed depth, on a
+/// scale that puts depth 1 at 1000. It is the engine's own scale, not that of rating lists.
+/// # Levels and Elo
+///```
+/// use chess_logic::Skill;
+/// assert!(!Skill::default().is_limited());
+/// assert_eq!(Skill::new(30), Skill::default());
+/// assert_eq!(Skill::new(20).elo(), None);
+/// assert_eq!(Skill::from_elo(Skill::MIN_ELO), Skill::new(0));
+/// assert_eq!(Skill::from_elo(Skill::MAX_ELO), Skill::new(19));
+/// assert_eq!(Skill::from_elo(Skill::new(7).elo().unwrap()).level(), 7);
+/// assert!(Skill::new(3).elo() < Skill::new(4).elo());
+///```
+/// # Missing the recapture
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/3PP3/5N2/PPP2PPP/RNBQKB1R b KQkq - 0 3").unwrap();
+/// let limits = SearchLimits { depth: Some(4), ..Default::default() };
+/// let best = Searcher::new(SearchOptions::default()).search(&game, limits).best_move.unwrap();
+/// assert_eq!(best.to_uci(), "e5d4");
+///
+/// let weakest = || {
+///     let mut searcher = Searcher::new(SearchOptions { skill: Skill::new(0), ..Default::default() });
+///     searcher.set_random_seed(1);
+///     searcher.search(&game, limits).best_move.unwrap()
+/// };
+/// assert_eq!(weakest().to_uci(), "h7h6");
+/// assert_eq!(weakest(), weakest());                           // the same seed, the same choice
+///```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Skill {
+    level: u8,
+}
+
+// Strength of levels 0 to 19, measured by
+//   calibrate_skill --games 60 --depths 7 --openings match_runner/openings.pgn
+//                   --resign score=600 moves=4 --draw score=10 moves=8 after=40
+// at 10+0.1, see match_runner/src/bin/calibrate_skill.rs. Each depth played the one below:
+//   depth  1 1000 (anchor)  2 1313  3 1638  4 1918  5 2197  6 2366  7 2520
+// and each level the depth nearest the level below it, wins-losses-draws:
+//   level  0 vs depth 1   0-59-1   170     level 10 vs depth 1  44-12-4  1207
+//   level  1 vs depth 1   1-59-0   292     level 11 vs depth 2  18-41-1  1173
+//   level  2 vs depth 1   8-51-1   687     level 12 vs depth 2  34-21-5  1389
+//   level  3 vs depth 1   3-55-2   542     level 13 vs depth 2  35-19-6  1408
+//   level  4 vs depth 1   5-53-2   618     level 14 vs depth 2  44-11-5  1528
+//   level  5 vs depth 1   6-50-4   675     level 15 vs depth 3  38-16-6  1772
+//   level  6 vs depth 1  11-43-6   793     level 16 vs depth 3  52-6-2   1990
+//   level  7 vs depth 1   7-49-4   699     level 17 vs depth 4  39-13-8  2079
+//   level  8 vs depth 1  29-28-3  1006     level 18 vs depth 5  35-12-13 2338
+//   level  9 vs depth 1  26-28-6   988     level 19 vs depth 6  35-13-12 2499
+// Levels that measured below the one under them share the average, one Elo apart. The
+// error is about 90 Elo a level, and well over that at levels 0 and 1, which hardly score
+const LEVEL_ELO: [u32; 20] = [
+    170, 292, 614, 615, 618, 675, 746, 747, 997, 998,
+    1190, 1191, 1389, 1408, 1528, 1772, 1990, 2079, 2338, 2499,
+];
+
+impl Default for Skill {
+    fn default() -> Skill {
+        Skill { level: Skill::MAX_LEVEL }
+    }
+}
+
+impl Skill {
+    pub const MAX_LEVEL: u8 = 20;
+    pub const MIN_ELO: u32 = LEVEL_ELO[0];
+    pub const MAX_ELO: u32 = LEVEL_ELO[LEVEL_ELO.len() - 1];
+
+    pub fn new(level: u8) -> Skill {
+        Skill { level: level.min(Skill::MAX_LEVEL) }
+    }
+
+    /// The strongest level that is not stronger than the given Elo, level 0 below the scale.
+    pub fn from_elo(elo: u32) -> Skill {
+        Skill::new(LEVEL_ELO.iter().rposition(|level_elo| *level_elo <= elo).unwrap_or(0) as u8)
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// The measured Elo, None at full strength.
+    pub fn elo(&self) -> Option<u32> {
+        LEVEL_ELO.get(self.level as usize).copied()
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.level < Skill::MAX_LEVEL
+    }
+
+    pub(crate) fn depth_limit(&self) -> u8 {
+        1 + self.level / 2
+    }
+
+    pub(crate) fn node_limit(&self) -> u64 {
+        100 << (self.level / 2)
+    }
+
+    // Largest random bonus a line can get, in centipawns
+    fn spread(&self) -> i32 {
+        (Skill::MAX_LEVEL - self.level) as i32 * 10
+    }
+
+    pub(crate) fn blunder_percent(&self) -> u64 {
+        (Skill::MAX_LEVEL - self.level) as u64
+    }
+
+    /// The most a deliberate mistake may lose, in centipawns.
+    pub(crate) fn max_blunder_loss(&self) -> i32 {
+        50 + (Skill::MAX_LEVEL - self.level) as i32 * 10
+    }
+
+    /// Index of the line to play: every score gets a random bonus up to the spread and the
+    /// highest total wins.
+    pub(crate) fn pick_line(&self, scores: &[i32], random: &mut u64) -> usize {
+        let spread = self.spread() as u64 + 1;
+        let mut best = (0, i32::MIN);
+        for (index, score) in scores.iter().enumerate() {
+            let total = score + (next_random(random) % spread) as i32;
+            if total > best.1 {
+                best = (index, total);
+            }
+        }
+        best.0
+    }
+}
+
+/// Xorshift64*, enough to make the weaker levels unpredictable.
+pub(crate) fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+}
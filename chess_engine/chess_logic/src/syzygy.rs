@@ -0,0 +1,1557 @@
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::movegen::{self, Move};
+use crate::retrograde::Dtm;
+use crate::{piece_color_index, piece_type_index};
+use crate::{COLORS, GAME, TYPES};
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+const FIFTY_MOVE_PLIES: i32 = 100;
+const MAX_TABLE_PIECES: usize = 7;
+
+// Flags of the compressed values of one side to move and leading pawn file
+const FLAG_STM: u8 = 1;                                         // a DTZ table stores black to move
+const FLAG_MAPPED: u8 = 2;                                      // DTZ values go through the value map
+const FLAG_WIN_PLIES: u8 = 4;                                   // DTZ wins are stored in plies, not moves
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;                                       // the value map holds 16 bit values
+const FLAG_SINGLE_VALUE: u8 = 128;                              // every position has the same value
+
+// Shape of the tables `write_tables` makes
+const WRITE_BLOCK_BITS: u8 = 5;                                 // 32 byte blocks
+const WRITE_SPAN_BITS: u8 = 10;                                 // a sparse index entry every 1024 positions
+const WRITE_MAX_SYMBOLS: usize = 256;
+const WRITE_MIN_PAIR_COUNT: usize = 8;                          // rarer pairs cost more in the symbol list than they save
+const WRITE_MAX_SYMBOL_VALUES: usize = 64;                      // keeps a block under 65536 values
+
+/// Win, draw or loss for the side to move. Cursed wins and blessed losses are decided
+/// positions that the fifty move rule turns into draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    /// The same result seen from the other side.
+    pub fn opposite(self) -> Wdl {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+
+    // The value a WDL table stores, the result + 2
+    fn from_stored(value: u16) -> Option<Wdl> {
+        [Wdl::Loss, Wdl::BlessedLoss, Wdl::Draw, Wdl::CursedWin, Wdl::Win].get(value as usize).copied()
+    }
+
+    fn from_dtm(dtm: Dtm) -> Wdl {
+        match dtm {
+            Dtm::Win(_) => Wdl::Win,
+            Dtm::Loss(_) => Wdl::Loss,
+            Dtm::Draw => Wdl::Draw,
+        }
+    }
+
+    fn signum(self) -> i32 {
+        match self {
+            Wdl::Loss | Wdl::BlessedLoss => -1,
+            Wdl::Draw => 0,
+            Wdl::CursedWin | Wdl::Win => 1,
+        }
+    }
+
+    // Distance to zeroing of a position whose best move zeroes the fifty move counter
+    fn dtz_before_zeroing(self) -> i32 {
+        match self {
+            Wdl::Loss => -1,
+            Wdl::BlessedLoss => -101,
+            Wdl::Draw => 0,
+            Wdl::CursedWin => 101,
+            Wdl::Win => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeError {
+    TooManyPieces(usize),
+    Castling,
+    MissingTable(String),
+    Corrupt(String),
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProbeError::TooManyPieces(pieces) => write!(f, "{} pieces is more than the tables cover", pieces),
+            ProbeError::Castling => write!(f, "positions with castling rights are not in the tables"),
+            ProbeError::MissingTable(name) => write!(f, "no table for {}", name),
+            ProbeError::Corrupt(message) => write!(f, "corrupt table: {}", message),
+        }
+    }
+}
+
+/// Syzygy tables found in one or more directories.
+///
+/// Files are found by their material name (`KQvK.rtbw`, `KQvK.rtbz`) and checked for the
+/// Syzygy magic number, then read the first time a position needs them. Endings without
+/// mating material are drawn without any table.
+/// # Known results from the test tables
+///```
+/// use chess_logic::*;
+/// let tablebase = Tablebase::open("testdata/syzygy").unwrap();
+/// assert!(tablebase.has_table("KQvK") && tablebase.has_table("KRvK") && tablebase.has_table("KPvK"));
+/// assert_eq!(tablebase.max_pieces(), 3);
+///
+/// let probe = |fen: &str| {
+///     let game = init_game_from_fen(fen).unwrap();
+///     (tablebase.probe_wdl(&game).unwrap(), tablebase.probe_dtz(&game).unwrap())
+/// };
+/// assert_eq!(probe("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"), (Wdl::Win, 1));             // Qg8 mates
+/// assert_eq!(probe("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"), (Wdl::Loss, -1));           // mated
+/// assert_eq!(probe("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), (Wdl::Draw, 0));            // stalemate
+/// assert_eq!(probe("8/8/8/8/8/2kQ4/8/6K1 b - - 0 1"), (Wdl::Draw, 0));            // Kxd3
+/// assert_eq!(probe("6q1/8/8/8/8/1k6/8/K7 b - - 0 1"), (Wdl::Win, 1));             // colours reversed
+/// assert_eq!(probe("4k3/8/4K3/8/8/8/8/R7 w - - 0 1"), (Wdl::Win, 1));             // Ra8 mates
+/// assert_eq!(probe("4k3/8/4K3/8/8/8/8/R7 b - - 0 1"), (Wdl::Loss, -4));           // Kd8 Kd6 Ke8 Ra8
+///
+/// // King and pawn: the pawn wins with the king in front of it, not against the opposition
+/// assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), (Wdl::Win, 3));             // Kd6 Kd8 e6
+/// assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), (Wdl::Loss, -4));           // Kd8 Kf7 Kd7 e6
+/// assert_eq!(probe("8/8/8/8/4p3/4k3/8/4K3 b - - 0 1"), (Wdl::Win, 3));             // colours reversed
+/// assert_eq!(probe("8/8/8/8/8/4k3/4P3/4K3 w - - 0 1"), (Wdl::Draw, 0));            // opposition
+/// assert_eq!(probe("8/8/8/8/8/4k3/4P3/4K3 b - - 0 1"), (Wdl::Draw, 0));
+/// assert_eq!(probe("k7/8/8/8/8/8/P7/K7 w - - 0 1"), (Wdl::Draw, 0));               // rook pawn
+/// assert_eq!(probe("8/8/8/8/8/8/3kP3/7K b - - 0 1"), (Wdl::Draw, 0));              // Kxe2
+/// assert_eq!(probe("8/4P3/8/8/8/8/k7/4K3 w - - 0 1"), (Wdl::Win, 1));              // e8=Q
+/// assert_eq!(probe("k7/8/1K6/8/8/8/6P1/8 b - - 0 1"), (Wdl::Loss, -2));            // any move, g4
+///
+/// let bare_kings = init_game_from_fen("8/8/4k3/8/8/3NK3/8/8 b - - 0 1").unwrap();
+/// assert_eq!(tablebase.probe_dtz(&bare_kings), Ok(0));
+/// let rook_and_pawn = init_game_from_fen("8/8/4k3/8/8/4K3/1P6/7R w - - 0 1").unwrap();
+/// assert_eq!(tablebase.probe_wdl(&rook_and_pawn), Err(ProbeError::TooManyPieces(4)));
+/// let bishop_and_knight = init_game_from_fen("8/8/4k3/8/8/4K3/1N6/7B w - - 0 1").unwrap();
+/// assert!(matches!(tablebase.probe_wdl(&bishop_and_knight), Err(ProbeError::TooManyPieces(_))));
+///```
+/// # Looking for tables
+///```
+/// use chess_logic::*;
+/// let directory = std::env::temp_dir().join("syzygy_doctest");
+/// std::fs::create_dir_all(&directory).unwrap();
+/// std::fs::copy("testdata/syzygy/KQvK.rtbw", directory.join("KQvK.rtbw")).unwrap();
+/// std::fs::write(directory.join("KRvK.rtbw"), b"not a table").unwrap();
+/// let tablebase = Tablebase::open(directory.to_str().unwrap()).unwrap();
+/// assert!(tablebase.has_table("KQvK"));
+/// assert!(!tablebase.has_table("KRvK"));
+///
+/// let queen = init_game_from_fen("8/8/4k3/8/8/4K3/8/7q w - - 0 1").unwrap();
+/// assert_eq!(tablebase.probe_wdl(&queen), Ok(Wdl::Loss));
+/// assert_eq!(tablebase.probe_dtz(&queen), Err(ProbeError::MissingTable("KvKQ".to_string())));
+/// let rook = init_game_from_fen("8/8/4k3/8/8/4K3/8/7R b - - 0 1").unwrap();
+/// assert_eq!(tablebase.probe_wdl(&rook), Err(ProbeError::MissingTable("KRvK".to_string())));
+///```
+#[derive(Default)]
+pub struct Tablebase {
+    wdl_tables: HashMap<String, PathBuf>,
+    dtz_tables: HashMap<String, PathBuf>,
+    max_pieces: usize,
+    loaded: Mutex<HashMap<PathBuf, Arc<Table>>>,
+}
+
+impl Tablebase {
+    pub fn new() -> Tablebase {
+        Tablebase::default()
+    }
+
+    /// Tables from a list of directories separated by `:` or `;`, the form of the UCI `SyzygyPath` option.
+    pub fn open(paths: &str) -> Result<Tablebase, String> {
+        let mut tablebase = Tablebase::new();
+        for directory in paths.split([':', ';']).filter(|directory| !directory.trim().is_empty()) {
+            tablebase.add_directory(directory.trim())?;
+        }
+        Ok(tablebase)
+    }
+
+    /// Adds the tables of one directory and returns how many were found.
+    pub fn add_directory<P: AsRef<Path>>(&mut self, directory: P) -> Result<usize, String> {
+        let directory = directory.as_ref();
+        let listing = fs::read_dir(directory).map_err(|error| format!("cannot read {}: {}", directory.display(), error))?;
+        let mut found = 0;
+        for path in listing.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            let (name, extension) = match (path.file_stem().and_then(|stem| stem.to_str()), path.extension().and_then(|extension| extension.to_str())) {
+                (Some(name), Some(extension)) => (name.to_string(), extension),
+                _ => continue,
+            };
+            let (tables, magic) = match extension {
+                "rtbw" => (&mut self.wdl_tables, WDL_MAGIC),
+                "rtbz" => (&mut self.dtz_tables, DTZ_MAGIC),
+                _ => continue,
+            };
+            if !is_material_name(&name) || name.len() - 1 > MAX_TABLE_PIECES || !starts_with_magic(&path, magic) {
+                continue;
+            }
+            self.max_pieces = self.max_pieces.max(name.len() - 1);
+            tables.insert(name, path);
+            found += 1;
+        }
+        Ok(found)
+    }
+
+    /// Largest number of pieces, kings included, any table was found for.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    pub fn has_table(&self, name: &str) -> bool {
+        self.wdl_tables.contains_key(name)
+    }
+
+    /// Result of the position with best play, counting the fifty move rule.
+    pub fn probe_wdl(&self, game: &GAME) -> Result<Wdl, ProbeError> {
+        self.check_probe(game)?;
+        let mut game = game.clone();
+        Ok(self.search(&mut game, false)?.0)
+    }
+
+    /// Distance to zeroing the fifty move counter in plies: positive when the side to move
+    /// wins, negative when it loses, zero for a draw. Mate counts as zeroing, and cursed wins
+    /// and blessed losses are 100 plies further away than the zeroing move.
+    /// # The longest wins
+    ///```
+    /// use chess_logic::*;
+    /// let tablebase = Tablebase::open("testdata/syzygy").unwrap();
+    /// let queen = init_game_from_fen("K7/1Q6/8/8/5k2/8/8/8 w - - 0 1").unwrap();
+    /// assert_eq!(tablebase.probe_dtz(&queen), Ok(19));                          // mate in 10
+    /// let rook = init_game_from_fen("K7/1R6/2k5/8/8/8/8/8 w - - 0 1").unwrap();
+    /// assert_eq!(tablebase.probe_dtz(&rook), Ok(31));                           // mate in 16
+    /// let rook = init_game_from_fen("K7/1R6/2k5/8/8/8/8/8 b - - 0 1").unwrap();
+    /// assert_eq!(tablebase.probe_dtz(&rook), Ok(-32));
+    ///```
+    pub fn probe_dtz(&self, game: &GAME) -> Result<i32, ProbeError> {
+        self.check_probe(game)?;
+        let mut game = game.clone();
+        self.dtz(&mut game)
+    }
+
+    /// The root moves that keep the best result the tables promise, see `filter_root_moves`.
+    /// # Winning in time
+    ///```
+    /// use chess_logic::*;
+    /// let tablebase = Tablebase::open("testdata/syzygy").unwrap();
+    /// let dtz_after = |game: &GAME, played: Move| {
+    ///     let mut child = game.clone();
+    ///     make_move(&mut child, played);
+    ///     tablebase.probe_dtz(&child).unwrap()
+    /// };
+    /// // Moves that leave the rook to the king only draw
+    /// let early = init_game_from_fen("K7/1R6/2k5/8/8/8/8/8 w - - 0 1").unwrap();
+    /// let winning = tablebase.root_moves(&early).unwrap();
+    /// assert_eq!(winning.len(), 12);
+    /// assert!(winning.iter().all(|played| dtz_after(&early, *played) < 0));
+    /// // With 69 plies on the clock only the fastest mate comes before the fifty move rule
+    /// let late = init_game_from_fen("K7/1R6/2k5/8/8/8/8/8 w - - 69 80").unwrap();
+    /// let in_time = tablebase.root_moves(&late).unwrap();
+    /// assert_eq!(in_time.len(), 8);
+    /// assert!(in_time.iter().all(|played| dtz_after(&late, *played) == -30));
+    /// // One ply later every win is too slow, the moves that win after the rule are kept
+    /// let too_late = init_game_from_fen("K7/1R6/2k5/8/8/8/8/8 w - - 70 80").unwrap();
+    /// assert_eq!(tablebase.root_moves(&too_late).unwrap(), winning);
+    ///```
+    pub fn root_moves(&self, game: &GAME) -> Result<Vec<Move>, ProbeError> {
+        filter_root_moves(game, |child| self.probe_dtz(child))
+    }
+
+    fn check_probe(&self, game: &GAME) -> Result<(), ProbeError> {
+        if game.chastling_ability.iter().any(|allowed| *allowed) {
+            return Err(ProbeError::Castling);
+        }
+        let pieces = game.board.iter().filter(|piece| **piece != TYPES::NONE).count();
+        if pieces > self.max_pieces && !is_insufficient_material(&game.board) {
+            return Err(ProbeError::TooManyPieces(pieces));
+        }
+        Ok(())
+    }
+
+    // Best result after the captures, and with `zeroing_moves` the pawn moves as well, when one
+    // of them is at least as good as the table value. True in second place when the best
+    // move zeroes the fifty move counter, then the DTZ table may hold anything.
+    fn search(&self, game: &mut GAME, zeroing_moves: bool) -> Result<(Wdl, bool), ProbeError> {
+        let moves = movegen::generate_legal_moves(game);
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for played in moves.iter() {
+            let pawn = game.board[played.from as usize] & TYPES::PAWN != 0;
+            if !movegen::is_capture(game, *played) && (!zeroing_moves || !pawn) {
+                continue;
+            }
+            searched += 1;
+            movegen::make_move(game, *played);
+            let result = self.search(game, false);
+            movegen::unmake_move(game);
+            let value = result?.0.opposite();
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Ok((value, true));
+                }
+            }
+        }
+
+        // With every move searched the table is not needed, it knows nothing of en passant
+        let every_move = searched > 0 && searched == moves.len();
+        let value = if every_move { best } else { self.probe_wdl_table(&game.board, game.turn == COLORS::WHITE)? };
+        if best >= value {
+            return Ok((best, best > Wdl::Draw || every_move));
+        }
+        Ok((value, false))
+    }
+
+    fn dtz(&self, game: &mut GAME) -> Result<i32, ProbeError> {
+        let (wdl, zeroing) = self.search(game, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if zeroing {
+            return Ok(wdl.dtz_before_zeroing());
+        }
+        if let Some(dtz) = self.probe_dtz_table(&game.board, game.turn == COLORS::WHITE, wdl)? {
+            let fifty_move_rule = if matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss) { 100 } else { 0 };
+            return Ok((dtz + fifty_move_rule) * wdl.signum());
+        }
+
+        // The table holds the other side to move, look one move ahead for the best distance
+        let mut best: Option<i32> = None;
+        for played in movegen::generate_legal_moves(game) {
+            let zeroing = movegen::is_capture(game, played) || game.board[played.from as usize] & TYPES::PAWN != 0;
+            movegen::make_move(game, played);
+            let result = if zeroing {
+                self.search(game, false).map(|(wdl, _)| -wdl.dtz_before_zeroing())
+            } else {
+                self.dtz(game).map(|dtz| -dtz)
+            };
+            let mates = game.check && movegen::generate_legal_moves(game).is_empty();
+            movegen::unmake_move(game);
+            let mut dtz = result?;
+            if mates {
+                return Ok(1);
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz.signum() == wdl.signum() && best.is_none_or(|best| dtz < best) {
+                best = Some(dtz);
+            }
+        }
+        Ok(best.unwrap_or(-1))
+    }
+
+    fn probe_wdl_table(&self, board: &[u8; 64], white_to_move: bool) -> Result<Wdl, ProbeError> {
+        if is_insufficient_material(board) {
+            return Ok(Wdl::Draw);
+        }
+        let table = self.table(board, false)?;
+        let (file, side, index) = table.encode(board, white_to_move)?.ok_or(ProbeError::Corrupt("WDL table without both sides to move".to_string()))?;
+        let value = table.parts[file][side].decompress(&table.bytes, index)?;
+        Wdl::from_stored(value).ok_or(ProbeError::Corrupt(format!("{} is not a WDL value", value)))
+    }
+
+    // None when the table stores the other side to move
+    fn probe_dtz_table(&self, board: &[u8; 64], white_to_move: bool, wdl: Wdl) -> Result<Option<i32>, ProbeError> {
+        let table = self.table(board, true)?;
+        match table.encode(board, white_to_move)? {
+            Some((file, side, index)) => {
+                let value = table.parts[file][side].decompress(&table.bytes, index)?;
+                Ok(Some(table.map_score(file, value, wdl)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // The table for the material of a board, read from its file the first time it is needed
+    fn table(&self, board: &[u8; 64], dtz: bool) -> Result<Arc<Table>, ProbeError> {
+        let name = board_material_name(board);
+        let tables = if dtz { &self.dtz_tables } else { &self.wdl_tables };
+        let (table_name, path) = match tables.get_key_value(&name).or_else(|| tables.get_key_value(&mirrored_name(&name))) {
+            Some(found) => found,
+            None => return Err(ProbeError::MissingTable(name)),
+        };
+        let mut loaded = self.loaded.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(table) = loaded.get(path) {
+            return Ok(table.clone());
+        }
+        let bytes = fs::read(path).map_err(|error| ProbeError::Corrupt(format!("cannot read {}: {}", path.display(), error)))?;
+        let table = Arc::new(Table::parse(Material::new(table_name), dtz, bytes)?);
+        loaded.insert(path.clone(), table.clone());
+        Ok(table)
+    }
+}
+
+fn starts_with_magic(path: &Path, magic: [u8; 4]) -> bool {
+    let mut start = [0u8; 4];
+    fs::File::open(path).and_then(|mut file| file.read_exact(&mut start)).is_ok() && start == magic
+}
+
+fn is_material_name(name: &str) -> bool {
+    let sides: Vec<&str> = name.split('v').collect();
+    sides.len() == 2 && sides.iter().all(|side| side.starts_with('K') && side.chars().all(|letter| "KQRBNP".contains(letter)))
+}
+
+fn mirrored_name(name: &str) -> String {
+    let mut sides = name.split('v');
+    let white = sides.next().unwrap_or("");
+    let black = sides.next().unwrap_or("");
+    format!("{}v{}", black, white)
+}
+
+/// The material of a position in table file naming, white first: `KRPvKR`.
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("8/8/4k3/2r5/8/4K3/1P6/7R w - - 0 1").unwrap();
+/// assert_eq!(material_name(&game), "KRPvKR");
+///```
+pub fn material_name(game: &GAME) -> String {
+    board_material_name(&game.board)
+}
+
+pub(crate) fn board_material_name(board: &[u8; 64]) -> String {
+    let mut sides = [String::from("K"), String::from("K")];
+    for piece_type in [TYPES::QUEEN, TYPES::ROOK, TYPES::BISHOP, TYPES::KNIGHT, TYPES::PAWN] {
+        for piece in board.iter().filter(|piece| **piece & 63 == piece_type) {
+            let letter = ['P', 'N', 'B', 'R', 'Q', 'K'][piece_type_index(*piece)];
+            sides[piece_color_index(*piece)].push(letter);
+        }
+    }
+    format!("{}v{}", sides[0], sides[1])
+}
+
+// Bare kings, or a king and one minor piece against a bare king
+pub(crate) fn is_insufficient_material(board: &[u8; 64]) -> bool {
+    let mut others = board.iter().filter(|piece| **piece != TYPES::NONE && **piece & TYPES::KING == 0);
+    match (others.next(), others.next()) {
+        (None, _) => true,
+        (Some(piece), None) => piece & (TYPES::BISHOP | TYPES::KNIGHT) != 0,
+        _ => false,
+    }
+}
+
+/// Root moves that keep the best result a DTZ probe promises. When the position is won under
+/// the fifty move rule every move that still wins in time is kept, so the search can choose
+/// among them. Otherwise only the moves with the best result stay, and when every move loses
+/// only those that resist longest.
+/// # Filtering with a made up probe
+///```
+/// use chess_logic::*;
+/// // Every position after a move is called a draw, so only the mate stands out
+/// let game = init_game_from_fen("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+/// let mates: Vec<String> = filter_root_moves(&game, |_| Ok(0)).unwrap().iter().map(|played| played.to_uci()).collect();
+/// assert_eq!(mates, ["g1g8"]);
+/// // A win that needs more plies than the fifty move counter has left is only a cursed win
+/// let late = init_game_from_fen("k7/8/1K6/8/8/8/8/6Q1 w - - 95 80").unwrap();
+/// let keeps_queen = filter_root_moves(&late, |child| Ok(if child.get_board()[62] != 0 { -8 } else { 0 })).unwrap();
+/// assert_eq!(keeps_queen.len(), 1);
+/// let early = init_game_from_fen("k7/8/1K6/8/8/8/8/6Q1 w - - 0 80").unwrap();
+/// let keeps_queen = filter_root_moves(&early, |child| Ok(if child.get_board()[62] != 0 { -8 } else { 0 })).unwrap();
+/// assert!(keeps_queen.len() > 1);
+///```
+pub fn filter_root_moves<F>(game: &GAME, mut probe_dtz: F) -> Result<Vec<Move>, ProbeError>
+where
+    F: FnMut(&GAME) -> Result<i32, ProbeError>,
+{
+    let mut ranked = Vec::new();
+    for played in movegen::generate_legal_moves(game) {
+        let mut child = game.clone();
+        movegen::make_move(&mut child, played);
+        // Plies to a win for the side that moved, negative for a loss
+        let plies = if movegen::generate_legal_moves(&child).is_empty() {
+            if child.check { 1 } else { 0 }
+        } else {
+            match probe_dtz(&child)? {
+                0 => 0,
+                dtz if dtz < 0 => -dtz + 1,
+                dtz => -(dtz + 1),
+            }
+        };
+        let clock = child.halfmove_clock as i32;
+        let result = match plies {
+            0 => Wdl::Draw,
+            1 if child.check => Wdl::Win,
+            plies if plies > 0 && clock + plies - 1 <= FIFTY_MOVE_PLIES => Wdl::Win,
+            plies if plies > 0 => Wdl::CursedWin,
+            plies if clock - plies - 1 <= FIFTY_MOVE_PLIES => Wdl::Loss,
+            _ => Wdl::BlessedLoss,
+        };
+        ranked.push((played, result, plies));
+    }
+
+    let best = match ranked.iter().map(|(_, result, _)| *result).max() {
+        Some(best) => best,
+        None => return Ok(Vec::new()),
+    };
+    let longest_loss = ranked.iter().filter(|(_, result, _)| *result == best).map(|(_, _, plies)| *plies).min().unwrap_or(0);
+    Ok(ranked.into_iter()
+        .filter(|(_, result, plies)| *result == best && (best != Wdl::Loss || *plies == longest_loss))
+        .map(|(played, _, _)| played)
+        .collect())
+}
+
+// Tables of the Syzygy position index. Squares are numbered as in the files, a1 = 0 ... h8 = 63,
+// which is a tile of the board with its rank turned over.
+struct IndexMaps {
+    binomial: [[u64; 64]; MAX_TABLE_PIECES],                    // ways to choose k of n squares
+    map_pawns: [usize; 64],                                     // a2-h7 to 0..47, highest nearest the a file and rank 2
+    lead_pawn_index: [[u64; 64]; MAX_TABLE_PIECES],
+    lead_pawns_size: [[u64; 4]; MAX_TABLE_PIECES],
+    map_b1h1h7: [u64; 64],                                      // squares below the a1-h8 diagonal to 0..27
+    map_a1d1d4: [usize; 64],                                    // the a1-d1-d4 triangle to 0..9, diagonal last
+    map_kk: [[u64; 64]; 10],                                    // the 462 legal king pairs, first king in the triangle
+}
+
+impl IndexMaps {
+    fn new() -> IndexMaps {
+        let mut maps = IndexMaps {
+            binomial: [[0; 64]; MAX_TABLE_PIECES],
+            map_pawns: [0; 64],
+            lead_pawn_index: [[0; 64]; MAX_TABLE_PIECES],
+            lead_pawns_size: [[0; 4]; MAX_TABLE_PIECES],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+        };
+
+        maps.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_TABLE_PIECES.min(n + 1) {
+                maps.binomial[k][n] = if k > 0 { maps.binomial[k - 1][n - 1] } else { 0 } + if k < n { maps.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        for (code, square) in (0..64).filter(|square| off_diagonal(*square) < 0).enumerate() {
+            maps.map_b1h1h7[square] = code as u64;
+        }
+
+        let mut code = 0;
+        let mut diagonal = Vec::new();
+        for square in (0..28).filter(|square| square % 8 <= 3) {
+            if off_diagonal(square) < 0 {
+                maps.map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            maps.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        let mut code = 0;
+        let mut both_on_diagonal = Vec::new();
+        for index in 0..10 {
+            let firsts: Vec<usize> = (0..28).filter(|first| maps.map_a1d1d4[*first] == index && (index > 0 || *first == 1)).collect();
+            for first in firsts {
+                for second in 0..64 {
+                    let (file_distance, rank_distance) = ((first % 8).abs_diff(second % 8), (first / 8).abs_diff(second / 8));
+                    if file_distance <= 1 && rank_distance <= 1 {
+                        continue;                                   // kings next to each other or on one square
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) > 0 {
+                        continue;                                   // the mirror image has the second king below
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((index, second));
+                    } else {
+                        maps.map_kk[index][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (index, second) in both_on_diagonal {
+            maps.map_kk[index][second] = code;
+            code += 1;
+        }
+
+        let mut available = 47;
+        for lead_pawns in 1..=5 {
+            for file in 0..4 {
+                let mut index = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        maps.map_pawns[square] = available;
+                        maps.map_pawns[square ^ 7] = available.saturating_sub(1);
+                        available = available.saturating_sub(2);
+                    }
+                    maps.lead_pawn_index[lead_pawns][square] = index;
+                    index += maps.binomial[lead_pawns - 1][maps.map_pawns[square]];
+                }
+                maps.lead_pawns_size[lead_pawns][file] = index;
+            }
+        }
+        maps
+    }
+}
+
+fn index_maps() -> &'static IndexMaps {
+    static MAPS: OnceLock<IndexMaps> = OnceLock::new();
+    MAPS.get_or_init(IndexMaps::new)
+}
+
+// Rank minus file, positive above the a1-h8 diagonal
+fn off_diagonal(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+// Pieces as the files number them, white pawn 1 ... white king 6, black pieces 8 higher
+fn syzygy_piece(piece: u8) -> u8 {
+    piece_type_index(piece) as u8 + 1 + 8 * piece_color_index(piece) as u8
+}
+
+// What the name of a table tells about its layout
+#[derive(Clone)]
+struct Material {
+    name: String,
+    symmetric: bool,                                            // both sides have the same pieces
+    pawns: bool,
+    unique_pieces: bool,                                        // a side has exactly one piece of some type
+    pawn_counts: [usize; 2],                                    // the colour of the leading pawns first
+    piece_count: usize,
+}
+
+impl Material {
+    fn new(name: &str) -> Material {
+        let mut sides = name.split('v');
+        let white = sides.next().unwrap_or("");
+        let black = sides.next().unwrap_or("");
+        let count = |side: &str, letter: char| side.chars().filter(|piece| *piece == letter).count();
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        // The side with fewer pawns leads, it compresses better
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        Material {
+            name: name.to_string(),
+            symmetric: white == black,
+            pawns: white_pawns + black_pawns > 0,
+            unique_pieces: [white, black].iter().any(|side| "PNBRQ".chars().any(|letter| count(side, letter) == 1)),
+            pawn_counts: if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+            piece_count: white.len() + black.len(),
+        }
+    }
+}
+
+// The compressed values of one side to move and, with pawns, one file of the leading pawn.
+// Values are stored as canonical Huffman codes of symbols, and a symbol stands for a pair of
+// symbols or for a single value.
+#[derive(Default, Clone)]
+struct Part {
+    flags: u8,
+    pieces: Vec<u8>,                                            // in the order their squares are encoded
+    group_len: Vec<usize>,                                      // pieces encoded together, zero terminated
+    group_idx: Vec<u64>,                                        // multiplier of every group, then the table size
+    single_value: u16,
+    block_size: usize,
+    span: u64,                                                  // positions between sparse index entries
+    sparse_index: usize,                                        // offsets into the file
+    sparse_count: usize,
+    block_lengths: usize,
+    block_length_count: usize,
+    data: usize,
+    blocks: usize,
+    min_symbol_bits: usize,
+    lowest_symbol: Vec<u64>,                                    // first symbol of every code length
+    base: Vec<u64>,                                             // smallest left aligned code of every length
+    symbols: Vec<(u16, u16)>,                                   // a pair of symbols, or a value and 0xfff
+    symbol_len: Vec<u32>,                                       // values a symbol stands for, minus one
+    map_index: [usize; 4],
+}
+
+impl Part {
+    fn size(&self) -> u64 {
+        let groups = self.group_len.iter().position(|len| *len == 0).unwrap_or(0);
+        self.group_idx.get(groups).copied().unwrap_or(0)
+    }
+
+    // How the pieces form groups and what each group multiplies the index by
+    fn set_groups(&mut self, material: &Material, order: [usize; 2], file: usize) {
+        let maps = index_maps();
+        let mut first_len: i32 = if material.pawns { 0 } else if material.unique_pieces { 3 } else { 2 };
+        self.group_len = vec![1];
+        for i in 1..material.piece_count {
+            first_len -= 1;
+            if first_len > 0 || self.pieces[i] == self.pieces[i - 1] {
+                *self.group_len.last_mut().unwrap() += 1;
+            } else {
+                self.group_len.push(1);
+            }
+        }
+        let groups = self.group_len.len();
+        self.group_len.push(0);
+
+        // The groups are multiplied in the order the table chose: the leading group at order[0],
+        // the other pawns at order[1] and the pieces in between and after
+        let both_pawns = material.pawns && material.pawn_counts[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - self.group_len[0] - if both_pawns { self.group_len[1] } else { 0 };
+        self.group_idx = vec![0; groups + 1];
+        let mut index = 1u64;
+        let mut k = 0;
+        while next < groups || k == order[0] || k == order[1] {
+            if k == order[0] {
+                self.group_idx[0] = index;
+                index *= if material.pawns { maps.lead_pawns_size[self.group_len[0]][file] } else if material.unique_pieces { 31332 } else { 462 };
+            } else if k == order[1] {
+                self.group_idx[1] = index;
+                index *= maps.binomial[self.group_len[1]][48 - self.group_len[0]];
+            } else {
+                self.group_idx[next] = index;
+                index *= maps.binomial[self.group_len[next]][free_squares];
+                free_squares -= self.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        self.group_idx[groups] = index;
+    }
+
+    fn read_sizes(&mut self, reader: &mut Reader) -> Result<(), ProbeError> {
+        self.flags = reader.byte()?;
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            self.single_value = reader.byte()? as u16;
+            return Ok(());
+        }
+        let (block_bits, span_bits) = (reader.byte()?, reader.byte()?);
+        if block_bits > 30 || span_bits > 30 {
+            return Err(ProbeError::Corrupt(format!("blocks of 2^{} bytes and an index every 2^{} positions", block_bits, span_bits)));
+        }
+        self.block_size = 1 << block_bits;
+        self.span = 1 << span_bits;
+        self.sparse_count = self.size().div_ceil(self.span) as usize;
+        let padding = reader.byte()? as usize;
+        self.blocks = reader.u32()? as usize;
+        self.block_length_count = self.blocks + padding;
+        let (max_bits, min_bits) = (reader.byte()? as usize, reader.byte()? as usize);
+        if min_bits == 0 || max_bits < min_bits || max_bits > 32 {
+            return Err(ProbeError::Corrupt(format!("codes of {} to {} bits", min_bits, max_bits)));
+        }
+        self.min_symbol_bits = min_bits;
+
+        // Longer codes have smaller values, so the smallest code of each length, left aligned
+        // in 64 bits, tells the length of the code at the start of a bit buffer
+        let lengths = max_bits - min_bits + 1;
+        self.lowest_symbol = (0..lengths).map(|_| reader.u16().map(u64::from)).collect::<Result<_, _>>()?;
+        self.base = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            self.base[i] = self.base[i + 1].wrapping_add(self.lowest_symbol[i]).wrapping_sub(self.lowest_symbol[i + 1]) / 2;
+        }
+        for (i, base) in self.base.iter_mut().enumerate() {
+            *base <<= 64 - i - min_bits;
+        }
+
+        let symbol_count = reader.u16()? as usize;
+        let tree = reader.take(3 * symbol_count)?;
+        self.symbols = tree.chunks(3).map(|pair| {
+            ((pair[1] as u16 & 0xf) << 8 | pair[0] as u16, (pair[2] as u16) << 4 | (pair[1] as u16) >> 4)
+        }).collect();
+        reader.take(symbol_count & 1)?;
+        self.symbol_len = vec![0; symbol_count];
+        let mut visited = vec![false; symbol_count];
+        for symbol in 0..symbol_count {
+            if !visited[symbol] {
+                self.symbol_len[symbol] = self.set_symbol_len(symbol, &mut visited)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_symbol_len(&mut self, symbol: usize, visited: &mut Vec<bool>) -> Result<u32, ProbeError> {
+        visited[symbol] = true;
+        let (left, right) = self.symbols[symbol];
+        if right == 0xfff {
+            return Ok(0);
+        }
+        let (left, right) = (left as usize, right as usize);
+        if left >= self.symbols.len() || right >= self.symbols.len() {
+            return Err(ProbeError::Corrupt(format!("symbol {} is a pair of unknown symbols", symbol)));
+        }
+        for half in [left, right] {
+            if !visited[half] {
+                self.symbol_len[half] = self.set_symbol_len(half, visited)?;
+            }
+        }
+        Ok(self.symbol_len[left] + self.symbol_len[right] + 1)
+    }
+
+    fn decompress(&self, bytes: &[u8], index: u64) -> Result<u16, ProbeError> {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return Ok(self.single_value);
+        }
+        let corrupt = || ProbeError::Corrupt(format!("position {} is outside the table", index));
+
+        // The sparse index points into the block list every span positions, from there walk
+        // the block lengths to the block that holds the position
+        let entry = self.sparse_index + 6 * (index / self.span) as usize;
+        let mut block = read_u32(bytes, entry).ok_or_else(corrupt)? as usize;
+        let mut offset = read_u16(bytes, entry + 4).ok_or_else(corrupt)? as i64;
+        offset += (index % self.span) as i64 - (self.span / 2) as i64;
+        let block_length = |block: usize| {
+            if block < self.block_length_count { read_u16(bytes, self.block_lengths + 2 * block).map(i64::from) } else { None }
+        };
+        while offset < 0 {
+            block = block.checked_sub(1).ok_or_else(corrupt)?;
+            offset += block_length(block).ok_or_else(corrupt)? + 1;
+        }
+        while offset > block_length(block).ok_or_else(corrupt)? {
+            offset -= block_length(block).ok_or_else(corrupt)? + 1;
+            block += 1;
+        }
+        if block >= self.blocks {
+            return Err(corrupt());
+        }
+
+        // Read codes until the one whose symbol covers the position
+        let mut next = self.data + block * self.block_size;
+        let mut buffer = (read_u32_or_zero(bytes, next) as u64) << 32 | read_u32_or_zero(bytes, next + 4) as u64;
+        next += 8;
+        let mut buffer_bits = 64;
+        let mut offset = offset as u64;
+        let symbol = loop {
+            let mut len = 0;
+            while buffer < self.base[len] {
+                len += 1;
+                if len == self.base.len() {
+                    return Err(ProbeError::Corrupt("a code longer than the longest".to_string()));
+                }
+            }
+            let symbol = (((buffer - self.base[len]) >> (64 - len - self.min_symbol_bits)) + self.lowest_symbol[len]) as usize;
+            let covered = *self.symbol_len.get(symbol).ok_or_else(corrupt)? as u64 + 1;
+            if offset < covered {
+                break symbol;
+            }
+            offset -= covered;
+            let bits = len + self.min_symbol_bits;
+            buffer <<= bits;
+            buffer_bits -= bits;
+            if buffer_bits <= 32 {
+                buffer_bits += 32;
+                buffer |= (read_u32_or_zero(bytes, next) as u64) << (64 - buffer_bits);
+                next += 4;
+            }
+        };
+
+        // Symbols of pairs are expanded down to the value at the offset
+        let mut symbol = symbol;
+        while self.symbol_len[symbol] > 0 {
+            let (left, right) = self.symbols[symbol];
+            let left_len = self.symbol_len[left as usize] as u64 + 1;
+            if offset < left_len {
+                symbol = left as usize;
+            } else {
+                offset -= left_len;
+                symbol = right as usize;
+            }
+        }
+        Ok(self.symbols[symbol].0)
+    }
+}
+
+// A Syzygy table file read into memory, or the layout of one being written
+struct Table {
+    bytes: Vec<u8>,
+    material: Material,
+    dtz: bool,
+    parts: Vec<Vec<Part>>,                                      // by leading pawn file, then side to move
+    map: usize,                                                 // DTZ value map
+}
+
+impl Table {
+    fn parse(material: Material, dtz: bool, bytes: Vec<u8>) -> Result<Table, ProbeError> {
+        let mut table = Table { bytes: Vec::new(), material, dtz, parts: Vec::new(), map: 0 };
+        let mut reader = Reader { bytes: &bytes, offset: 4 };
+        let flags = reader.byte()?;
+        if (flags & 2 != 0) != table.material.pawns {
+            return Err(ProbeError::Corrupt(format!("{} does not agree with the table about pawns", table.material.name)));
+        }
+        let sides = if dtz || table.material.symmetric { 1 } else { 2 };
+        let files = if table.material.pawns { 4 } else { 1 };
+        let both_pawns = table.material.pawns && table.material.pawn_counts[1] > 0;
+
+        for file in 0..files {
+            let first = reader.byte()?;
+            let second = if both_pawns { reader.byte()? } else { 0xff };
+            let orders = [[first & 0xf, second & 0xf], [first >> 4, second >> 4]];
+            let mut parts = vec![Part::default(); sides];
+            for _ in 0..table.material.piece_count {
+                let pieces = reader.byte()?;
+                for (side, part) in parts.iter_mut().enumerate() {
+                    part.pieces.push(if side == 1 { pieces >> 4 } else { pieces & 0xf });
+                }
+            }
+            for (part, order) in parts.iter_mut().zip(orders.iter()) {
+                part.set_groups(&table.material, [order[0] as usize, order[1] as usize], file);
+            }
+            table.parts.push(parts);
+        }
+        reader.align(2);
+        for part in table.parts.iter_mut().flatten() {
+            part.read_sizes(&mut reader)?;
+        }
+        if dtz {
+            table.read_dtz_map(&mut reader)?;
+        }
+        for part in table.parts.iter_mut().flatten() {
+            part.sparse_index = reader.offset;
+            reader.take(6 * part.sparse_count)?;
+        }
+        for part in table.parts.iter_mut().flatten() {
+            part.block_lengths = reader.offset;
+            reader.take(2 * part.block_length_count)?;
+        }
+        for part in table.parts.iter_mut().flatten() {
+            reader.align(64);
+            part.data = reader.offset;
+            reader.take(part.blocks * part.block_size)?;
+        }
+        table.bytes = bytes;
+        Ok(table)
+    }
+
+    // Where the four value maps of every file start: loss, win, blessed loss and cursed win
+    fn read_dtz_map(&mut self, reader: &mut Reader) -> Result<(), ProbeError> {
+        self.map = reader.offset;
+        for parts in self.parts.iter_mut() {
+            let part = &mut parts[0];
+            if part.flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+            if part.flags & FLAG_WIDE != 0 {
+                reader.align(2);
+                for map_index in part.map_index.iter_mut() {
+                    *map_index = (reader.offset - self.map) / 2 + 1;
+                    let len = reader.u16()? as usize;
+                    reader.take(2 * len)?;
+                }
+            } else {
+                for map_index in part.map_index.iter_mut() {
+                    *map_index = reader.offset - self.map + 1;
+                    let len = reader.byte()? as usize;
+                    reader.take(len)?;
+                }
+            }
+        }
+        reader.align(2);
+        Ok(())
+    }
+
+    // Plies to zeroing of a stored DTZ value
+    fn map_score(&self, file: usize, value: u16, wdl: Wdl) -> Result<i32, ProbeError> {
+        let part = &self.parts[file][0];
+        let mut value = value as i32;
+        if part.flags & FLAG_MAPPED != 0 {
+            let map = part.map_index[match wdl {
+                Wdl::Loss => 1,
+                Wdl::BlessedLoss => 3,
+                Wdl::CursedWin => 2,
+                Wdl::Draw | Wdl::Win => 0,
+            }];
+            let mapped = if part.flags & FLAG_WIDE != 0 {
+                read_u16(&self.bytes, self.map + 2 * (map + value as usize)).map(i32::from)
+            } else {
+                self.bytes.get(self.map + map + value as usize).map(|value| *value as i32)
+            };
+            value = mapped.ok_or(ProbeError::Corrupt(format!("DTZ value {} is not in the map", value)))?;
+        }
+        let in_moves = match wdl {
+            Wdl::Win => part.flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => part.flags & FLAG_LOSS_PLIES == 0,
+            _ => true,
+        };
+        Ok(if in_moves { 2 * value + 1 } else { value + 1 })
+    }
+
+    // The part and index of a position with this material. None when a DTZ table has the
+    // other side to move.
+    fn encode(&self, board: &[u8; 64], white_to_move: bool) -> Result<Option<(usize, usize, u64)>, ProbeError> {
+        let maps = index_maps();
+        let material = &self.material;
+
+        // The stronger side is white in the table, with equal material the side to move is
+        let flip = board_material_name(board) != material.name || (material.symmetric && !white_to_move);
+        let (flip_color, flip_squares) = if flip { (8, 56) } else { (0, 0) };
+        let side = (flip == white_to_move) as usize;
+
+        let occupied: Vec<(usize, u8)> = (0..64)
+            .filter(|square| board[square ^ 56] != TYPES::NONE)
+            .map(|square| (square, syzygy_piece(board[square ^ 56])))
+            .collect();
+        let mut squares = Vec::with_capacity(occupied.len());
+        let mut pieces = Vec::with_capacity(occupied.len());
+
+        // With pawns the tables are split by the file of the leading pawn, the one nearest the
+        // a file and then the second rank
+        let mut lead = None;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+        if material.pawns {
+            let lead_pawn = self.parts[0][0].pieces[0] ^ flip_color;
+            for (square, _) in occupied.iter().filter(|(_, piece)| *piece == lead_pawn) {
+                squares.push(square ^ flip_squares);
+                pieces.push(lead_pawn ^ flip_color);
+            }
+            lead = Some(lead_pawn);
+            lead_pawns = squares.len();
+            let leading = (0..lead_pawns).max_by_key(|i| maps.map_pawns[squares[*i]]).unwrap_or(0);
+            squares.swap(0, leading);
+            file = (squares[0] % 8).min(7 - squares[0] % 8);
+        }
+        if self.dtz && (self.parts[file][0].flags & FLAG_STM) as usize != side && (material.pawns || !material.symmetric) {
+            return Ok(None);
+        }
+        for (square, piece) in occupied.iter().filter(|(_, piece)| Some(*piece) != lead) {
+            squares.push(square ^ flip_squares);
+            pieces.push(piece ^ flip_color);
+        }
+        let part = self.parts[file].get(if self.dtz { 0 } else { side }).ok_or(ProbeError::Corrupt("a side to move is missing".to_string()))?;
+        if squares.len() != part.pieces.len() {
+            return Err(ProbeError::Corrupt(format!("{} pieces in a table of {}", squares.len(), part.pieces.len())));
+        }
+
+        // Put the pieces in the order the table encodes them
+        for i in lead_pawns..squares.len() {
+            if let Some(j) = (i..squares.len()).find(|j| pieces[*j] == part.pieces[i]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            } else {
+                return Err(ProbeError::Corrupt(format!("no piece {} for {}", part.pieces[i], material.name)));
+            }
+        }
+
+        // Turn the board until the first piece is in the a1-d1-d4 triangle, with pawns only
+        // mirroring the files is allowed
+        if squares[0] % 8 > 3 {
+            for square in squares.iter_mut() {
+                *square ^= 7;
+            }
+        }
+        let mut index;
+        if material.pawns {
+            index = maps.lead_pawn_index[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|square| maps.map_pawns[*square]);
+            for (i, square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                index += maps.binomial[i][maps.map_pawns[*square]];
+            }
+        } else {
+            if squares[0] / 8 > 3 {
+                for square in squares.iter_mut() {
+                    *square ^= 56;
+                }
+            }
+            // The first piece of the leading group off the a1-h8 diagonal goes below it
+            for i in 0..part.group_len[0] {
+                let off = off_diagonal(squares[i]);
+                if off > 0 {
+                    for square in squares[i..].iter_mut() {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                if off != 0 {
+                    break;
+                }
+            }
+            index = if material.unique_pieces {
+                let (first, second, third) = (squares[0], squares[1], squares[2]);
+                let adjust1 = (second > first) as u64;
+                let adjust2 = (third > first) as u64 + (third > second) as u64;
+                let rank = |square: usize| (square / 8) as u64;
+                if off_diagonal(first) != 0 {
+                    (maps.map_a1d1d4[first] as u64 * 63 + second as u64 - adjust1) * 62 + third as u64 - adjust2
+                } else if off_diagonal(second) != 0 {
+                    (6 * 63 + rank(first) * 28 + maps.map_b1h1h7[second]) * 62 + third as u64 - adjust2
+                } else if off_diagonal(third) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + rank(first) * 7 * 28 + (rank(second) - adjust1) * 28 + maps.map_b1h1h7[third]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank(first) * 7 * 6 + (rank(second) - adjust1) * 6 + rank(third) - adjust2
+                }
+            } else {
+                maps.map_kk[maps.map_a1d1d4[squares[0]]][squares[1]]
+            };
+        }
+
+        // The other groups choose their squares among those the earlier groups left
+        index *= part.group_idx[0];
+        let mut start = part.group_len[0];
+        let mut other_pawns = material.pawns && material.pawn_counts[1] > 0;
+        for (len, multiplier) in part.group_len[1..].iter().take_while(|len| **len > 0).zip(part.group_idx[1..].iter()) {
+            squares[start..start + len].sort_unstable();
+            let mut group = 0;
+            for i in 0..*len {
+                let square = squares[start + i];
+                let below = squares[..start].iter().filter(|earlier| square > **earlier).count();
+                group += maps.binomial[i + 1][square - below - if other_pawns { 8 } else { 0 }];
+            }
+            other_pawns = false;
+            index += group * multiplier;
+            start += len;
+        }
+        Ok(Some((file, side, index)))
+    }
+}
+
+// Little endian reads through a table file that fail at its end
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], ProbeError> {
+        if count == 0 {
+            return Ok(&[]);
+        }
+        let taken = self.bytes.get(self.offset..self.offset + count).ok_or(ProbeError::Corrupt(format!("the file ends before byte {}", self.offset + count)))?;
+        self.offset += count;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, ProbeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ProbeError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ProbeError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn align(&mut self, alignment: usize) {
+        self.offset = self.offset.div_ceil(alignment) * alignment;
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let word = bytes.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([word[0], word[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let word = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+}
+
+// Codes are read big endian, and the last ones of a block may be read past the end of the file
+fn read_u32_or_zero(bytes: &[u8], offset: usize) -> u32 {
+    let mut word = [0u8; 4];
+    for (i, byte) in word.iter_mut().enumerate() {
+        *byte = bytes.get(offset + i).copied().unwrap_or(0);
+    }
+    u32::from_be_bytes(word)
+}
+
+/// Writes the WDL and DTZ tables of a three piece ending, given the distance to mate of its
+/// positions and, with a pawn, of the positions its captures and promotions lead to. Without
+/// pawns nothing but mate ends a won game, so the distance to mate is also the distance to
+/// zeroing; with a pawn it is worked out from the moves. Every position is read back before
+/// the files are written.
+pub(crate) fn write_tables<F>(signature: &str, probe: F, directory: &Path) -> Result<(), String>
+where
+    F: Fn(&[u8; 64], u8) -> Option<Dtm>,
+{
+    let material = Material::new(signature);
+    let black_pawns = signature.split('v').nth(1).is_some_and(|black| black.contains('P'));
+    if !is_material_name(signature) || material.piece_count != 3 || material.symmetric || black_pawns {
+        return Err(format!("{} cannot be written as a Syzygy table, only three piece endings with any pawn on the first side can", signature));
+    }
+    let mut board_pieces: Vec<u8> = signature.split('v').zip([COLORS::WHITE, COLORS::BLACK]).flat_map(|(side, color)| {
+        side.chars().map(move |letter| color | match letter {
+            'K' => TYPES::KING,
+            'Q' => TYPES::QUEEN,
+            'R' => TYPES::ROOK,
+            'B' => TYPES::BISHOP,
+            'N' => TYPES::KNIGHT,
+            _ => TYPES::PAWN,
+        })
+    }).collect();
+    board_pieces.sort_by_key(|piece| piece & TYPES::PAWN == 0);   // the leading pawn is encoded first
+    let codes: Vec<u8> = board_pieces.iter().map(|piece| syzygy_piece(*piece)).collect();
+
+    let files = if material.pawns { 4 } else { 1 };
+    let layout = |dtz: bool, sides: usize, flags: u8| {
+        let parts = (0..files).map(|file| {
+            let mut parts = vec![Part { flags, pieces: codes.clone(), ..Part::default() }; sides];
+            for part in parts.iter_mut() {
+                part.set_groups(&material, [0, 0xf], file);
+            }
+            parts
+        }).collect();
+        Table { bytes: Vec::new(), material: material.clone(), dtz, parts, map: 0 }
+    };
+    let wdl = layout(false, 2, 0);
+    let dtz = layout(true, 1, FLAG_WIN_PLIES | FLAG_LOSS_PLIES);      // white to move, in plies
+    let distances = if material.pawns { Some(zeroing_distances(&board_pieces, &probe)?) } else { None };
+
+    // Every placement of the pieces, positions that share an index must agree
+    let empty = |table: &Table| -> Vec<Vec<Vec<Option<u16>>>> {
+        table.parts.iter().map(|parts| parts.iter().map(|part| vec![None; part.size() as usize]).collect()).collect()
+    };
+    let mut wdl_values = empty(&wdl);
+    let mut dtz_values = empty(&dtz);
+    let store = |slot: &mut Option<u16>, value: u16, index: u64| match *slot {
+        Some(stored) if stored != value => Err(format!("positions with index {} have the values {} and {}", index, stored, value)),
+        _ => {
+            *slot = Some(value);
+            Ok(())
+        }
+    };
+    for (placement, board) in placements(&board_pieces) {
+        for (side_to_move, turn) in [COLORS::WHITE, COLORS::BLACK].iter().copied().enumerate() {
+            let result = match probe(&board, turn) {
+                Some(result) => result,
+                None => continue,
+            };
+            let white_to_move = turn == COLORS::WHITE;
+            if let Some((file, side, index)) = wdl.encode(&board, white_to_move).map_err(|error| error.to_string())? {
+                store(&mut wdl_values[file][side][index as usize], Wdl::from_dtm(result) as u16, index)?;
+            }
+            let plies = match (result, &distances) {
+                (Dtm::Draw, _) => continue,                         // not stored, the WDL table knows
+                (_, Some(distances)) => distances[2 * placement + side_to_move] - 1,
+                (Dtm::Win(plies), None) => plies - 1,
+                (Dtm::Loss(plies), None) => plies.max(1) - 1,
+            };
+            if let Some((file, _, index)) = dtz.encode(&board, white_to_move).map_err(|error| error.to_string())? {
+                store(&mut dtz_values[file][0][index as usize], plies, index)?;
+            }
+        }
+    }
+
+    for (table, values, magic, extension) in [(&wdl, &wdl_values, WDL_MAGIC, "rtbw"), (&dtz, &dtz_values, DTZ_MAGIC, "rtbz")] {
+        let packed = values.iter().map(|sides| {
+            sides.iter().zip(table.parts[0].iter()).map(|(values, part)| PackedPart::pack(values, part.flags)).collect()
+        }).collect::<Result<Vec<Vec<PackedPart>>, String>>()?;
+        let bytes = table_bytes(magic, &codes, &packed, table.dtz);
+        let written = Table::parse(material.clone(), table.dtz, bytes).map_err(|error| error.to_string())?;
+        for (file, sides) in values.iter().enumerate() {
+            for (side, values) in sides.iter().enumerate() {
+                for (index, value) in values.iter().enumerate().filter_map(|(index, value)| Some((index, (*value)?))) {
+                    if written.parts[file][side].decompress(&written.bytes, index as u64) != Ok(value) {
+                        return Err(format!("{}.{} does not read back {} at {}", signature, extension, value, index));
+                    }
+                }
+            }
+        }
+        let path = directory.join(format!("{}.{}", signature, extension));
+        fs::write(&path, &written.bytes).map_err(|error| format!("cannot write {}: {}", path.display(), error))?;
+    }
+    Ok(())
+}
+
+// Every board with the pieces on distinct squares and pawns off the back ranks, numbered by
+// the squares of the pieces in order
+fn placements(pieces: &[u8]) -> impl Iterator<Item = (usize, [u8; 64])> + '_ {
+    (0..64usize.pow(pieces.len() as u32)).filter_map(move |placement| {
+        let mut board = [TYPES::NONE; 64];
+        for (i, piece) in pieces.iter().enumerate() {
+            let tile = placement / 64usize.pow((pieces.len() - 1 - i) as u32) % 64;
+            if board[tile] != TYPES::NONE || (piece & TYPES::PAWN != 0 && !(8..56).contains(&tile)) {
+                return None;
+            }
+            board[tile] = *piece;
+        }
+        Some((placement, board))
+    })
+}
+
+// The number `placements` gives a board, None when the pieces differ
+fn placement_of(pieces: &[u8], board: &[u8; 64]) -> Option<usize> {
+    if board.iter().filter(|piece| **piece != TYPES::NONE).count() != pieces.len() {
+        return None;
+    }
+    pieces.iter().try_fold(0, |placement, piece| Some(placement * 64 + board.iter().position(|on_board| on_board == piece)?))
+}
+
+// Plies to the move that zeroes the fifty move counter of every won or lost position, by
+// placement and side to move, mate counting as zeroing. The winner heads for the nearest
+// zeroing move and the loser for the furthest, so the distances are found level by level:
+// the wins a move before a loss of the level below, and the losses whose every move reaches
+// a win of the level below at most.
+fn zeroing_distances<F>(pieces: &[u8], probe: &F) -> Result<Vec<u16>, String>
+where
+    F: Fn(&[u8; 64], u8) -> Option<Dtm>,
+{
+    let mut game = crate::init_game_from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let mut distances = vec![0u16; 2 * 64usize.pow(pieces.len() as u32)];
+    let mut undecided: Vec<(usize, bool, Vec<usize>)> = Vec::new();     // with the positions the other moves reach
+    for (placement, board) in placements(pieces) {
+        for (side_to_move, turn) in [COLORS::WHITE, COLORS::BLACK].iter().copied().enumerate() {
+            let wins = match probe(&board, turn) {
+                Some(Dtm::Win(_)) => true,
+                Some(Dtm::Loss(_)) => false,
+                _ => continue,
+            };
+            let position = 2 * placement + side_to_move;
+            game.board = board;
+            game.turn = turn;
+            movegen::sync_derived_state(&mut game);
+            let mut children = Vec::new();
+            for played in movegen::generate_legal_moves(&game) {
+                let zeroing = movegen::is_capture(&game, played) || game.board[played.from as usize] & TYPES::PAWN != 0;
+                movegen::make_move(&mut game, played);
+                let result = probe(&game.board, game.turn).ok_or_else(|| format!("{} has no table", board_material_name(&game.board)));
+                let child = placement_of(pieces, &game.board).map(|placement| 2 * placement + 1 - side_to_move);
+                movegen::unmake_move(&mut game);
+                match (wins, result?) {
+                    (true, Dtm::Loss(0)) => distances[position] = 1,
+                    (true, Dtm::Loss(_)) if zeroing => distances[position] = 1,
+                    (false, _) if zeroing => {}
+                    (true, Dtm::Loss(_)) | (false, _) => children.push(child.ok_or("a move that keeps the material changed it")?),
+                    _ => {}
+                }
+            }
+            if !wins && children.is_empty() {
+                distances[position] = 1;                            // mated, or every move zeroes
+            }
+            if distances[position] == 0 {
+                undecided.push((position, wins, children));
+            }
+        }
+    }
+
+    let mut level = 1;
+    loop {
+        level += 1;
+        let mut found = false;
+        for (position, wins, children) in undecided.iter() {
+            if distances[*position] != 0 {
+                continue;
+            }
+            let reached = if *wins {
+                children.iter().any(|child| distances[*child] == level - 1)
+            } else {
+                children.iter().all(|child| distances[*child] != 0) && children.iter().map(|child| distances[*child]).max() == Some(level - 1)
+            };
+            if reached {
+                distances[*position] = level;
+                found = true;
+            }
+        }
+        if !found {
+            break;
+        }
+    }
+    if undecided.iter().any(|(position, _, _)| distances[*position] == 0) {
+        return Err("a won or lost position never reaches a zeroing move".to_string());
+    }
+    Ok(distances)
+}
+
+// The header, sizes, sparse index, block lengths and blocks of one part being written
+struct PackedPart {
+    sizes: Vec<u8>,
+    sparse_index: Vec<u8>,
+    block_lengths: Vec<u8>,
+    blocks: Vec<u8>,
+}
+
+impl PackedPart {
+    // Positions without a value repeat the one before them. The most frequent pair of
+    // neighbouring symbols becomes a symbol of its own while pairs are common enough, then
+    // the symbols get canonical Huffman codes, which fill the blocks.
+    fn pack(values: &[Option<u16>], flags: u8) -> Result<PackedPart, String> {
+        let mut leaves: Vec<u16> = values.iter().filter_map(|value| *value).collect();
+        leaves.sort_unstable();
+        leaves.dedup();
+        if leaves.len() <= 1 {
+            let value = leaves.first().copied().unwrap_or(0);
+            return Ok(PackedPart { sizes: vec![flags | FLAG_SINGLE_VALUE, value as u8], sparse_index: Vec::new(), block_lengths: Vec::new(), blocks: Vec::new() });
+        }
+        let mut last = values.iter().find_map(|value| *value).unwrap_or(0);
+        let mut sequence: Vec<u16> = values.iter().map(|value| {
+            last = value.unwrap_or(last);
+            leaves.binary_search(&last).unwrap() as u16
+        }).collect();
+
+        let mut symbols: Vec<(u16, u16)> = leaves.iter().map(|value| (*value, 0xfff)).collect();
+        let mut covered = vec![1; symbols.len()];                  // values a symbol stands for
+        while symbols.len() < WRITE_MAX_SYMBOLS {
+            let mut counts: HashMap<(u16, u16), usize> = HashMap::new();
+            for pair in sequence.windows(2).filter(|pair| covered[pair[0] as usize] + covered[pair[1] as usize] <= WRITE_MAX_SYMBOL_VALUES) {
+                *counts.entry((pair[0], pair[1])).or_insert(0) += 1;
+            }
+            let (pair, count) = match counts.into_iter().max_by_key(|(pair, count)| (*count, std::cmp::Reverse(*pair))) {
+                Some(best) => best,
+                None => break,
+            };
+            if count < WRITE_MIN_PAIR_COUNT {
+                break;
+            }
+            let symbol = symbols.len() as u16;
+            symbols.push(pair);
+            covered.push(covered[pair.0 as usize] + covered[pair.1 as usize]);
+            let mut merged = Vec::with_capacity(sequence.len());
+            let mut i = 0;
+            while i < sequence.len() {
+                if i + 1 < sequence.len() && (sequence[i], sequence[i + 1]) == pair {
+                    merged.push(symbol);
+                    i += 2;
+                } else {
+                    merged.push(sequence[i]);
+                    i += 1;
+                }
+            }
+            sequence = merged;
+        }
+
+        // Longer codes take the lower symbol numbers, the symbols only pairs use come last
+        let mut frequency = vec![0; symbols.len()];
+        for symbol in sequence.iter() {
+            frequency[*symbol as usize] += 1;
+        }
+        let lengths = code_lengths(&frequency);
+        let min_bits = lengths.iter().copied().filter(|len| *len > 0).min().unwrap_or(1);
+        let max_bits = lengths.iter().copied().max().unwrap_or(1);
+        if max_bits > 32 {
+            return Err(format!("codes of {} bits do not fit the format", max_bits));
+        }
+        let mut order: Vec<usize> = (0..symbols.len()).collect();
+        order.sort_by_key(|symbol| (lengths[*symbol] == 0, std::cmp::Reverse(lengths[*symbol]), *symbol));
+        let mut number = vec![0u16; symbols.len()];
+        for (position, symbol) in order.iter().enumerate() {
+            number[*symbol] = position as u16;
+        }
+        let count = |len: usize| lengths.iter().filter(|symbol_len| **symbol_len == len).count() as u64;
+        let mut lowest_symbol = vec![0u64; max_bits - min_bits + 1];
+        let mut base = vec![0u64; max_bits - min_bits + 1];
+        for i in (0..lowest_symbol.len() - 1).rev() {
+            lowest_symbol[i] = lowest_symbol[i + 1] + count(min_bits + i + 1);
+            base[i] = (base[i + 1] + count(min_bits + i + 1)) / 2;
+        }
+
+        let block_size = 1usize << WRITE_BLOCK_BITS;
+        let mut sizes = vec![flags, WRITE_BLOCK_BITS, WRITE_SPAN_BITS, 0];
+        let mut block_lengths = Vec::new();
+        let mut blocks = Vec::new();
+        let mut block_starts = vec![0];                            // the first value of every block
+        let mut block = vec![0u8; block_size];
+        let (mut block_bits, mut block_values) = (0, 0);
+        for symbol in sequence.iter().map(|symbol| *symbol as usize) {
+            let (len, i) = (lengths[symbol], lengths[symbol] - min_bits);
+            if block_bits + len > 8 * block_size {
+                block_lengths.extend_from_slice(&(block_values as u16 - 1).to_le_bytes());
+                blocks.append(&mut block);
+                block = vec![0u8; block_size];
+                block_starts.push(block_starts[block_starts.len() - 1] + block_values);
+                block_bits = 0;
+                block_values = 0;
+            }
+            let code = base[i] + number[symbol] as u64 - lowest_symbol[i];
+            for bit in 0..len {
+                if code >> (len - 1 - bit) & 1 != 0 {
+                    block[(block_bits + bit) / 8] |= 0x80 >> ((block_bits + bit) % 8);
+                }
+            }
+            block_bits += len;
+            block_values += covered[symbol];
+        }
+        block_lengths.extend_from_slice(&(block_values as u16 - 1).to_le_bytes());
+        blocks.append(&mut block);
+        sizes.extend_from_slice(&(block_starts.len() as u32).to_le_bytes());
+        sizes.extend_from_slice(&[max_bits as u8, min_bits as u8]);
+        for lowest in lowest_symbol.iter() {
+            sizes.extend_from_slice(&(*lowest as u16).to_le_bytes());
+        }
+        sizes.extend_from_slice(&(symbols.len() as u16).to_le_bytes());
+        for symbol in order.iter() {
+            let (left, right) = match symbols[*symbol] {
+                (value, 0xfff) => (value, 0xfff),
+                (left, right) => (number[left as usize], number[right as usize]),
+            };
+            sizes.extend_from_slice(&[left as u8, (left >> 8) as u8 | (right << 4) as u8, (right >> 4) as u8]);
+        }
+        if symbols.len() % 2 == 1 {
+            sizes.push(0);
+        }
+
+        let span = 1usize << WRITE_SPAN_BITS;
+        let mut sparse_index = Vec::new();
+        for entry in 0..values.len().div_ceil(span) {
+            let middle = entry * span + span / 2;
+            let block = block_starts.partition_point(|start| *start <= middle) - 1;
+            sparse_index.extend_from_slice(&(block as u32).to_le_bytes());
+            sparse_index.extend_from_slice(&((middle - block_starts[block]) as u16).to_le_bytes());
+        }
+        Ok(PackedPart { sizes, sparse_index, block_lengths, blocks })
+    }
+}
+
+// Huffman code lengths of the symbols that occur, zero for the others
+fn code_lengths(frequency: &[usize]) -> Vec<usize> {
+    let mut lengths = vec![0; frequency.len()];
+    let mut parent = vec![usize::MAX; frequency.len()];         // merged nodes are numbered after the symbols
+    let mut heap: BinaryHeap<std::cmp::Reverse<(usize, usize)>> = frequency.iter().enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(symbol, count)| std::cmp::Reverse((*count, symbol)))
+        .collect();
+    if heap.len() == 1 {
+        return frequency.iter().map(|count| (*count > 0) as usize).collect();
+    }
+    while heap.len() > 1 {
+        let (std::cmp::Reverse((first_count, first)), std::cmp::Reverse((second_count, second))) = (heap.pop().unwrap(), heap.pop().unwrap());
+        parent[first] = parent.len();
+        parent[second] = parent.len();
+        heap.push(std::cmp::Reverse((first_count + second_count, parent.len())));
+        parent.push(usize::MAX);
+    }
+    for (symbol, len) in lengths.iter_mut().enumerate().filter(|(symbol, _)| frequency[*symbol] > 0) {
+        let mut node = symbol;
+        while parent[node] != usize::MAX {
+            *len += 1;
+            node = parent[node];
+        }
+    }
+    lengths
+}
+
+// Parts by leading pawn file, then side to move
+fn table_bytes(magic: [u8; 4], codes: &[u8], parts: &[Vec<PackedPart>], dtz: bool) -> Vec<u8> {
+    let mut bytes = magic.to_vec();
+    bytes.push(if parts.len() > 1 { 1 | 2 } else { 1 });        // the sides differ, and pawns
+    for _ in parts {
+        bytes.push(0);                                          // the leading group is encoded first
+        bytes.extend(codes.iter().map(|code| code | code << 4));
+    }
+    let align = |bytes: &mut Vec<u8>, alignment: usize| bytes.resize(bytes.len().div_ceil(alignment) * alignment, 0);
+    align(&mut bytes, 2);
+    for part in parts.iter().flatten() {
+        bytes.extend_from_slice(&part.sizes);
+    }
+    if dtz {
+        align(&mut bytes, 2);                                   // no value map
+    }
+    for part in parts.iter().flatten() {
+        bytes.extend_from_slice(&part.sparse_index);
+    }
+    for part in parts.iter().flatten() {
+        bytes.extend_from_slice(&part.block_lengths);
+    }
+    for part in parts.iter().flatten() {
+        align(&mut bytes, 64);
+        bytes.extend_from_slice(&part.blocks);
+    }
+    bytes
+}
@@ -0,0 +1,303 @@
+use crate::movegen::{self, Move, SLIDING_OFFSETS};
+use crate::move_picker::SEE_VALUES;
+use crate::movement::{attackers_of, attackers_of_tile, least_valuable_attacker};
+use crate::notation::move_to_san;
+use crate::{enemy_color, memory_location_to_algebraic_notation, piece_type_index, Square, COLORS, GAME, TYPES};
+
+const PIECE_NAMES: [&str; 6] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
+
+/// A piece the other side can win: attacked and undefended, attacked by something worth less,
+/// or attacked more often than it is defended. Kings are never hanging.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HangingPiece {
+    pub tile: Square,
+    pub attackers: Vec<Square>,
+    pub defenders: Vec<Square>,
+}
+
+/// A piece that cannot leave the line of the pinner without giving up the piece behind it. The
+/// pin is absolute when that piece is the king, and then the pinned piece may not leave at all.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pin {
+    pub pinner: Square,
+    pub pinned: Square,
+    pub behind: Square,
+    pub absolute: bool,
+}
+
+/// A slider attacking a piece that has to move away and leave a lesser piece behind it to be
+/// taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Skewer {
+    pub attacker: Square,
+    pub front: Square,
+    pub behind: Square,
+}
+
+/// A piece of the side to move standing between one of its own sliders and an enemy piece,
+/// which opens the attack as soon as it steps off the line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiscoveredAttack {
+    pub slider: Square,
+    pub blocker: Square,
+    pub target: Square,
+}
+
+/// A move after which the moved piece, safe on its square, attacks two or more pieces that are
+/// worth taking.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fork {
+    pub played: Move,
+    pub targets: Vec<Square>,
+}
+
+/// What a glance at the board shows: hanging pieces, pins and skewers of both sides, and the
+/// discovered attacks, forks and checks the side to move has. Attackers and defenders are the
+/// pieces that reach the tile directly, batteries are not looked through.
+/// # A knight fork
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("r3k3/8/8/3N4/8/8/8/4K3 w - - 0 1").unwrap();
+/// let tactics = find_tactics(&game);
+/// let forks: Vec<String> = tactics.forks.iter().map(|fork| fork.played.to_uci()).collect();
+/// assert_eq!(forks, vec!["d5c7"]);
+/// assert_eq!(tactics.forks[0].targets.len(), 2);
+/// assert!(tactics.checks.iter().any(|check| check.to_uci() == "d5f6"));
+/// // Nothing attacks the undefended rook yet
+/// assert!(tactics.hanging.is_empty());
+/// assert!(tactics.to_lines(&game).contains(&"fork: Nc7+ hits rook a8, king e8".to_string()));
+///```
+/// # Pins and skewers
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("k3r3/8/8/8/1b6/2N5/3QN3/4K3 w - - 0 1").unwrap();
+/// let tactics = find_tactics(&game);
+/// let e2 = algebraic_notation_to_memory_location("e2");
+/// let c3 = algebraic_notation_to_memory_location("c3");
+/// assert!(tactics.pins.iter().any(|pin| pin.pinned == e2 && pin.absolute));
+/// assert!(tactics.pins.iter().any(|pin| pin.pinned == c3 && !pin.absolute));
+///
+/// let game = init_game_from_fen("8/8/8/8/q2k3R/8/8/1K6 b - - 0 1").unwrap();
+/// let tactics = find_tactics(&game);
+/// assert_eq!(tactics.skewers.len(), 1);
+/// assert_eq!(tactics.skewers[0].behind, algebraic_notation_to_memory_location("a4"));
+/// // The black king stands between its own queen and the rook
+/// assert!(tactics.discovered.iter().any(|discovered| discovered.target == algebraic_notation_to_memory_location("h4")));
+///```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tactics {
+    pub hanging: Vec<HangingPiece>,
+    pub pins: Vec<Pin>,
+    pub skewers: Vec<Skewer>,
+    pub discovered: Vec<DiscoveredAttack>,
+    pub forks: Vec<Fork>,
+    pub checks: Vec<Move>,
+}
+
+impl Tactics {
+    pub fn is_empty(&self) -> bool {
+        self.hanging.is_empty() && self.pins.is_empty() && self.skewers.is_empty()
+            && self.discovered.is_empty() && self.forks.is_empty() && self.checks.is_empty()
+    }
+
+    /// One line per finding, with moves in SAN.
+    pub fn to_lines(&self, game: &GAME) -> Vec<String> {
+        let mut game = game.clone();
+        movegen::sync_derived_state(&mut game);
+        let board = game.board;
+        let mut lines = Vec::new();
+        for piece in self.hanging.iter() {
+            lines.push(format!(
+                "hanging: {}, {} attackers, {} defenders",
+                colored_piece_on(&board, piece.tile), piece.attackers.len(), piece.defenders.len(),
+            ));
+        }
+        for pin in self.pins.iter() {
+            lines.push(format!(
+                "{} pin: {} pins {} to {}",
+                if pin.absolute { "absolute" } else { "relative" },
+                colored_piece_on(&board, pin.pinner), piece_on(&board, pin.pinned), piece_on(&board, pin.behind),
+            ));
+        }
+        for skewer in self.skewers.iter() {
+            lines.push(format!(
+                "skewer: {} skewers {} and {}",
+                colored_piece_on(&board, skewer.attacker), piece_on(&board, skewer.front), piece_on(&board, skewer.behind),
+            ));
+        }
+        for discovered in self.discovered.iter() {
+            lines.push(format!(
+                "discovered attack: moving {} lets {} hit {}",
+                piece_on(&board, discovered.blocker), piece_on(&board, discovered.slider), piece_on(&board, discovered.target),
+            ));
+        }
+        for fork in self.forks.iter() {
+            let targets: Vec<String> = fork.targets.iter().map(|tile| piece_on(&board, *tile)).collect();
+            lines.push(format!("fork: {} hits {}", move_to_san(&game, fork.played), targets.join(", ")));
+        }
+        for check in self.checks.iter() {
+            lines.push(format!("check: {}", move_to_san(&game, *check)));
+        }
+        lines
+    }
+}
+
+/// Looks for hanging pieces, pins, skewers, discovered attacks, forks and checks.
+pub fn find_tactics(game: &GAME) -> Tactics {
+    let mut game = game.clone();
+    movegen::sync_derived_state(&mut game);
+    let mut tactics = Tactics::default();
+    for color in [COLORS::WHITE, COLORS::BLACK].iter() {
+        tactics.hanging.extend(hanging_pieces(&game, *color));
+        find_lines(&game, *color, &mut tactics);
+    }
+    find_forks_and_checks(&mut game, &mut tactics);
+    tactics
+}
+
+fn value(piece: u8) -> i32 {
+    SEE_VALUES[piece_type_index(piece)]
+}
+
+fn is_king(piece: u8) -> bool {
+    piece & TYPES::KING > 0
+}
+
+fn piece_on(board: &[u8; 64], tile: usize) -> String {
+    format!("{} {}", PIECE_NAMES[piece_type_index(board[tile])], memory_location_to_algebraic_notation(tile))
+}
+
+fn colored_piece_on(board: &[u8; 64], tile: usize) -> String {
+    let color = if board[tile] & COLORS::WHITE > 0 { "white" } else { "black" };
+    format!("{} {}", color, piece_on(board, tile))
+}
+
+// Whether a piece is attacked at all comes from least_valuable_attacker rather than from
+// get_all_attacked_squares: that one needs a &mut GAME and fills in every tile the enemy
+// attacks, where the attacker query answers for the one tile and also says by what
+fn hanging_pieces(game: &GAME, color: u8) -> Vec<HangingPiece> {
+    let enemy = enemy_color(color);
+    let board = &game.board;
+    let mut hanging = Vec::new();
+    for tile in 0..64 {
+        let piece = board[tile];
+        if piece & color == 0 || is_king(piece) {
+            continue;
+        }
+        let cheapest = match least_valuable_attacker(board, tile, enemy, &game.computed_distances) {
+            Some(attacker) => board[attacker],
+            None => continue,
+        };
+        let attackers = attackers_of(game, tile, enemy);
+        let defenders = attackers_of(game, tile, color);
+        if defenders.is_empty() || value(cheapest) < value(piece) || attackers.len() > defenders.len() {
+            hanging.push(HangingPiece { tile, attackers, defenders });
+        }
+    }
+    hanging
+}
+
+// The first two pieces on the line from the tile in the direction
+fn first_two_on_line(game: &GAME, tile: usize, direction: usize) -> (Option<usize>, Option<usize>) {
+    let mut found = (None, None);
+    for step in 1..game.computed_distances[tile][direction] as i8 + 1 {
+        let target_tile = (tile as i8 + SLIDING_OFFSETS[direction] * step) as usize;
+        if game.board[target_tile] != TYPES::NONE {
+            if found.0.is_none() {
+                found.0 = Some(target_tile);
+            } else {
+                found.1 = Some(target_tile);
+                break;
+            }
+        }
+    }
+    found
+}
+
+fn slides_along(piece: u8, direction: usize) -> bool {
+    piece & TYPES::QUEEN > 0
+        || (piece & TYPES::ROOK > 0 && direction < 4)
+        || (piece & TYPES::BISHOP > 0 && direction >= 4)
+}
+
+fn is_defended(board: &[u8; 64], tile: usize, game: &GAME) -> bool {
+    let color = board[tile] & (COLORS::WHITE | COLORS::BLACK);
+    !attackers_of_tile(board, tile, color, &game.computed_distances).is_empty()
+}
+
+// Pins, skewers and discovered attacks along the lines of the sliders of one color
+fn find_lines(game: &GAME, color: u8, tactics: &mut Tactics) {
+    let board = game.board;
+    let enemy = enemy_color(color);
+    let legal_moves = if game.turn == color { movegen::generate_legal_moves(game) } else { Vec::new() };
+    for slider in 0..64 {
+        if board[slider] & color == 0 {
+            continue;
+        }
+        for (direction, offset) in SLIDING_OFFSETS.iter().enumerate() {
+            if !slides_along(board[slider], direction) {
+                continue;
+            }
+            let (front, behind) = match first_two_on_line(game, slider, direction) {
+                (Some(front), Some(behind)) if board[behind] & enemy > 0 => (front, behind),
+                _ => continue,
+            };
+            let (front_piece, behind_piece) = (board[front], board[behind]);
+
+            if front_piece & enemy > 0 {
+                // The piece behind once the front one is gone
+                let mut opened = board;
+                opened[front] = TYPES::NONE;
+                let behind_falls = !is_defended(&opened, behind, game) || value(behind_piece) > value(board[slider]);
+                if is_king(behind_piece) {
+                    tactics.pins.push(Pin { pinner: slider, pinned: front, behind, absolute: true });
+                } else if is_king(front_piece) || value(front_piece) > value(behind_piece) {
+                    if behind_falls {
+                        tactics.skewers.push(Skewer { attacker: slider, front, behind });
+                    }
+                } else if value(behind_piece) > value(front_piece) && behind_falls {
+                    tactics.pins.push(Pin { pinner: slider, pinned: front, behind, absolute: false });
+                }
+            } else if game.turn == color {
+                let worth_it = is_king(behind_piece) || value(behind_piece) > value(board[slider]) || !is_defended(&board, behind, game);
+                let line = |tile: usize| {
+                    (1..game.computed_distances[slider][direction] as i8 + 1)
+                        .any(|step| (slider as i8 + offset * step) as usize == tile)
+                };
+                let steps_off = legal_moves.iter().any(|played| played.from as usize == front && !line(played.to as usize));
+                if worth_it && steps_off {
+                    tactics.discovered.push(DiscoveredAttack { slider, blocker: front, target: behind });
+                }
+            }
+        }
+    }
+}
+
+// Every move of the side to move that checks, and the ones that leave the moved piece safely
+// attacking two targets worth taking
+fn find_forks_and_checks(game: &mut GAME, tactics: &mut Tactics) {
+    let color = game.turn;
+    let enemy = enemy_color(color);
+    for played in movegen::generate_legal_moves(game) {
+        movegen::make_move(game, played);
+        if game.check {
+            tactics.checks.push(played);
+        }
+        let board = game.board;
+        let to = played.to as usize;
+        let forker_value = value(board[to]);
+        let targets: Vec<usize> = (0..64)
+            .filter(|tile| board[*tile] & enemy > 0)
+            .filter(|tile| attackers_of_tile(&board, *tile, color, &game.computed_distances).contains(&to))
+            .filter(|tile| is_king(board[*tile]) || value(board[*tile]) > forker_value || !is_defended(&board, *tile, game))
+            .collect();
+        let safe = match least_valuable_attacker(&board, to, enemy, &game.computed_distances) {
+            None => true,
+            Some(attacker) => is_defended(&board, to, game) && value(board[attacker]) >= forker_value,
+        };
+        if targets.len() > 1 && safe {
+            tactics.forks.push(Fork { played, targets });
+        }
+        movegen::unmake_move(game);
+    }
+}
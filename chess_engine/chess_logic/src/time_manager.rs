@@ -0,0 +1,181 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::movegen::Move;
+
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);      // reserved for communication lag each move
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+const MAX_MOVES_TO_GO: u32 = 50;
+const MAX_INSTABILITY_SCALE: f64 = 2.0;
+const SCORE_DROP_MARGIN: i32 = 30;
+
+/// Source of time for the search. Every reading is a duration since a fixed origin.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Duration;
+}
+
+pub struct WallClock {
+    origin: Instant,
+}
+
+impl WallClock {
+    pub fn new() -> WallClock {
+        WallClock { origin: Instant::now() }
+    }
+}
+
+impl Default for WallClock {
+    fn default() -> WallClock {
+        WallClock::new()
+    }
+}
+
+impl Clock for WallClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// Clock that only moves when told to, or by a fixed tick every time it is read,
+/// so timed searches can be tested without waiting. Clones share the same time.
+/// # Ticking
+///```
+/// use chess_logic::{Clock, MockClock};
+/// use std::time::Duration;
+/// let clock = MockClock::with_tick(Duration::from_millis(5));
+/// assert_eq!(clock.now(), Duration::from_millis(0));
+/// clock.advance(Duration::from_millis(100));
+/// assert_eq!(clock.now(), Duration::from_millis(105));
+///```
+#[derive(Clone, Default)]
+pub struct MockClock {
+    micros: Arc<AtomicU64>,
+    tick: u64,
+}
+
+impl MockClock {
+    pub fn new() -> MockClock {
+        MockClock::default()
+    }
+
+    pub fn with_tick(tick: Duration) -> MockClock {
+        MockClock { micros: Arc::new(AtomicU64::new(0)), tick: tick.as_micros() as u64 }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.micros.fetch_add(by.as_micros() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        Duration::from_micros(self.micros.fetch_add(self.tick, Ordering::SeqCst))
+    }
+}
+
+/// Decides how long one move may take. The soft limit is checked between iterations
+/// and grows while the best move keeps changing or the score falls, the hard limit
+/// aborts the search wherever it is.
+/// # Allocation
+///```
+/// use chess_logic::{MockClock, TimeManager};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// let clock = MockClock::new();
+/// let mut time = TimeManager::new(Arc::new(clock.clone()), Duration::from_secs(60), Duration::from_secs(1), None);
+/// assert!(time.soft_limit() < time.hard_limit());
+/// assert!(time.hard_limit() < Duration::from_secs(60));
+/// clock.advance(time.soft_limit());
+/// assert!(time.soft_limit_reached());
+/// assert!(!time.hard_limit_reached());
+///```
+pub struct TimeManager {
+    clock: Arc<dyn Clock>,
+    start: Duration,
+    soft_limit: Duration,
+    hard_limit: Duration,
+    instability: f64,
+    score_drop: f64,
+    previous_best_move: Option<Move>,
+    previous_score: Option<i32>,
+}
+
+impl TimeManager {
+    /// Allocation for a clock game: time left and increment of the side to move, and moves until the next time control.
+    pub fn new(clock: Arc<dyn Clock>, time_left: Duration, increment: Duration, moves_to_go: Option<u32>) -> TimeManager {
+        let available = time_left.saturating_sub(MOVE_OVERHEAD);
+        let moves_to_go = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, MAX_MOVES_TO_GO);
+
+        // The increment only counts once it is certain to arrive, so it can never push the limits past the clock
+        let soft_limit = available / moves_to_go + increment * 3 / 4;
+        let hard_limit = if moves_to_go == 1 { available * 9 / 10 } else { (soft_limit * 5).min(available / 2) };
+        TimeManager::with_limits(clock, soft_limit.min(hard_limit), hard_limit)
+    }
+
+    /// Fixed time per move, used to the full.
+    pub fn for_move_time(clock: Arc<dyn Clock>, move_time: Duration) -> TimeManager {
+        let limit = move_time.saturating_sub(MOVE_OVERHEAD).max(move_time / 2);
+        TimeManager::with_limits(clock, limit, limit)
+    }
+
+    fn with_limits(clock: Arc<dyn Clock>, soft_limit: Duration, hard_limit: Duration) -> TimeManager {
+        let start = clock.now();
+        TimeManager {
+            clock,
+            start,
+            soft_limit,
+            hard_limit,
+            instability: 0.0,
+            score_drop: 0.0,
+            previous_best_move: None,
+            previous_score: None,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.clock.now().saturating_sub(self.start)
+    }
+
+    /// The soft limit after scaling for instability and falling scores, never beyond the hard limit.
+    pub fn soft_limit(&self) -> Duration {
+        let scale = (1.0 + self.instability + self.score_drop).min(MAX_INSTABILITY_SCALE);
+        self.soft_limit.mul_f64(scale).min(self.hard_limit)
+    }
+
+    pub fn hard_limit(&self) -> Duration {
+        self.hard_limit
+    }
+
+    pub fn soft_limit_reached(&self) -> bool {
+        self.elapsed() >= self.soft_limit()
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        self.elapsed() >= self.hard_limit
+    }
+
+    /// Called after every completed iteration with its best move and score.
+    ///```
+    /// use chess_logic::{Move, MockClock, TimeManager};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// let mut time = TimeManager::new(Arc::new(MockClock::new()), Duration::from_secs(60), Duration::ZERO, None);
+    /// let stable_limit = time.soft_limit();
+    /// time.update(Move::new(52, 36, 0), 20);
+    /// time.update(Move::new(51, 35, 0), -40);
+    /// assert!(time.soft_limit() > stable_limit);
+    ///```
+    pub fn update(&mut self, best_move: Move, score: i32) {
+        self.instability *= 0.5;                                // older changes count less
+        if self.previous_best_move.is_some_and(|previous| previous != best_move) {
+            self.instability += 0.5;
+        }
+        self.score_drop = match self.previous_score {
+            Some(previous) if previous - score > SCORE_DROP_MARGIN => ((previous - score) as f64 / 100.0).min(0.5),
+            _ => 0.0,
+        };
+        self.previous_best_move = Some(best_move);
+        self.previous_score = Some(score);
+    }
+}
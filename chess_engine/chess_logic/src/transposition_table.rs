@@ -0,0 +1,101 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::movegen::Move;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,                                                      // failed high, score is at least this
+    Upper,                                                      // failed low, score is at most this
+}
+
+#[derive(Clone, Copy)]
+pub struct TableEntry {
+    best_move: u16,
+    pub score: i32,
+    pub depth: i32,
+    pub bound: Bound,
+}
+
+impl TableEntry {
+    pub fn best_move(&self) -> Move {
+        Move::unpack(self.best_move)
+    }
+
+    // best move in bits 0-15, score 16-31, depth 32-39, bound 40-41 where 0 marks an empty slot
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        self.best_move as u64
+            | (self.score as i16 as u16 as u64) << 16
+            | (self.depth.clamp(i8::MIN as i32, i8::MAX as i32) as i8 as u8 as u64) << 32
+            | bound << 40
+    }
+
+    fn unpack(data: u64) -> Option<TableEntry> {
+        let bound = match (data >> 40) & 3 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => return None,
+        };
+        Some(TableEntry {
+            best_move: data as u16,
+            score: (data >> 16) as u16 as i16 as i32,
+            depth: (data >> 32) as u8 as i8 as i32,
+            bound,
+        })
+    }
+}
+
+/// Shared between search threads without locking. Each slot stores the key xor'ed
+/// with the data, so a slot torn by two threads writing at once fails the key check
+/// on the next probe instead of returning another position's entry.
+pub struct TranspositionTable {
+    entries: Vec<[AtomicU64; 2]>,                               // [key ^ data, data]
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let entry_size = std::mem::size_of::<[AtomicU64; 2]>();
+        let wanted_entries = (size_mb.max(1) * 1024 * 1024) / entry_size;
+        // Round down to a power of two so the index is a simple mask
+        let entry_count = 1 << (usize::BITS - 1 - wanted_entries.leading_zeros());
+        TranspositionTable { entries: (0..entry_count).map(|_| [AtomicU64::new(0), AtomicU64::new(0)]).collect() }
+    }
+
+    pub fn clear(&self) {
+        for [checked_key, data] in self.entries.iter() {
+            checked_key.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TableEntry> {
+        let [checked_key, data] = &self.entries[self.index(key)];
+        let data = data.load(Ordering::Relaxed);
+        if checked_key.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        TableEntry::unpack(data)
+    }
+
+    pub fn store(&self, key: u64, depth: i32, score: i32, bound: Bound, best_move: Move) {
+        let best_move = match self.probe(key) {
+            // Keep the old move when this search did not find one for the same position
+            Some(old) if best_move.is_none() => old.best_move,
+            _ => best_move.pack(),
+        };
+        let data = TableEntry { best_move, score, depth, bound }.pack();
+        let [checked_key, stored_data] = &self.entries[self.index(key)];
+        checked_key.store(key ^ data, Ordering::Relaxed);
+        stored_data.store(data, Ordering::Relaxed);
+    }
+}
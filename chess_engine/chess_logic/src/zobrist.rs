@@ -0,0 +1,65 @@
+use crate::COLORS;
+use crate::GAME;
+use crate::{piece_color_index, piece_type_index};
+
+const fn split_mix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut value = state;
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, value ^ (value >> 31))
+}
+
+const fn generate_keys() -> [u64; 781] {
+    let mut keys = [0u64; 781];
+    let mut state = 0x2545_F491_4F6C_DD1D;
+    let mut index = 0;
+    while index < keys.len() {
+        let (next_state, value) = split_mix(state);
+        state = next_state;
+        keys[index] = value;
+        index += 1;
+    }
+    keys
+}
+
+// 12 * 64 piece keys, 4 castling keys, 8 en passant file keys and the side to move key
+const KEYS: [u64; 781] = generate_keys();
+
+pub fn piece_key(piece: u8, tile: usize) -> u64 {
+    let piece_index = piece_color_index(piece) * 6 + piece_type_index(piece);
+    KEYS[piece_index * 64 + tile]
+}
+
+pub fn castling_key(castling_index: usize) -> u64 {
+    KEYS[768 + castling_index]
+}
+
+pub fn un_passant_key(tile: u8) -> u64 {
+    KEYS[772 + (tile % 8) as usize]
+}
+
+pub fn black_to_move_key() -> u64 {
+    KEYS[780]
+}
+
+pub fn compute_hash(game: &GAME) -> u64 {
+    let mut hash = 0;
+    for (tile, piece) in game.board.iter().enumerate() {
+        if *piece != 0 {
+            hash ^= piece_key(*piece, tile);
+        }
+    }
+    for (castling_index, allowed) in game.chastling_ability.iter().enumerate() {
+        if *allowed {
+            hash ^= castling_key(castling_index);
+        }
+    }
+    if game.tile_available_to_un_passant < 64 {
+        hash ^= un_passant_key(game.tile_available_to_un_passant);
+    }
+    if game.turn == COLORS::BLACK {
+        hash ^= black_to_move_key();
+    }
+    hash
+}
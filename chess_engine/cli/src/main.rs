@@ -3,23 +3,101 @@ use chess_logic::*;
 
 use std::{collections::HashMap};
 use std::io::{self, BufRead};
+use std::time::{Duration, Instant};
 
-fn main() {
-    let mut symbol_to_piece = HashMap::new();
-    symbol_to_piece.insert("bk", "\u{2654}");
-    symbol_to_piece.insert("bq", "\u{2655}");
-    symbol_to_piece.insert("br", "\u{2656}");
-    symbol_to_piece.insert("bb", "\u{2657}");
-    symbol_to_piece.insert("bn", "\u{2658}");
-    symbol_to_piece.insert("bp", "\u{2659}");
+use uci_client::UciClient;
 
-    symbol_to_piece.insert("wk", "\u{265A}");
-    symbol_to_piece.insert("wq", "\u{265B}");
-    symbol_to_piece.insert("wr", "\u{265C}");
-    symbol_to_piece.insert("wb", "\u{265D}");
-    symbol_to_piece.insert("wn", "\u{265E}");
-    symbol_to_piece.insert("wp", "\u{265F}");
+const BENCH_POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "2r3k1/pp3ppp/2n1b3/q2pP3/3P4/P1rB1N2/5PPP/R2Q1RK1 w - - 0 18",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+];
 
+#[allow(clippy::print_literal)]
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 && args[1] == "bench" {                  // cli bench [depth]
+        let depth = args.get(2).and_then(|depth| depth.parse::<u8>().ok()).unwrap_or(4);
+        run_bench(depth);
+        return;
+    }
+    if args.len() > 1 && args[1] == "analyze" {                // cli analyze [depth] [lines] [fen]
+        let depth = args.get(2).and_then(|depth| depth.parse::<u8>().ok()).unwrap_or(8);
+        let lines = args.get(3).and_then(|lines| lines.parse::<usize>().ok()).unwrap_or(3);
+        let fen = if args.len() > 4 { args[4..].join(" ") } else { BENCH_POSITIONS[0].to_string() };
+        run_analysis(&fen, depth, lines);
+        return;
+    }
+    if args.len() > 2 && args[1] == "engine" {                 // cli engine <path> [movetime ms] [fen]
+        let move_time = args.get(3).and_then(|time| time.parse::<u64>().ok()).unwrap_or(1000);
+        let fen = if args.len() > 4 { args[4..].join(" ") } else { BENCH_POSITIONS[0].to_string() };
+        run_external_engine(&args[2], move_time, &fen);
+        return;
+    }
+    if args.len() > 2 && args[1] == "book" {                   // cli book <file.bin> [fen]
+        let fen = if args.len() > 3 { args[3..].join(" ") } else { BENCH_POSITIONS[0].to_string() };
+        run_book(&args[2], &fen);
+        return;
+    }
+    if args.len() > 3 && args[1] == "makebook" {               // cli makebook <out.bin> <pgn files> [--min-games n] [--max-ply n] [--only white|black]
+        run_make_book(&args[2], &args[3..]);
+        return;
+    }
+    if args.len() > 3 && args[1] == "tb" && args[2] == "generate" {    // cli tb generate <signature> [directory]
+        run_tb_generate(&args[3], args.get(4).map_or("tables", String::as_str));
+        return;
+    }
+    if args.len() > 3 && args[1] == "tb" && args[2] == "syzygy" {      // cli tb syzygy <signature> [directory]
+        run_tb_syzygy(&args[3], args.get(4).map_or("tables", String::as_str));
+        return;
+    }
+    if args.len() > 3 && args[1] == "tb" && args[2] == "probe" {       // cli tb probe <directory> [fen]
+        let fen = if args.len() > 4 { args[4..].join(" ") } else { BENCH_POSITIONS[0].to_string() };
+        run_tb_probe(&args[3], &fen);
+        return;
+    }
+    if args.len() > 2 && args[1] == "epd" {                    // cli epd <epd files> [--depth n] [--time ms] [--json file]
+        run_epd_suite(&args[2..]);
+        return;
+    }
+    if args.len() > 1 && args[1] == "eval" {                   // cli eval [fen]
+        let fen = if args.len() > 2 { args[2..].join(" ") } else { BENCH_POSITIONS[0].to_string() };
+        run_eval(&fen);
+        return;
+    }
+    if args.len() > 3 && matches!(args[1].as_str(), "mate" | "selfmate" | "helpmate") {   // cli mate|selfmate|helpmate <moves> <fen>
+        match args[2].parse::<u8>() {
+            Ok(moves) if args[1] == "helpmate" => run_helpmate(moves, &args[3..].join(" ")),
+            Ok(moves) => run_mate(moves, &args[3..].join(" "), args[1] == "selfmate"),
+            Err(_) => println!("usage: cli {} <moves> <fen>", args[1]),
+        }
+        return;
+    }
+    if args.len() > 1 && args[1] == "tactics" {                // cli tactics [fen]
+        let fen = if args.len() > 2 { args[2..].join(" ") } else { BENCH_POSITIONS[0].to_string() };
+        match init_game_from_fen(&fen) {
+            Ok(game) => print_tactics(&game),
+            Err(error) => println!("invalid fen: {}", error),
+        }
+        return;
+    }
+    if args.len() > 1 && args[1] == "play" {                   // cli play [--level 0-20 | --elo n] [--black] [--time ms] [fen]
+        run_play(&args[2..]);
+        return;
+    }
+    if args.len() > 1 && args[1] == "smp" {                    // cli smp [depth] [max threads]
+        let depth = args.get(2).and_then(|depth| depth.parse::<u8>().ok()).unwrap_or(6);
+        let max_threads = args.get(3).and_then(|threads| threads.parse::<usize>().ok()).unwrap_or(4);
+        run_smp_bench(depth, max_threads);
+        return;
+    }
+
+    let mut symbol_to_piece = unicode_pieces();
     let mut game = init_game();                             // todo - half/fullmove, add moves played to list
                                                             // check mate, draw, generate all possible moves, promote
 
@@ -69,6 +147,25 @@ fn main() {
     }
 }
 
+fn unicode_pieces() -> HashMap<&'static str, &'static str> {
+    let mut symbol_to_piece = HashMap::new();
+    symbol_to_piece.insert("bk", "\u{2654}");
+    symbol_to_piece.insert("bq", "\u{2655}");
+    symbol_to_piece.insert("br", "\u{2656}");
+    symbol_to_piece.insert("bb", "\u{2657}");
+    symbol_to_piece.insert("bn", "\u{2658}");
+    symbol_to_piece.insert("bp", "\u{2659}");
+
+    symbol_to_piece.insert("wk", "\u{265A}");
+    symbol_to_piece.insert("wq", "\u{265B}");
+    symbol_to_piece.insert("wr", "\u{265C}");
+    symbol_to_piece.insert("wb", "\u{265D}");
+    symbol_to_piece.insert("wn", "\u{265E}");
+    symbol_to_piece.insert("wp", "\u{265F}");
+    symbol_to_piece
+}
+
+#[allow(clippy::print_literal, clippy::explicit_counter_loop)]
 pub fn draw_chess_board(board: [u8; 64], symbol_to_piece: &mut HashMap<&str, &str>) {
     let mut rank  = 1;
     let mut file = 8;
@@ -112,3 +209,573 @@ pub fn draw_chess_board(board: [u8; 64], symbol_to_piece: &mut HashMap<&str, &st
         rank += 1;
     }
 }
+
+// Searches every bench position to a fixed depth with and without move ordering
+pub fn run_bench(depth: u8) {
+    let limits = SearchLimits { depth: Some(depth), ..Default::default() };
+    let mut total_unordered = 0;
+    let mut total_ordered = 0;
+    let mut total_selective = 0;
+    let start = Instant::now();
+
+    println!("{:>8} {:>14} {:>14} {:>14}  best move", "position", "unordered", "ordered", "selective");
+    for (index, fen) in BENCH_POSITIONS.iter().enumerate() {
+        let game = init_game_from_fen(fen).unwrap();
+
+        let mut unordered = Searcher::new(SearchOptions { move_ordering: false, ..SearchOptions::without_selectivity() });
+        let unordered_result = unordered.search(&game, limits);
+
+        let mut ordered = Searcher::new(SearchOptions::without_selectivity());
+        let ordered_result = ordered.search(&game, limits);
+
+        let mut selective = Searcher::new(SearchOptions::default());
+        let selective_result = selective.search(&game, limits);
+
+        total_unordered += unordered_result.nodes;
+        total_ordered += ordered_result.nodes;
+        total_selective += selective_result.nodes;
+        let best_move = selective_result.best_move.map_or("none".to_string(), |played| played.to_uci());
+        println!("{:>8} {:>14} {:>14} {:>14}  {}", index + 1, unordered_result.nodes, ordered_result.nodes, selective_result.nodes, best_move);
+    }
+
+    let elapsed = start.elapsed();
+    let nodes_per_second = ((total_unordered + total_ordered + total_selective) as f64 / elapsed.as_secs_f64().max(0.001)) as u64;
+    println!("{:>8} {:>14} {:>14} {:>14}", "total", total_unordered, total_ordered, total_selective);
+    println!("depth {}, {} nodes/s, {} ms", depth, nodes_per_second, elapsed.as_millis());
+}
+
+/// Ranked lines for a position, each with its score in UCI form and the moves in UCI and SAN.
+pub fn run_analysis(fen: &str, depth: u8, lines: usize) {
+    let game = match init_game_from_fen(fen) {
+        Ok(game) => game,
+        Err(error) => {
+            println!("invalid fen: {}", error);
+            return;
+        }
+    };
+    let start = Instant::now();
+    let mut searcher = Searcher::new(SearchOptions { multi_pv: lines, ..Default::default() });
+    let result = searcher.search(&game, SearchLimits { depth: Some(depth), ..Default::default() });
+
+    println!("depth {}, {} nodes, {} ms", result.depth, result.nodes, start.elapsed().as_millis());
+    for (index, line) in result.lines.iter().enumerate() {
+        println!("{:>2}. {:<10} depth {:>2} nodes {:>10}  {}", index + 1, line.score().to_string(), line.depth, line.nodes, pv_to_movetext(&game, &line.pv));
+        println!("    {:<10} {}", "", line.uci().join(" "));
+    }
+}
+
+/// Asks an external UCI engine for its move in a position and prints what it reports.
+pub fn run_external_engine(path: &str, move_time: u64, fen: &str) {
+    let game = match init_game_from_fen(fen) {
+        Ok(game) => game,
+        Err(error) => {
+            println!("invalid fen: {}", error);
+            return;
+        }
+    };
+    let timeout = Duration::from_millis(move_time) + Duration::from_secs(5);
+    let mut engine = match UciClient::spawn(path, &[], timeout) {
+        Ok(engine) => engine,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    println!("{} by {}", engine.name, engine.author);
+
+    let limits = SearchLimits { move_time: Some(Duration::from_millis(move_time)), ..Default::default() };
+    let outcome = engine.set_position(&game).and_then(|_| engine.go(&limits, timeout));
+    match outcome {
+        Ok(outcome) => {
+            for info in outcome.infos.iter().filter(|info| info.depth.is_some()) {
+                let score = info.score.map_or("-".to_string(), |score| score.to_string());
+                println!("depth {:>3} {:<10} nodes {:>10}  {}", info.depth.unwrap_or(0), score, info.nodes.unwrap_or(0), info.pv.join(" "));
+            }
+            match move_from_uci(&game, &outcome.best_move.best_move) {
+                Some(played) => println!("bestmove {} ({})", played.to_uci(), move_to_san(&game, played)),
+                None => println!("illegal bestmove {}", outcome.best_move.best_move),
+            }
+        }
+        Err(error) => println!("{}", error),
+    }
+    engine.quit();
+}
+
+/// Lists the moves a Polyglot book has for a position, with their weights and share of the total.
+pub fn run_book(path: &str, fen: &str) {
+    let game = match init_game_from_fen(fen) {
+        Ok(game) => game,
+        Err(error) => {
+            println!("invalid fen: {}", error);
+            return;
+        }
+    };
+    let book = match OpeningBook::open(path) {
+        Ok(book) => book,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    let moves = book.moves(&game);
+    println!("{} entries, key {:016x}", book.len(), polyglot_key(&game));
+    if moves.is_empty() {
+        println!("position not in book");
+        return;
+    }
+    let total: u64 = moves.iter().map(|book_move| book_move.weight as u64).sum();
+    println!("{:>6} {:>7} {:>7} {:>7}", "move", "san", "weight", "share");
+    for book_move in moves.iter() {
+        let share = 100.0 * book_move.weight as f64 / total.max(1) as f64;
+        println!("{:>6} {:>7} {:>7} {:>6.1}%", book_move.played.to_uci(), move_to_san(&game, book_move.played), book_move.weight, share);
+    }
+}
+
+/// Builds a Polyglot book from PGN files, options may appear anywhere among the files.
+pub fn run_make_book(output: &str, arguments: &[String]) {
+    let mut builder = BookBuilder::default();
+    let mut files = Vec::new();
+    let mut index = 0;
+    while index < arguments.len() {
+        let value = arguments.get(index + 1);
+        match arguments[index].as_str() {
+            "--min-games" => builder.min_games = value.and_then(|games| games.parse().ok()).unwrap_or(builder.min_games),
+            "--max-ply" => builder.max_ply = value.and_then(|plies| plies.parse().ok()).unwrap_or(builder.max_ply),
+            "--only" => {
+                builder.white = value.map(String::as_str) != Some("black");
+                builder.black = value.map(String::as_str) != Some("white");
+            }
+            file => {
+                files.push(file.to_string());
+                index += 1;
+                continue;
+            }
+        }
+        index += 2;
+    }
+
+    let (mut games, mut skipped) = (0, 0);
+    for file in files.iter() {
+        let text = match std::fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(error) => {
+                println!("cannot read {}: {}", file, error);
+                continue;
+            }
+        };
+        for game in parse_pgn(&text) {
+            match game {
+                Ok(game) => {
+                    builder.add_game(&game.start, &game.moves, game.outcome);
+                    games += 1;
+                }
+                Err(error) => {
+                    println!("{}: {}", file, error);
+                    skipped += 1;
+                }
+            }
+        }
+    }
+
+    let entries = builder.entries().len();
+    match builder.write(output) {
+        Ok(()) => println!("{} games read, {} skipped, {} moves seen, {} entries written to {}", games, skipped, builder.len(), entries, output),
+        Err(error) => println!("{}", error),
+    }
+}
+
+/// Builds the endgame table for a material signature and the ones it depends on, and writes them to a directory.
+pub fn run_tb_generate(signature: &str, directory: &str) {
+    let start = Instant::now();
+    let mut tables = EndgameTables::new();
+    if let Err(error) = tables.generate(signature) {
+        println!("{}", error);
+        return;
+    }
+    if let Err(error) = std::fs::create_dir_all(directory) {
+        println!("cannot create {}: {}", directory, error);
+        return;
+    }
+    println!("{:>6} {:>8} {:>8} {:>8}  longest win", "table", "wins", "draws", "losses");
+    for name in tables.signatures() {
+        let table = tables.get(name).unwrap();
+        let (wins, draws, losses) = table.counts();
+        let longest = table.longest_win().map_or("-".to_string(), |(fen, dtm)| format!("{}  {}", dtm, fen));
+        println!("{:>6} {:>8} {:>8} {:>8}  {}", name, wins, draws, losses, longest);
+        if let Err(error) = table.save(directory) {
+            println!("{}", error);
+        }
+    }
+    println!("{} ms", start.elapsed().as_millis());
+}
+
+/// Builds the endgame table of a three piece ending and writes it as Syzygy WDL and DTZ files.
+pub fn run_tb_syzygy(signature: &str, directory: &str) {
+    let mut tables = EndgameTables::new();
+    if let Err(error) = tables.generate(signature) {
+        println!("{}", error);
+        return;
+    }
+    if let Err(error) = std::fs::create_dir_all(directory) {
+        println!("cannot create {}: {}", directory, error);
+        return;
+    }
+    match tables.save_syzygy(signature, directory) {
+        Ok(()) => println!("{}.rtbw and {}.rtbz written to {}", signature, signature, directory),
+        Err(error) => println!("{}", error),
+    }
+}
+
+/// Distance to mate of a position and of every move from it, from the tables in a directory.
+pub fn run_tb_probe(directory: &str, fen: &str) {
+    let game = match init_game_from_fen(fen) {
+        Ok(game) => game,
+        Err(error) => {
+            println!("invalid fen: {}", error);
+            return;
+        }
+    };
+    let tables = match EndgameTables::open(directory) {
+        Ok(tables) => tables,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    match tables.probe(&game) {
+        Some(dtm) => println!("{}: {}", material_name(&game), dtm),
+        None => {
+            println!("no table for {} in {}", material_name(&game), directory);
+            return;
+        }
+    }
+    for played in generate_legal_moves(&game) {
+        let mut child = game.clone();
+        make_move(&mut child, played);
+        let result = tables.probe(&child).map_or("unknown".to_string(), |dtm| format!("opponent {}", dtm));
+        println!("{:>6} {:>7}  {}", played.to_uci(), move_to_san(&game, played), result);
+    }
+}
+
+/// Searches every position of the EPD files and counts the ones whose bm, am and dm operations are met.
+pub fn run_epd_suite(arguments: &[String]) {
+    let mut limits = SearchLimits::default();
+    let mut json_path = None;
+    let mut files = Vec::new();
+    let mut index = 0;
+    while index < arguments.len() {
+        let value = arguments.get(index + 1);
+        match arguments[index].as_str() {
+            "--depth" => limits.depth = value.and_then(|depth| depth.parse().ok()),
+            "--time" => limits.move_time = value.and_then(|time| time.parse().ok()).map(Duration::from_millis),
+            "--json" => json_path = value.cloned(),
+            file => {
+                files.push(file.to_string());
+                index += 1;
+                continue;
+            }
+        }
+        index += 2;
+    }
+    if limits.depth.is_none() && limits.move_time.is_none() {
+        limits.move_time = Some(Duration::from_millis(1000));
+    }
+
+    let mut searcher = Searcher::new(SearchOptions::default());
+    let mut results = Vec::new();
+    let mut invalid = Vec::new();                               // records that cannot be tested, kept out of the percentage
+    let (mut total, mut solved) = (0, 0);
+    for file in files.iter() {
+        let text = match std::fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(error) => {
+                println!("cannot read {}: {}", file, error);
+                continue;
+            }
+        };
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#')) {
+            let location = format!("{}:{}", file, number + 1);
+            let parsed = parse_epd(line).and_then(|record| {
+                let (best_moves, avoid_moves) = (record.best_moves()?, record.avoid_moves()?);
+                if best_moves.is_empty() && avoid_moves.is_empty() && record.mate_in().is_none() {
+                    return Err("no bm, am or dm to test".to_string());
+                }
+                Ok((record, best_moves, avoid_moves))
+            });
+            let (record, best_moves, avoid_moves) = match parsed {
+                Ok(parsed) => parsed,
+                Err(error) => {
+                    println!("{}: {}", location, error);
+                    invalid.push(format!("    {{\"record\": {}, \"error\": {}}}", json_string(&location), json_string(&error)));
+                    continue;
+                }
+            };
+            let mate_in = record.mate_in();
+
+            searcher.clear();
+            let start = Instant::now();
+            let result = searcher.search(&record.game, SearchLimits { mate: mate_in.map(|moves| moves.min(u8::MAX as u32) as u8), ..limits });
+            let elapsed = start.elapsed().as_millis();
+            let score = Score::from_search(result.score);
+            let found = result.best_move;
+            let is_solved = found.is_some_and(|found| {
+                (best_moves.is_empty() || best_moves.contains(&found)) && !avoid_moves.contains(&found)
+            }) && mate_in.is_none_or(|moves| matches!(score, Score::Mate(mate) if mate > 0 && mate as u32 <= moves));
+
+            total += 1;
+            if is_solved {
+                solved += 1;
+            }
+            let id = record.id().map_or(location, str::to_string);
+            let found_san = found.map_or("none".to_string(), |found| move_to_san(&record.game, found));
+            println!("{:<20} {:<9} {:<8} {:<10} depth {:>2} nodes {:>10} {:>6} ms", id, if is_solved { "solved" } else { "unsolved" }, found_san, score.to_string(), result.depth, result.nodes, elapsed);
+
+            let san_list = |moves: &[Move]| moves.iter().map(|listed| json_string(&move_to_san(&record.game, *listed))).collect::<Vec<_>>().join(", ");
+            results.push(format!(
+                "    {{\"id\": {}, \"fen\": {}, \"best_moves\": [{}], \"avoid_moves\": [{}], \"mate_in\": {}, \"found\": {}, \"score\": {}, \"depth\": {}, \"nodes\": {}, \"time_ms\": {}, \"solved\": {}}}",
+                json_string(&id), json_string(&game_to_fen(&record.game)), san_list(&best_moves), san_list(&avoid_moves),
+                mate_in.map_or("null".to_string(), |moves| moves.to_string()),
+                found.map_or("null".to_string(), |found| json_string(&found.to_uci())),
+                json_string(&score.to_string()), result.depth, result.nodes, elapsed, is_solved,
+            ));
+        }
+    }
+
+    let percentage = if total > 0 { 100.0 * solved as f64 / total as f64 } else { 0.0 };
+    println!("solved {} of {} ({:.1}%), {} invalid", solved, total, percentage, invalid.len());
+    if let Some(path) = json_path {
+        let limit = match (limits.depth, limits.move_time) {
+            (Some(depth), _) => format!("{{\"depth\": {}}}", depth),
+            (None, move_time) => format!("{{\"move_time_ms\": {}}}", move_time.unwrap_or_default().as_millis()),
+        };
+        let suites: Vec<String> = files.iter().map(|file| json_string(file)).collect();
+        let summary = format!(
+            "{{\n  \"suites\": [{}],\n  \"limit\": {},\n  \"positions\": {},\n  \"solved\": {},\n  \"percentage\": {:.2},\n  \"invalid\": {},\n  \"results\": [\n{}\n  ],\n  \"invalid_records\": [\n{}\n  ]\n}}\n",
+            suites.join(", "), limit, total, solved, percentage, invalid.len(), results.join(",\n"), invalid.join(",\n"),
+        );
+        if let Err(error) = std::fs::write(&path, summary) {
+            println!("cannot write {}: {}", path, error);
+        }
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for symbol in text.chars() {
+        match symbol {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            symbol if (symbol as u32) < 0x20 => quoted += &format!("\\u{:04x}", symbol as u32),
+            symbol => quoted.push(symbol),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+pub fn run_eval(fen: &str) {
+    let game = match init_game_from_fen(fen) {
+        Ok(game) => game,
+        Err(error) => {
+            println!("invalid fen: {}", error);
+            return;
+        }
+    };
+    let trace = evaluate_trace(&game);
+    println!("{:<14} {:>15} {:>15} {:>15} {:>8}", "term", "white mg/eg", "black mg/eg", "total mg/eg", "tapered");
+    for (name, term) in trace.terms().iter() {
+        println!(
+            "{:<14} {:>7} {:>7} {:>7} {:>7} {:>7} {:>7} {:>8}",
+            name,
+            term.middlegame[0], term.endgame[0],
+            term.middlegame[1], term.endgame[1],
+            term.middlegame[0] - term.middlegame[1], term.endgame[0] - term.endgame[1],
+            term.tapered(trace.phase),
+        );
+    }
+    println!("phase {}/24", trace.phase);
+    println!("score {} for white, {} for the side to move", trace.score, evaluate(&game));
+}
+
+/// Time to reach a fixed depth on the bench positions for 1, 2, 4 ... threads, with the speedup over one thread.
+pub fn run_smp_bench(depth: u8, max_threads: usize) {
+    let limits = SearchLimits { depth: Some(depth), ..Default::default() };
+    let games: Vec<GAME> = BENCH_POSITIONS.iter().map(|fen| init_game_from_fen(fen).unwrap()).collect();
+    let mut single_thread_time = None;
+
+    println!("{:>8} {:>10} {:>14} {:>10} {:>8}", "threads", "ms", "nodes", "nodes/s", "speedup");
+    let mut threads = 1;
+    while threads <= max_threads.max(1) {
+        let start = Instant::now();
+        let mut nodes = 0;
+        for game in games.iter() {
+            let mut searcher = Searcher::new(SearchOptions { threads, ..Default::default() });
+            nodes += searcher.search(game, limits).nodes;
+        }
+        let elapsed = start.elapsed().as_secs_f64().max(0.001);
+        let baseline = *single_thread_time.get_or_insert(elapsed);
+        println!("{:>8} {:>10} {:>14} {:>10} {:>8.2}", threads, (elapsed * 1000.0) as u64, nodes, (nodes as f64 / elapsed) as u64, baseline / elapsed);
+        threads *= 2;
+    }
+}
+
+// Plays a game against the engine at a chosen strength. Moves are typed in SAN or UCI
+// notation, `level n` and `elo n` change the difficulty during the game.
+pub fn run_play(arguments: &[String]) {
+    let mut skill = Skill::new(10);
+    let mut human_is_white = true;
+    let mut move_time = Duration::from_millis(1000);
+    let mut fen_words = Vec::new();
+    let mut index = 0;
+    while index < arguments.len() {
+        let value = arguments.get(index + 1);
+        match arguments[index].as_str() {
+            "--level" => skill = Skill::new(value.and_then(|level| level.parse().ok()).unwrap_or(skill.level())),
+            "--elo" => skill = value.and_then(|elo| elo.parse().ok()).map_or(skill, Skill::from_elo),
+            "--time" => move_time = value.and_then(|time| time.parse().ok()).map_or(move_time, Duration::from_millis),
+            "--black" => {
+                human_is_white = false;
+                index += 1;
+                continue;
+            }
+            word => {
+                fen_words.push(word.to_string());
+                index += 1;
+                continue;
+            }
+        }
+        index += 2;
+    }
+    let fen = if fen_words.is_empty() { BENCH_POSITIONS[0].to_string() } else { fen_words.join(" ") };
+    let mut game = match init_game_from_fen(&fen) {
+        Ok(game) => game,
+        Err(error) => {
+            println!("invalid fen: {}", error);
+            return;
+        }
+    };
+
+    let mut searcher = Searcher::new(SearchOptions { skill, ..Default::default() });
+    let mut symbol_to_piece = unicode_pieces();
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    println!("{}", difficulty(skill));
+    loop {
+        draw_chess_board(game.get_board(), &mut symbol_to_piece);
+        if let Some((outcome, reason)) = game_outcome(&game) {
+            println!("{} ({})", outcome.to_pgn(), reason);
+            return;
+        }
+        if game.is_whites_turn() != human_is_white {
+            let result = searcher.search(&game, SearchLimits { move_time: Some(move_time), ..Default::default() });
+            match result.best_move {
+                Some(played) => {
+                    println!("engine plays {}", move_to_san(&game, played));
+                    make_move(&mut game, played);
+                }
+                None => return,
+            }
+            continue;
+        }
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line.trim().to_string(),
+            _ => return,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["quit"] | ["exit"] => return,
+            ["level", level] => match level.parse::<u8>() {
+                Ok(level) => skill = Skill::new(level),
+                Err(_) => println!("levels go from 0 to {}", Skill::MAX_LEVEL),
+            },
+            ["elo", elo] => match elo.parse::<u32>() {
+                Ok(elo) => skill = Skill::from_elo(elo),
+                Err(_) => println!("not a number: {}", elo),
+            },
+            ["hint"] => print_tactics(&game),
+            _ => match move_from_san(&game, &line).or_else(|| move_from_uci(&game, &line.replace(' ', ""))) {
+                Some(played) => {
+                    make_move(&mut game, played);
+                }
+                None => println!("not a valid move"),
+            },
+        }
+        if skill.level() != searcher.options.skill.level() {
+            searcher.options.skill = skill;
+            println!("{}", difficulty(skill));
+        }
+    }
+}
+
+// Hanging pieces, pins, skewers and the discovered attacks, forks and checks of the side to move
+fn print_tactics(game: &GAME) {
+    let tactics = find_tactics(game);
+    if tactics.is_empty() {
+        println!("nothing tactical on the board");
+    }
+    for line in tactics.to_lines(game) {
+        println!("{}", line);
+    }
+}
+
+fn difficulty(skill: Skill) -> String {
+    match skill.elo() {
+        Some(elo) => format!("difficulty: level {} of {}, about {} Elo", skill.level(), Skill::MAX_LEVEL, elo),
+        None => format!("difficulty: level {}, full strength", Skill::MAX_LEVEL),
+    }
+}
+
+// Checks a "mate in n" or "selfmate in n" problem: the keys with their solution trees, cooks,
+// duals and short mates.
+pub fn run_mate(moves: u8, fen: &str, selfmate: bool) {
+    let game = match init_game_from_fen(fen) {
+        Ok(game) => game,
+        Err(error) => {
+            println!("invalid fen: {}", error);
+            return;
+        }
+    };
+    let start = Instant::now();
+    let solution = if selfmate { solve_selfmate(&game, moves) } else { solve_mate(&game, moves) };
+    println!("{} nodes in {:.2}s", solution.nodes, start.elapsed().as_secs_f64());
+    match solution.shortest {
+        None => {
+            println!("no mate in {}", moves);
+            return;
+        }
+        Some(shortest) if shortest < moves => println!("short mate: already mates in {}", shortest),
+        Some(_) => {}
+    }
+    if solution.keys.len() > 1 {
+        println!("cooked: {} keys", solution.keys.len());
+    } else if solution.is_sound() {
+        println!("sound, one key");
+    }
+    if solution.dual_count() > 0 {
+        println!("{} duals", solution.dual_count());
+    }
+    println!("{}", solution.to_text(&game));
+}
+
+// Lists every solution of a helpmate in n, with the duals of the mating side
+pub fn run_helpmate(moves: u8, fen: &str) {
+    let game = match init_game_from_fen(fen) {
+        Ok(game) => game,
+        Err(error) => {
+            println!("invalid fen: {}", error);
+            return;
+        }
+    };
+    let start = Instant::now();
+    let solution = solve_helpmate(&game, moves);
+    println!("{} nodes in {:.2}s", solution.nodes, start.elapsed().as_secs_f64());
+    let lines = solution.lines();
+    if lines.is_empty() {
+        println!("no helpmate in {}", moves);
+        return;
+    }
+    println!("solutions: {}, duals: {}", lines.len(), solution.dual_count());
+    println!("{}", solution.to_text(&game));
+}
@@ -0,0 +1,5 @@
+mod record;
+mod selfplay;
+
+pub use record::{TrainingRecord, RECORD_SIZE};
+pub use selfplay::{Rng, GeneratorSettings, play_selfplay_game};
@@ -0,0 +1,189 @@
+use chess_logic::*;
+use datagen::*;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::time::Instant;
+
+// Self-play training data: the engine plays itself from random openings and every kept
+// position is written with its search score and the result of its game.
+//
+// datagen [--games n] [--depth n] [--nodes n] [--random-plies n] [--opening-limit cp]
+//         [--max-plies n] [--keep-checks] [--keep-captures] [--threads n] [--seed n]
+//         [--hash mb] [--text file] [--binary file]
+//
+// Game n only depends on the seed and n, and the games are written in order, so the same
+// settings give the same files whatever the number of threads.
+
+struct Options {
+    games: u64,
+    settings: GeneratorSettings,
+    threads: usize,
+    seed: u64,
+    hash_size_mb: usize,
+    text: Option<String>,
+    binary: Option<String>,
+}
+
+const USAGE: &str = "usage: datagen [--games n] [--depth n] [--nodes n] [--random-plies n] [--opening-limit cp] [--max-plies n] [--keep-checks] [--keep-captures] [--threads n] [--seed n] [--hash mb] [--text file] [--binary file]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let options = match parse_options(&args[1..]) {
+        Ok(options) => options,
+        Err(error) => {
+            println!("{}", error);
+            println!("{}", USAGE);
+            return;
+        }
+    };
+    if let Err(error) = generate(&options) {
+        println!("{}", error);
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        games: 100,
+        settings: GeneratorSettings::default(),
+        threads: 1,
+        seed: 1,
+        hash_size_mb: 16,
+        text: None,
+        binary: None,
+    };
+    let mut depth_given = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--keep-checks" => {
+                options.settings.skip_check = false;
+                continue;
+            }
+            "--keep-captures" => {
+                options.settings.skip_captures = false;
+                continue;
+            }
+            _ => {}
+        }
+        let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+        let invalid = |_| format!("invalid value for {}: {}", arg, value);
+        match arg.as_str() {
+            "--games" => options.games = value.parse().map_err(invalid)?,
+            "--depth" => {
+                options.settings.depth = Some(value.parse().map_err(invalid)?);
+                depth_given = true;
+            }
+            "--nodes" => options.settings.nodes = Some(value.parse().map_err(invalid)?),
+            "--random-plies" => options.settings.random_plies = value.parse().map_err(invalid)?,
+            "--opening-limit" => options.settings.opening_score_limit = value.parse().map_err(invalid)?,
+            "--max-plies" => options.settings.max_plies = value.parse().map_err(invalid)?,
+            "--threads" => options.threads = value.parse::<usize>().map_err(invalid)?.max(1),
+            "--seed" => options.seed = value.parse().map_err(invalid)?,
+            "--hash" => options.hash_size_mb = value.parse().map_err(invalid)?,
+            "--text" => options.text = Some(value.clone()),
+            "--binary" => options.binary = Some(value.clone()),
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+    // A node limit alone replaces the default depth
+    if options.settings.nodes.is_some() && !depth_given {
+        options.settings.depth = None;
+    }
+    if options.text.is_none() && options.binary.is_none() {
+        return Err("no output given, use --text and/or --binary".to_string());
+    }
+    Ok(options)
+}
+
+fn create(path: &Option<String>) -> Result<Option<BufWriter<File>>, String> {
+    match path {
+        Some(path) => File::create(path).map(|file| Some(BufWriter::new(file))).map_err(|error| format!("cannot write {}: {}", path, error)),
+        None => Ok(None),
+    }
+}
+
+fn generate(options: &Options) -> Result<(), String> {
+    let mut text = create(&options.text)?;
+    let mut binary = create(&options.binary)?;
+    println!("{} games, {} threads, seed {}", options.games, options.threads, options.seed);
+
+    let started = Instant::now();
+    let next_game = AtomicU64::new(0);
+    let (sender, finished) = mpsc::channel();
+    let write_error = std::thread::scope(|scope| {
+        for _ in 0..options.threads {
+            let (sender, next_game) = (sender.clone(), &next_game);
+            scope.spawn(move || {
+                let search_options = SearchOptions { hash_size_mb: options.hash_size_mb, threads: 1, ..Default::default() };
+                let mut searcher = Searcher::new(search_options);
+                loop {
+                    let game = next_game.fetch_add(1, Ordering::SeqCst);
+                    if game >= options.games {
+                        break;
+                    }
+                    let played = play_selfplay_game(&mut searcher, &options.settings, &mut Rng::for_game(options.seed, game));
+                    if sender.send((game, played)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Games can finish out of order, they wait here until the ones before them are written
+        let mut waiting = BTreeMap::new();
+        let mut written = 0;
+        let (mut positions, mut results) = (0, [0u64; 3]);
+        let report_every = (options.games / 20).max(1);
+        for (game, played) in finished {
+            waiting.insert(game, played);
+            while let Some((records, outcome)) = waiting.remove(&written) {
+                for record in records.iter() {
+                    if let Err(error) = write_record(record, &mut text, &mut binary) {
+                        next_game.store(options.games, Ordering::SeqCst);
+                        return Some(error);
+                    }
+                }
+                positions += records.len();
+                match outcome {
+                    Outcome::WhiteWins => results[0] += 1,
+                    Outcome::Draw => results[1] += 1,
+                    _ => results[2] += 1,
+                }
+                written += 1;
+                if written % report_every == 0 || written == options.games {
+                    let seconds = started.elapsed().as_secs_f64();
+                    println!(
+                        "games {:>6}/{}  +{} ={} -{}  positions {:>8}  {:.0} positions/s",
+                        written, options.games, results[0], results[1], results[2], positions, positions as f64 / seconds.max(0.001),
+                    );
+                }
+            }
+        }
+        None
+    });
+    if let Some(error) = write_error {
+        return Err(error);
+    }
+    for (writer, path) in [(text.as_mut(), &options.text), (binary.as_mut(), &options.binary)] {
+        if let (Some(writer), Some(path)) = (writer, path) {
+            writer.flush().map_err(|error| format!("cannot write {}: {}", path, error))?;
+            println!("written to {}", path);
+        }
+    }
+    Ok(())
+}
+
+fn write_record(record: &TrainingRecord, text: &mut Option<BufWriter<File>>, binary: &mut Option<BufWriter<File>>) -> Result<(), String> {
+    if let Some(text) = text.as_mut() {
+        writeln!(text, "{}", record.to_text()).map_err(|error| format!("cannot write the text records: {}", error))?;
+    }
+    if let Some(binary) = binary.as_mut() {
+        binary.write_all(&record.to_bytes()?).map_err(|error| format!("cannot write the binary records: {}", error))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use chess_logic::*;
+
+/// Bytes of one record in the binary format.
+pub const RECORD_SIZE: usize = 32;
+
+const PIECE_SYMBOLS: &str = " PNBRQK  pnbrqk";                 // index is the 4 bit piece code
+
+/// A position from a self-play game with the search score and the result of the game, both
+/// from white's side.
+///
+/// The text form is one line, `<fen> | <score> | <result>`, with the result written as in
+/// PGN so the tuner reads it as it is. The binary form is 32 bytes, little endian:
+///
+/// | bytes  | contents                                                            |
+/// |--------|---------------------------------------------------------------------|
+/// | 0..8   | occupied squares, bit 0 is a1 and bit 63 is h8                      |
+/// | 8..24  | a 4 bit code per occupied square in bit order, low nibble first:    |
+/// |        | 1 to 6 white pawn, knight, bishop, rook, queen, king, 9 to 14 black |
+/// | 24     | bit 0 black to move, bits 1 to 4 castling rights KQkq               |
+/// | 25     | en passant square with a1 = 0, 255 when there is none               |
+/// | 26     | halfmove clock                                                      |
+/// | 27..29 | fullmove number                                                     |
+/// | 29..31 | score in centipawns                                                 |
+/// | 31     | 0 black won, 1 draw, 2 white won                                    |
+/// # Both formats
+///```
+/// use chess_logic::Outcome;
+/// use datagen::TrainingRecord;
+/// let record = TrainingRecord {
+///     fen: "r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 0 23".to_string(),
+///     score: -57,
+///     result: Outcome::Draw,
+/// };
+/// assert_eq!(record.to_text(), "r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 0 23 | -57 | 1/2-1/2");
+/// assert_eq!(TrainingRecord::from_text(&record.to_text()).unwrap(), record);
+/// let bytes = record.to_bytes().unwrap();
+/// assert_eq!(bytes.len(), datagen::RECORD_SIZE);
+/// assert_eq!(TrainingRecord::from_bytes(&bytes).unwrap(), record);
+/// assert!(TrainingRecord::from_text("8/8/8/8 w - - | 0 | 1-0").is_err());
+///```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrainingRecord {
+    pub fen: String,
+    pub score: i16,
+    pub result: Outcome,
+}
+
+impl TrainingRecord {
+    pub fn to_text(&self) -> String {
+        format!("{} | {} | {}", self.fen, self.score, self.result.to_pgn())
+    }
+
+    pub fn from_text(line: &str) -> Result<TrainingRecord, String> {
+        let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+        if fields.len() != 3 {
+            return Err(format!("expected '<fen> | <score> | <result>', found '{}'", line));
+        }
+        init_game_from_fen(fields[0])?;
+        let score = fields[1].parse::<i16>().map_err(|_| format!("invalid score {}", fields[1]))?;
+        let result = match Outcome::from_pgn(fields[2]) {
+            Some(Outcome::Unfinished) | None => return Err(format!("invalid result {}", fields[2])),
+            Some(result) => result,
+        };
+        Ok(TrainingRecord { fen: fields[0].to_string(), score, result })
+    }
+
+    pub fn to_bytes(&self) -> Result<[u8; RECORD_SIZE], String> {
+        let game = init_game_from_fen(&self.fen)?;
+        let fen = game_to_fen(&game);
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let mut bytes = [0u8; RECORD_SIZE];
+
+        let board = game.get_board();
+        let mut occupied = 0u64;
+        let mut codes = Vec::new();
+        for square in 0..64 {
+            let symbol = piece_symbol(board[square ^ 56]);       // the board starts at a8
+            if symbol != ' ' {
+                occupied |= 1 << square;
+                codes.push(PIECE_SYMBOLS.find(symbol).unwrap_or(0) as u8);
+            }
+        }
+        if codes.len() > 32 {
+            return Err(format!("more than 32 pieces in {}", self.fen));
+        }
+        bytes[..8].copy_from_slice(&occupied.to_le_bytes());
+        for (index, code) in codes.iter().enumerate() {
+            bytes[8 + index / 2] |= code << (4 * (index % 2));
+        }
+
+        let mut flags = if fields[1] == "b" { 1 } else { 0 };
+        for (bit, right) in "KQkq".chars().enumerate() {
+            if fields[2].contains(right) {
+                flags |= 2 << bit;
+            }
+        }
+        bytes[24] = flags;
+        bytes[25] = if fields[3] == "-" { 255 } else { (algebraic_notation_to_memory_location(fields[3]) ^ 56) as u8 };
+        bytes[26] = fields[4].parse::<u32>().unwrap_or(0).min(255) as u8;
+        bytes[27..29].copy_from_slice(&fields[5].parse::<u16>().unwrap_or(1).to_le_bytes());
+        bytes[29..31].copy_from_slice(&self.score.to_le_bytes());
+        bytes[31] = match self.result {
+            Outcome::BlackWins => 0,
+            Outcome::Draw => 1,
+            Outcome::WhiteWins => 2,
+            Outcome::Unfinished => return Err("a record needs the result of its game".to_string()),
+        };
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8; RECORD_SIZE]) -> Result<TrainingRecord, String> {
+        let mut occupied = u64::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if occupied.count_ones() > 32 {
+            return Err("more than 32 pieces in a record".to_string());
+        }
+        let mut squares = [' '; 64];
+        let mut index = 0;
+        while occupied != 0 {
+            let square = occupied.trailing_zeros() as usize;
+            occupied &= occupied - 1;
+            let code = (bytes[8 + index / 2] >> (4 * (index % 2))) & 15;
+            squares[square] = match PIECE_SYMBOLS.chars().nth(code as usize) {
+                Some(symbol) if symbol != ' ' => symbol,
+                _ => return Err(format!("invalid piece code {}", code)),
+            };
+            index += 1;
+        }
+
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty_squares = 0;
+            for file in 0..8 {
+                let symbol = squares[rank * 8 + file];
+                if symbol == ' ' {
+                    empty_squares += 1;
+                    continue;
+                }
+                if empty_squares > 0 {
+                    fen.push_str(&empty_squares.to_string());
+                    empty_squares = 0;
+                }
+                fen.push(symbol);
+            }
+            if empty_squares > 0 {
+                fen.push_str(&empty_squares.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+        fen.push_str(if bytes[24] & 1 != 0 { " b " } else { " w " });
+        let castling: String = "KQkq".chars().enumerate().filter(|(bit, _)| bytes[24] & (2 << bit) != 0).map(|(_, right)| right).collect();
+        fen.push_str(if castling.is_empty() { "-" } else { &castling });
+        fen.push(' ');
+        match bytes[25] {
+            255 => fen.push('-'),
+            square if square < 64 => fen.push_str(&memory_location_to_algebraic_notation(square as usize ^ 56)),
+            square => return Err(format!("invalid en passant square {}", square)),
+        }
+        fen.push_str(&format!(" {} {}", bytes[26], u16::from_le_bytes([bytes[27], bytes[28]])));
+        init_game_from_fen(&fen)?;
+
+        let result = match bytes[31] {
+            0 => Outcome::BlackWins,
+            1 => Outcome::Draw,
+            2 => Outcome::WhiteWins,
+            result => return Err(format!("invalid result {}", result)),
+        };
+        Ok(TrainingRecord { fen, score: i16::from_le_bytes([bytes[29], bytes[30]]), result })
+    }
+}
+
+type PieceTest = fn(u8) -> bool;
+
+// The FEN letter of a piece, a space for an empty square
+fn piece_symbol(piece: u8) -> char {
+    let checks: [(PieceTest, char); 12] = [
+        (is_white_pawn, 'P'), (is_white_knight, 'N'), (is_white_bishop, 'B'), (is_white_rook, 'R'), (is_white_queen, 'Q'), (is_white_king, 'K'),
+        (is_black_pawn, 'p'), (is_black_knight, 'n'), (is_black_bishop, 'b'), (is_black_rook, 'r'), (is_black_queen, 'q'), (is_black_king, 'k'),
+    ];
+    checks.iter().find(|(is_piece, _)| is_piece(piece)).map_or(' ', |(_, symbol)| *symbol)
+}
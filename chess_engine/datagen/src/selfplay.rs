@@ -0,0 +1,144 @@
+use chess_logic::*;
+
+use crate::record::TrainingRecord;
+
+/// A small seeded generator (splitmix64), so the same seed gives the same games.
+/// # Reproducible streams
+///```
+/// use datagen::Rng;
+/// let (mut first, mut second) = (Rng::for_game(7, 3), Rng::for_game(7, 3));
+/// assert_eq!(first.next_u64(), second.next_u64());
+/// assert_ne!(Rng::for_game(7, 3).next_u64(), Rng::for_game(7, 4).next_u64());
+/// assert!((0..100).all(|_| first.below(6) < 6));
+///```
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    /// The generator of one game, which only depends on the seed and the game number so
+    /// the games do not change with the number of threads.
+    pub fn for_game(seed: u64, game: u64) -> Rng {
+        Rng::new(mix(seed ^ mix(game.wrapping_add(1))))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix(self.state)
+    }
+
+    /// A number from 0 to `bound - 1`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (((self.next_u64() >> 32) * bound as u64) >> 32) as usize
+    }
+}
+
+fn mix(value: u64) -> u64 {
+    let mut value = value;
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+/// How the self-play games are played and which of their positions are kept.
+#[derive(Clone, Copy, Debug)]
+pub struct GeneratorSettings {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub random_plies: usize,                                    // random moves before the engine plays
+    pub opening_score_limit: i32,                               // openings searched outside this are drawn again
+    pub max_plies: usize,                                       // longer games are counted as draws
+    pub skip_check: bool,                                       // leave out positions with the side to move in check
+    pub skip_captures: bool,                                    // leave out positions where the best move captures
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> GeneratorSettings {
+        GeneratorSettings {
+            depth: Some(8),
+            nodes: None,
+            random_plies: 8,
+            opening_score_limit: 1000,
+            max_plies: 400,
+            skip_check: true,
+            skip_captures: true,
+        }
+    }
+}
+
+/// Plays one game of the engine against itself from a random opening and returns the kept
+/// positions with the result. Positions with a mate score are never kept.
+/// # A short game
+///```
+/// use datagen::*;
+/// let settings = GeneratorSettings { depth: Some(2), random_plies: 4, max_plies: 20, ..Default::default() };
+/// let mut searcher = chess_logic::Searcher::new(Default::default());
+/// let (records, outcome) = play_selfplay_game(&mut searcher, &settings, &mut Rng::for_game(1, 0));
+/// assert!(records.len() <= 20);
+/// assert!(records.iter().all(|record| record.result == outcome));
+/// let (again, _) = play_selfplay_game(&mut searcher, &settings, &mut Rng::for_game(1, 0));
+/// assert_eq!(records, again);
+///```
+pub fn play_selfplay_game(searcher: &mut Searcher, settings: &GeneratorSettings, rng: &mut Rng) -> (Vec<TrainingRecord>, Outcome) {
+    searcher.clear();
+    let limits = SearchLimits { depth: settings.depth, nodes: settings.nodes, ..Default::default() };
+    let mut game = random_opening(searcher, settings, limits, rng);
+
+    let mut positions = Vec::new();                             // fen and white's score
+    let mut outcome = Outcome::Draw;
+    for _ in 0..settings.max_plies {
+        if let Some((result, _)) = game_outcome(&game) {
+            outcome = result;
+            break;
+        }
+        let result = searcher.search(&game, limits);
+        let best_move = match result.best_move {
+            Some(best_move) => best_move,
+            None => break,
+        };
+        let quiet = !settings.skip_captures || !is_capture(&game, best_move);
+        let calm = !settings.skip_check || !game.is_check();
+        if let (Score::Centipawns(score), true, true) = (Score::from_search(result.score), quiet, calm) {
+            let score = if game.is_whites_turn() { score } else { -score };
+            positions.push((game_to_fen(&game), score.clamp(i16::MIN as i32, i16::MAX as i32) as i16));
+        }
+        make_move(&mut game, best_move);
+    }
+
+    let records = positions.into_iter().map(|(fen, score)| TrainingRecord { fen, score, result: outcome }).collect();
+    (records, outcome)
+}
+
+// Random moves from the start position, drawn again when they end the game or leave a
+// position the engine already judges as lost for one side
+fn random_opening(searcher: &mut Searcher, settings: &GeneratorSettings, limits: SearchLimits, rng: &mut Rng) -> GAME {
+    loop {
+        let mut game = init_game();
+        let mut playable = true;
+        for _ in 0..settings.random_plies {
+            let moves = generate_legal_moves(&game);
+            if moves.is_empty() {
+                playable = false;
+                break;
+            }
+            make_move(&mut game, moves[rng.below(moves.len())]);
+        }
+        if !playable || game_outcome(&game).is_some() {
+            continue;
+        }
+        if settings.random_plies == 0 || searcher.search(&game, limits).score.abs() <= settings.opening_score_limit {
+            return game;
+        }
+    }
+}
+
+fn is_capture(game: &GAME, played: Move) -> bool {
+    let board = game.get_board();
+    let en_passant = (is_white_pawn(board[played.from as usize]) || is_black_pawn(board[played.from as usize])) && played.from % 8 != played.to % 8;
+    board[played.to as usize] != 0 || en_passant
+}
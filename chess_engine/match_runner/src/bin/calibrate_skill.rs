@@ -0,0 +1,190 @@
+use match_runner::*;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+// calibrate_skill [--games 100] [--depths 7] [--anchor 1000] [play settings]
+//
+// Rates the skill levels against fixed depth opponents. The engine at full strength with its
+// depth capped at 1, 2, ... plays the next depth each time, which rates the depths one after
+// the other from depth 1, put at the anchor. Then every level plays the fixed depth opponent
+// rated nearest to the level below it, and the next depth up or down as well when it scores
+// under 10% or over 90%, keeping the closer match. Where a level measures below the one
+// under it, both take their average, and levels left level are put one Elo apart, so the
+// table goes up with the level as Skill::from_elo needs. The result is the LEVEL_ELO table
+// of chess_logic/src/skill.rs.
+
+const LEVELS: u8 = 20;                                          // Skill::MAX_LEVEL, full strength, is not rated
+
+struct Settings {
+    games: usize,
+    depths: u8,
+    anchor: f64,
+    play: PlaySettings,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let settings = match parse_settings(&args[1..]) {
+        Ok(settings) => settings,
+        Err(error) => {
+            println!("{}", error);
+            println!("usage: calibrate_skill [--games n] [--depths n] [--anchor elo] {}", PlaySettings::USAGE);
+            return;
+        }
+    };
+    calibrate(&Arc::new(settings));
+}
+
+fn parse_settings(args: &[String]) -> Result<Settings, String> {
+    let mut settings = Settings { games: 100, depths: 7, anchor: 1000.0, play: PlaySettings::default() };
+    for (flag, values) in split_flags(args) {
+        if settings.play.apply(flag, &values)? {
+            continue;
+        }
+        let value = values.first().copied().ok_or_else(|| format!("{} needs a value", flag))?;
+        match flag {
+            "--games" => settings.games = value.parse().ok().filter(|games| *games > 0).ok_or_else(|| "invalid --games".to_string())?,
+            "--depths" => settings.depths = value.parse().ok().filter(|depths| *depths > 1).ok_or_else(|| "invalid --depths".to_string())?,
+            "--anchor" => settings.anchor = value.parse().map_err(|_| "invalid --anchor".to_string())?,
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+    Ok(settings)
+}
+
+fn depth_opponent(depth: u8) -> EngineSpec {
+    EngineSpec::parse(&["cmd=internal", &format!("name=depth{}", depth), &format!("option.depth={}", depth)]).unwrap()
+}
+
+fn level_player(level: u8) -> EngineSpec {
+    EngineSpec::parse(&["cmd=internal", &format!("name=level{}", level), &format!("option.skill={}", level)]).unwrap()
+}
+
+fn calibrate(settings: &Arc<Settings>) {
+    println!("{} games a match at {}, depth 1 rated {}", settings.games, settings.play.time_control, settings.anchor);
+    let mut depth_elo = vec![settings.anchor];                  // index 0 is depth 1
+    for depth in 2..=settings.depths {
+        let score = play_match(settings, &depth_opponent(depth), &depth_opponent(depth - 1));
+        depth_elo.push(depth_elo[depth_elo.len() - 1] + elo_difference(&score));
+        report(&format!("depth{} - depth{}", depth, depth - 1), &score, depth_elo[depth_elo.len() - 1]);
+    }
+
+    let mut level_elo = Vec::new();
+    let mut previous = settings.anchor;
+    for level in 0..LEVELS {
+        let nearest = (0..depth_elo.len()).min_by(|a, b| (depth_elo[*a] - previous).abs().total_cmp(&(depth_elo[*b] - previous).abs())).unwrap_or(0);
+        let play_depth = |index: usize| {
+            let score = play_match(settings, &level_player(level), &depth_opponent(index as u8 + 1));
+            report(&format!("level{} - depth{}", level, index + 1), &score, depth_elo[index] + elo_difference(&score));
+            score
+        };
+        let mut best = (nearest, play_depth(nearest));
+        let neighbour = if best.1.score() < 0.1 && nearest > 0 {
+            Some(nearest - 1)
+        } else if best.1.score() > 0.9 && nearest + 1 < depth_elo.len() {
+            Some(nearest + 1)
+        } else {
+            None
+        };
+        if let Some(index) = neighbour {
+            let score = play_depth(index);
+            if (score.score() - 0.5).abs() < (best.1.score() - 0.5).abs() {
+                best = (index, score);
+            }
+        }
+        let (index, score) = best;
+        previous = depth_elo[index] + elo_difference(&score);
+        level_elo.push(previous);
+    }
+
+    println!();
+    println!("measured: {:?}", level_elo.iter().map(|elo| elo.round() as i32).collect::<Vec<i32>>());
+    let mut table: Vec<u32> = increasing(&level_elo).iter().map(|elo| elo.round().max(0.0) as u32).collect();
+    for level in 1..table.len() {
+        table[level] = table[level].max(table[level - 1] + 1);
+    }
+    println!("const LEVEL_ELO: [u32; {}] = [", LEVELS);
+    for row in table.chunks(10) {
+        let values: Vec<String> = row.iter().map(|elo| elo.to_string()).collect();
+        println!("    {},", values.join(", "));
+    }
+    println!("];");
+}
+
+// The closest sequence that never goes down: runs of values that do are replaced by their average
+fn increasing(values: &[f64]) -> Vec<f64> {
+    let mut runs: Vec<(f64, usize)> = Vec::new();                // average and length
+    for value in values {
+        let (mut sum, mut len) = (*value, 1);
+        while let Some((average, run_len)) = runs.last().copied().filter(|(average, _)| *average > sum / len as f64) {
+            runs.pop();
+            sum += average * run_len as f64;
+            len += run_len;
+        }
+        runs.push((sum / len as f64, len));
+    }
+    runs.iter().flat_map(|(average, len)| std::iter::repeat_n(*average, *len)).collect()
+}
+
+// The Elo the first engine is ahead by; a match one side won outright counts as if the
+// other had drawn one more game, which keeps the difference finite
+fn elo_difference(score: &MatchScore) -> f64 {
+    match score.elo() {
+        Some((elo, _)) => elo,
+        None => elo_from_score((score.wins as f64 + (score.draws as f64 + 1.0) / 2.0) / (score.games() as f64 + 1.0)),
+    }
+}
+
+fn report(pairing: &str, score: &MatchScore, elo: f64) {
+    let error = score.elo().map_or("".to_string(), |(_, error)| format!(" +/- {:.0}", error));
+    println!("{:<18} {:>4}-{:>4}-{:>4}  score {:>5.1}%  diff {:+5.0}{}  rated {:.0}", pairing, score.wins, score.losses, score.draws, 100.0 * score.score(), elo_difference(score), error, elo);
+}
+
+// Plays the openings in turn, each twice with the colours reversed, from the first engine's side
+fn play_match(settings: &Arc<Settings>, first: &EngineSpec, second: &EngineSpec) -> MatchScore {
+    let next_game = Arc::new(AtomicUsize::new(0));
+    let (sender, results) = mpsc::channel();
+    let workers: Vec<_> = (0..settings.play.concurrency.min(settings.games)).map(|_| {
+        let (settings, next_game, sender) = (settings.clone(), next_game.clone(), sender.clone());
+        let specs = [first.clone(), second.clone()];
+        thread::spawn(move || {
+            let mut players = Vec::new();
+            for spec in specs.iter() {
+                match spec.start() {
+                    Ok(player) => players.push(player),
+                    Err(error) => {
+                        println!("cannot start {}: {}", spec.name, error);
+                        return;
+                    }
+                }
+            }
+            loop {
+                let index = next_game.fetch_add(1, Ordering::SeqCst);
+                if index >= settings.games {
+                    break;
+                }
+                let opening = &settings.play.openings[(index / 2) % settings.play.openings.len()];
+                let first_is_white = index % 2 == 0;
+                let (first, second) = players.split_at_mut(1);
+                let (first, second) = (first[0].as_mut(), second[0].as_mut());
+                let pair: [&mut dyn Player; 2] = if first_is_white { [first, second] } else { [second, first] };
+                let game = play_game(pair, opening, settings.play.time_control, &settings.play.adjudication, Vec::new());
+                if sender.send((game.outcome, first_is_white)).is_err() {
+                    break;
+                }
+            }
+        })
+    }).collect();
+    drop(sender);
+
+    let mut score = MatchScore::default();
+    for (outcome, first_is_white) in results {
+        score.add(outcome, first_is_white);
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+    score
+}
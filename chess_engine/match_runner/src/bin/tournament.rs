@@ -0,0 +1,206 @@
+use chess_logic::Outcome;
+use match_runner::*;
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+// tournament new <file> <round-robin|double-round-robin|swiss=<rounds>|gauntlet> [--name text]
+//                (--engine cmd=.. [name=..] [arg=..] [option.<name>=..] | --human <name>)...
+// tournament play <file> [play settings]
+// tournament result <file> <game number> <1-0|0-1|1/2-1/2>
+// tournament show <file>
+//
+// The file keeps the schedule and every result, and is rewritten after each game, so an
+// interrupted tournament continues where it stopped. Games between engines are played by
+// `play`; games with a human wait for their result to be entered.
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let outcome = match (args.get(1).map(String::as_str), args.get(2)) {
+        (Some("new"), Some(path)) if args.len() > 3 => create(path, &args[3], &args[4..]),
+        (Some("play"), Some(path)) => play(path, &args[3..]),
+        (Some("result"), Some(path)) if args.len() == 5 => enter_result(path, &args[3], &args[4]),
+        (Some("show"), Some(path)) => Tournament::load(path).map(|tournament| show(&tournament)),
+        _ => Err(format!(
+            "usage: tournament new <file> <round-robin|double-round-robin|swiss=<rounds>|gauntlet> [--name text] (--engine ... | --human name)...\n       tournament play <file> {}\n       tournament result <file> <game> <1-0|0-1|1/2-1/2>\n       tournament show <file>",
+            PlaySettings::USAGE,
+        )),
+    };
+    if let Err(error) = outcome {
+        println!("{}", error);
+    }
+}
+
+fn create(path: &str, format: &str, args: &[String]) -> Result<(), String> {
+    let format = Format::parse(format)?;
+    let mut name = "Tournament".to_string();
+    let mut participants = Vec::new();
+    for (flag, values) in split_flags(args) {
+        match flag {
+            "--name" => name = values.join(" "),
+            "--human" => participants.push(Participant { name: values.join(" "), engine: None }),
+            "--engine" => {
+                let spec = EngineSpec::parse(&values)?;
+                participants.push(Participant { name: spec.name.clone(), engine: Some(spec) });
+            }
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+    let tournament = Tournament::new(&name, format, participants)?;
+    tournament.save(path)?;
+    println!("{} ({}), {} participants, {} games scheduled, saved to {}", tournament.name, tournament.format, tournament.participants.len(), tournament.pairings.len(), path);
+    Ok(())
+}
+
+fn enter_result(path: &str, game: &str, result: &str) -> Result<(), String> {
+    let mut tournament = Tournament::load(path)?;
+    let game = game.parse::<usize>().ok().filter(|game| *game > 0).ok_or_else(|| format!("invalid game number {}", game))?;
+    let outcome = Outcome::from_pgn(result).ok_or_else(|| format!("invalid result {}", result))?;
+    tournament.record(game - 1, outcome)?;
+    if tournament.pair_next_round() {
+        println!("round {} paired", tournament.current_round());
+    }
+    tournament.save(path)?;
+    show(&tournament);
+    Ok(())
+}
+
+struct Job {
+    game: usize,
+    players: [(usize, EngineSpec); 2],                          // participant and engine, white first
+    opening: usize,
+    tags: Vec<(String, String)>,
+}
+
+fn play(path: &str, args: &[String]) -> Result<(), String> {
+    let mut tournament = Tournament::load(path)?;
+    let mut settings = PlaySettings::default();
+    for (flag, values) in split_flags(args) {
+        if !settings.apply(flag, &values)? {
+            return Err(format!("unknown option {}", flag));
+        }
+    }
+    let settings = Arc::new(settings);
+    let mut pgn_file = match settings.pgn_output.as_ref() {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path).map_err(|error| format!("cannot write {}: {}", path, error))?),
+        None => None,
+    };
+    let date = pgn_date();
+
+    loop {
+        tournament.pair_next_round();
+        let jobs: Vec<Job> = tournament.pairings.iter().enumerate()
+            .filter(|(_, pairing)| pairing.result.is_none())
+            .filter_map(|(game, pairing)| {
+                let white = tournament.participants[pairing.white].engine.clone()?;
+                let black = tournament.participants[pairing.black].engine.clone()?;
+                let tags = vec![
+                    ("Event".to_string(), tournament.name.clone()),
+                    ("Site".to_string(), "?".to_string()),
+                    ("Date".to_string(), date.clone()),
+                    ("Round".to_string(), pairing.round.to_string()),
+                ];
+                Some(Job { game, players: [(pairing.white, white), (pairing.black, black)], opening: pairing.opening, tags })
+            })
+            .collect();
+        if jobs.is_empty() {
+            break;
+        }
+
+        // Workers keep the engines they started for the rest of the batch
+        let queue = Arc::new(Mutex::new(jobs.into_iter().rev().collect::<Vec<_>>()));
+        let (sender, results) = mpsc::channel();
+        let workers: Vec<_> = (0..settings.concurrency).map(|_| {
+            let (queue, sender, settings) = (queue.clone(), sender.clone(), settings.clone());
+            thread::spawn(move || {
+                let mut started: HashMap<usize, Box<dyn Player>> = HashMap::new();
+                loop {
+                    let next = queue.lock().unwrap().pop();
+                    let job = match next {
+                        Some(job) => job,
+                        None => break,
+                    };
+                    for (participant, spec) in job.players.iter() {
+                        if !started.contains_key(participant) {
+                            match spec.start() {
+                                Ok(player) => {
+                                    started.insert(*participant, player);
+                                }
+                                Err(error) => println!("cannot start {}: {}", spec.name, error),
+                            }
+                        }
+                    }
+                    let (white, black) = (job.players[0].0, job.players[1].0);
+                    let mut white_player = started.remove(&white);
+                    let mut black_player = started.remove(&black);
+                    let game = match (white_player.as_mut(), black_player.as_mut()) {
+                        (Some(white_player), Some(black_player)) => {
+                            let opening = &settings.openings[job.opening % settings.openings.len()];
+                            Some(play_game([white_player.as_mut(), black_player.as_mut()], opening, settings.time_control, &settings.adjudication, job.tags.clone()))
+                        }
+                        _ => None,
+                    };
+                    for (participant, player) in [(white, white_player), (black, black_player)] {
+                        if let Some(player) = player {
+                            started.insert(participant, player);
+                        }
+                    }
+                    if sender.send((job.game, game)).is_err() {
+                        break;
+                    }
+                }
+            })
+        }).collect();
+        drop(sender);
+
+        let mut started_all = true;
+        for (game, played) in results {
+            let played = match played {
+                Some(played) => played,
+                None => {
+                    started_all = false;
+                    continue;
+                }
+            };
+            tournament.record(game, played.outcome)?;
+            tournament.save(path)?;
+            if let Some(file) = pgn_file.as_mut() {
+                file.write_all(played.to_pgn().as_bytes()).map_err(|error| format!("cannot write games: {}", error))?;
+            }
+            println!(
+                "game {:>4} round {:>2}: {} - {} {} ({})",
+                game + 1, tournament.pairings[game].round, played.tag("White").unwrap_or("?"), played.tag("Black").unwrap_or("?"),
+                played.outcome.to_pgn(), played.tag("Termination").unwrap_or(""),
+            );
+        }
+        for worker in workers {
+            let _ = worker.join();
+        }
+        if !started_all {
+            return Err("some engines could not be started, their games are still open".to_string());
+        }
+    }
+    show(&tournament);
+    Ok(())
+}
+
+fn show(tournament: &Tournament) {
+    println!("{} ({}), round {} of {}", tournament.name, tournament.format, tournament.current_round(), tournament.rounds());
+    println!();
+    print!("{}", tournament.crosstable());
+    let open: Vec<(usize, &Pairing)> = tournament.pairings.iter().enumerate().filter(|(_, pairing)| pairing.result.is_none()).collect();
+    if !open.is_empty() {
+        println!();
+        println!("games without a result:");
+        for (game, pairing) in open {
+            println!("{:>5}  round {:>2}  {} - {}", game + 1, pairing.round, tournament.participants[pairing.white].name, tournament.participants[pairing.black].name);
+        }
+    }
+    if tournament.is_finished() {
+        println!();
+        println!("the tournament is finished");
+    }
+}
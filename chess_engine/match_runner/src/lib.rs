@@ -0,0 +1,13 @@
+mod openings;
+mod play;
+mod player;
+mod settings;
+mod stats;
+mod tournament;
+
+pub use openings::{Opening, load_openings};
+pub use play::{TimeControl, Adjudication, play_game};
+pub use settings::{PlaySettings, key_values, split_flags, pgn_date};
+pub use player::{Player, EngineSpec, MoveReport};
+pub use stats::{MatchScore, Sprt, SprtDecision, elo_from_score, score_from_elo};
+pub use tournament::{Tournament, Format, Participant, Pairing, Standing};
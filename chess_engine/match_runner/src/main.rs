@@ -0,0 +1,166 @@
+use match_runner::*;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+// match_runner --engine cmd=internal name=A [option.X=v ...] --engine cmd=<path> name=B [arg=..] [option.X=v ...]
+//              [--games 100] [--tc 10+0.1 | 40/60+0.6 | movetime=100 | depth=6 | nodes=20000]
+//              [--concurrency 1] [--openings file.epd|file.pgn] [--pgnout games.pgn]
+//              [--resign score=600 moves=4] [--draw score=10 moves=8 after=40] [--max-moves 200]
+//              [--tb directory] [--sprt elo0=0 elo1=5 alpha=0.05 beta=0.05]
+//
+// Every opening is played twice with colours reversed. With --sprt the match stops as soon
+// as the test accepts one of its hypotheses.
+
+struct Settings {
+    engines: Vec<EngineSpec>,
+    games: usize,
+    sprt: Option<Sprt>,
+    play: PlaySettings,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let settings = match parse_settings(&args[1..]) {
+        Ok(settings) => settings,
+        Err(error) => {
+            println!("{}", error);
+            println!("usage: match_runner --engine cmd=internal|<path> [name=..] [arg=..] [option.<name>=..] --engine ... [--games n] [--sprt elo0=x elo1=y alpha=a beta=b] {}", PlaySettings::USAGE);
+            return;
+        }
+    };
+    run_match(settings);
+}
+
+fn parse_settings(args: &[String]) -> Result<Settings, String> {
+    let mut settings = Settings { engines: Vec::new(), games: 100, sprt: None, play: PlaySettings::default() };
+    for (flag, values) in split_flags(args) {
+        if settings.play.apply(flag, &values)? {
+            continue;
+        }
+        match flag {
+            "--engine" => settings.engines.push(EngineSpec::parse(&values)?),
+            "--games" => settings.games = values.first().and_then(|games| games.parse().ok()).ok_or_else(|| "invalid --games".to_string())?,
+            "--sprt" => {
+                let mut sprt = Sprt::default();
+                for (key, value) in key_values(flag, &values)? {
+                    let number = value.parse::<f64>().map_err(|_| format!("invalid number for {}: {}", flag, value))?;
+                    match key {
+                        "elo0" => sprt.elo0 = number,
+                        "elo1" => sprt.elo1 = number,
+                        "alpha" => sprt.alpha = number,
+                        "beta" => sprt.beta = number,
+                        _ => return Err(format!("unknown setting {} for --sprt", key)),
+                    }
+                }
+                settings.sprt = Some(sprt);
+            }
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+    if settings.engines.len() != 2 {
+        return Err(format!("a match needs two engines, {} given", settings.engines.len()));
+    }
+    Ok(settings)
+}
+
+fn run_match(settings: Settings) {
+    let settings = Arc::new(settings);
+    let next_game = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, results) = mpsc::channel();
+    let date = pgn_date();
+
+    let workers: Vec<_> = (0..settings.play.concurrency.min(settings.games)).map(|_| {
+        let (settings, next_game, stop, sender, date) = (settings.clone(), next_game.clone(), stop.clone(), sender.clone(), date.clone());
+        thread::spawn(move || {
+            let mut players = Vec::new();
+            for spec in settings.engines.iter() {
+                match spec.start() {
+                    Ok(player) => players.push(player),
+                    Err(error) => {
+                        println!("cannot start {}: {}", spec.name, error);
+                        stop.store(true, Ordering::SeqCst);
+                        return;
+                    }
+                }
+            }
+            while !stop.load(Ordering::SeqCst) {
+                let index = next_game.fetch_add(1, Ordering::SeqCst);
+                if index >= settings.games {
+                    break;
+                }
+                // Each opening is played twice, the second time with the colours reversed
+                let opening = &settings.play.openings[(index / 2) % settings.play.openings.len()];
+                let first_is_white = index % 2 == 0;
+                let tags = vec![
+                    ("Event".to_string(), "Engine match".to_string()),
+                    ("Site".to_string(), "?".to_string()),
+                    ("Date".to_string(), date.clone()),
+                    ("Round".to_string(), (index + 1).to_string()),
+                ];
+                let (first, second) = players.split_at_mut(1);
+                let (first, second) = (first[0].as_mut(), second[0].as_mut());
+                let pair: [&mut dyn Player; 2] = if first_is_white { [first, second] } else { [second, first] };
+                let game = play_game(pair, opening, settings.play.time_control, &settings.play.adjudication, tags);
+                if sender.send((index, game, first_is_white)).is_err() {
+                    break;
+                }
+            }
+        })
+    }).collect();
+    drop(sender);
+
+    let mut pgn_file = settings.play.pgn_output.as_ref().and_then(|path| {
+        OpenOptions::new().create(true).write(true).truncate(true).open(path)
+            .map_err(|error| println!("cannot write {}: {}", path, error))
+            .ok()
+    });
+    let mut score = MatchScore::default();
+    let (first_name, second_name) = (&settings.engines[0].name, &settings.engines[1].name);
+    println!("{} vs {}, {} games at {}", first_name, second_name, settings.games, settings.play.time_control);
+
+    for (index, game, first_is_white) in results {
+        score.add(game.outcome, first_is_white);
+        if let Some(file) = pgn_file.as_mut() {
+            if let Err(error) = file.write_all(game.to_pgn().as_bytes()) {
+                println!("cannot write games: {}", error);
+            }
+        }
+        let elo = score.elo().map_or("elo -".to_string(), |(elo, error)| format!("elo {:+.1} +/- {:.1}", elo, error));
+        print!(
+            "game {:>4}: {} - {} {} ({})  score {}-{}-{}  {}",
+            index + 1, game.tag("White").unwrap_or("?"), game.tag("Black").unwrap_or("?"), game.outcome.to_pgn(),
+            game.tag("Termination").unwrap_or(""), score.wins, score.losses, score.draws, elo,
+        );
+        if let Some(sprt) = settings.sprt {
+            let (lower, upper) = sprt.bounds();
+            print!("  llr {:.2} ({:.2}, {:.2})", sprt.llr(&score), lower, upper);
+            if sprt.decide(&score) != SprtDecision::Continue {
+                stop.store(true, Ordering::SeqCst);
+            }
+        }
+        println!();
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    println!();
+    println!("{} vs {}: {} games, {} wins, {} losses, {} draws, score {:.1}%", first_name, second_name, score.games(), score.wins, score.losses, score.draws, 100.0 * score.score());
+    match score.elo() {
+        Some((elo, error)) => println!("elo difference {:+.1} +/- {:.1}, los {:.1}%", elo, error, 100.0 * score.los()),
+        None => println!("elo difference not defined yet, los {:.1}%", 100.0 * score.los()),
+    }
+    if let Some(sprt) = settings.sprt {
+        let verdict = match sprt.decide(&score) {
+            SprtDecision::AcceptH1 => "H1 accepted",
+            SprtDecision::AcceptH0 => "H0 accepted",
+            SprtDecision::Continue => "no decision",
+        };
+        println!("sprt elo0 {} elo1 {} alpha {} beta {}: llr {:.2}, {}", sprt.elo0, sprt.elo1, sprt.alpha, sprt.beta, sprt.llr(&score), verdict);
+    }
+}
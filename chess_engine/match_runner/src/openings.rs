@@ -0,0 +1,52 @@
+use chess_logic::*;
+
+/// A position to start games from and the moves that led there, which are replayed into each game.
+#[derive(Clone)]
+pub struct Opening {
+    pub start: GAME,
+    pub moves: Vec<Move>,
+}
+
+impl Opening {
+    pub fn startpos() -> Opening {
+        Opening { start: init_game(), moves: Vec::new() }
+    }
+}
+
+/// Reads the openings of a PGN file (the main line of each game) or an EPD or FEN file (one position a line).
+/// # Opening suites
+///```
+/// let path = std::env::temp_dir().join("match_runner_openings.epd");
+/// std::fs::write(&path, "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 id \"e4\";\n\n8/8/8/8/8/8/8/k6K w - - 0 1\n").unwrap();
+/// let openings = match_runner::load_openings(path.to_str().unwrap()).unwrap();
+/// assert_eq!(openings.len(), 2);
+/// assert!(openings[0].moves.is_empty());
+///
+/// let path = std::env::temp_dir().join("match_runner_openings.pgn");
+/// std::fs::write(&path, "1. e4 c5 2. Nf3 *\n\n1. d4 Nf6 *\n").unwrap();
+/// let openings = match_runner::load_openings(path.to_str().unwrap()).unwrap();
+/// assert_eq!(openings.iter().map(|opening| opening.moves.len()).collect::<Vec<_>>(), [3, 2]);
+///```
+pub fn load_openings(path: &str) -> Result<Vec<Opening>, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+    let mut openings = Vec::new();
+    if path.to_lowercase().ends_with(".pgn") {
+        for game in parse_pgn(&text) {
+            let game = game.map_err(|error| format!("{}: {}", path, error))?;
+            openings.push(Opening { start: game.start, moves: game.moves });
+        }
+    } else {
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#')) {
+            // FEN lines carry move counters where EPD lines have operations
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let is_fen = fields.len() == 6 && fields[4..].iter().all(|counter| counter.parse::<u32>().is_ok());
+            let start = if is_fen { init_game_from_fen(line.trim()) } else { parse_epd(line).map(|record| record.game) }
+                .map_err(|error| format!("{}:{}: {}", path, number + 1, error))?;
+            openings.push(Opening { start, moves: Vec::new() });
+        }
+    }
+    if openings.is_empty() {
+        return Err(format!("no openings in {}", path));
+    }
+    Ok(openings)
+}
@@ -0,0 +1,239 @@
+use chess_logic::*;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::openings::Opening;
+use crate::player::Player;
+
+const FIXED_LIMIT_TIMEOUT: Duration = Duration::from_secs(60);  // longest wait for a depth or node limited move
+const TIME_MARGIN: Duration = Duration::from_millis(100);       // overstepping the clock by less is tolerated
+const MATE_VALUE: i32 = 100_000;
+
+/// How long the engines think: a game clock, or a fixed limit for every move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeControl {
+    Clock { moves: Option<u32>, base: Duration, increment: Duration },
+    MoveTime(Duration),
+    Depth(u8),
+    Nodes(u64),
+}
+
+impl TimeControl {
+    /// Reads `40/60+0.6` (moves per period, seconds, increment), `10+0.1`, `movetime=100`
+    /// (milliseconds), `depth=6` or `nodes=20000`.
+    /// # Time controls
+    ///```
+    /// use match_runner::TimeControl;
+    /// use std::time::Duration;
+    /// assert_eq!(TimeControl::parse("10+0.1").unwrap(), TimeControl::Clock { moves: None, base: Duration::from_secs(10), increment: Duration::from_millis(100) });
+    /// assert_eq!(TimeControl::parse("40/60").unwrap().to_string(), "40/60");
+    /// assert_eq!(TimeControl::parse("depth=6").unwrap(), TimeControl::Depth(6));
+    /// assert!(TimeControl::parse("fast").is_err());
+    ///```
+    pub fn parse(text: &str) -> Result<TimeControl, String> {
+        let invalid = || format!("invalid time control {}", text);
+        if let Some((kind, value)) = text.split_once('=') {
+            return match kind {
+                "movetime" => Ok(TimeControl::MoveTime(Duration::from_millis(value.parse().map_err(|_| invalid())?))),
+                "depth" => Ok(TimeControl::Depth(value.parse().map_err(|_| invalid())?)),
+                "nodes" => Ok(TimeControl::Nodes(value.parse().map_err(|_| invalid())?)),
+                _ => Err(invalid()),
+            };
+        }
+        let (moves, clock) = match text.split_once('/') {
+            Some((moves, clock)) => (Some(moves.parse::<u32>().map_err(|_| invalid())?), clock),
+            None => (None, text),
+        };
+        let (base, increment) = clock.split_once('+').unwrap_or((clock, "0"));
+        let seconds = |value: &str| value.parse::<f64>().ok().filter(|seconds| *seconds >= 0.0).map(Duration::from_secs_f64).ok_or_else(invalid);
+        Ok(TimeControl::Clock { moves, base: seconds(base)?, increment: seconds(increment)? })
+    }
+}
+
+impl fmt::Display for TimeControl {
+    // The value of the PGN TimeControl tag where there is one
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeControl::Clock { moves, base, increment } => {
+                if let Some(moves) = moves {
+                    write!(f, "{}/", moves)?;
+                }
+                write!(f, "{}", base.as_secs_f64())?;
+                if !increment.is_zero() {
+                    write!(f, "+{}", increment.as_secs_f64())?;
+                }
+                Ok(())
+            }
+            TimeControl::MoveTime(move_time) => write!(f, "movetime={}", move_time.as_millis()),
+            TimeControl::Depth(depth) => write!(f, "depth={}", depth),
+            TimeControl::Nodes(nodes) => write!(f, "nodes={}", nodes),
+        }
+    }
+}
+
+/// When a game is ended before the rules end it. Scores are the ones the engines report.
+#[derive(Default)]
+pub struct Adjudication {
+    /// A side loses once both engines agree for `resign_moves` moves each that it is this many centipawns behind.
+    pub resign_score: Option<i32>,
+    pub resign_moves: usize,
+    /// A draw once both engines see at most this many centipawns for `draw_moves` moves each,
+    /// from move `draw_after` on.
+    pub draw_score: Option<i32>,
+    pub draw_moves: usize,
+    pub draw_after: u32,
+    /// A draw after this many moves of each side.
+    pub max_moves: Option<usize>,
+    /// Positions these tables know are decided by them.
+    pub tables: Option<EndgameTables>,
+}
+
+/// Plays one game from an opening. The game gets the White, Black, Result, TimeControl and
+/// Termination tags after the `tags` given; an engine that fails or runs out of time loses.
+/// # A quick game
+///```
+/// use match_runner::*;
+/// let spec = EngineSpec::parse(&["cmd=internal"]).unwrap();
+/// let (mut white, mut black) = (spec.start().unwrap(), spec.start().unwrap());
+/// let opening = Opening { start: chess_logic::init_game_from_fen("6k1/8/6K1/8/8/8/8/1R6 w - - 0 1").unwrap(), moves: Vec::new() };
+/// let game = play_game([white.as_mut(), black.as_mut()], &opening, TimeControl::Depth(3), &Adjudication::default(), Vec::new());
+/// assert_eq!(game.outcome, chess_logic::Outcome::WhiteWins);
+/// assert_eq!(game.tag("Termination"), Some("checkmate"));
+/// assert_eq!(game.moves.len(), 1);
+///```
+pub fn play_game(mut players: [&mut dyn Player; 2], opening: &Opening, time_control: TimeControl, adjudication: &Adjudication, mut tags: Vec<(String, String)>) -> PgnGame {
+    tags.push(("White".to_string(), players[0].name().to_string()));
+    tags.push(("Black".to_string(), players[1].name().to_string()));
+    let mut game = opening.start.clone();
+    for played in opening.moves.iter() {
+        make_move(&mut game, *played);
+    }
+
+    let mut clocks = [Duration::ZERO; 2];
+    let mut moves_left = [None; 2];
+    if let TimeControl::Clock { moves, base, .. } = time_control {
+        clocks = [base; 2];
+        moves_left = [moves; 2];
+    }
+    let mut scores: Vec<i32> = Vec::new();                      // white's view, one per engine move
+    let mut failure = None;
+    for (side, player) in players.iter_mut().enumerate() {
+        if let Err(error) = player.new_game() {
+            failure = failure.or_else(|| Some((side == 0, format!("{}: {}", player.name(), error))));
+        }
+    }
+
+    let (outcome, termination) = loop {
+        if let Some((side_is_white, reason)) = failure.take() {
+            break (if side_is_white { Outcome::BlackWins } else { Outcome::WhiteWins }, reason);
+        }
+        if let Some((outcome, reason)) = game_outcome(&game) {
+            break (outcome, reason.to_string());
+        }
+        if let Some(dtm) = adjudication.tables.as_ref().and_then(|tables| tables.probe(&game)) {
+            let outcome = match dtm {
+                Dtm::Draw => Outcome::Draw,
+                Dtm::Win(_) if game.is_whites_turn() => Outcome::WhiteWins,
+                Dtm::Loss(_) if !game.is_whites_turn() => Outcome::WhiteWins,
+                _ => Outcome::BlackWins,
+            };
+            break (outcome, "tablebase".to_string());
+        }
+        if let Some(outcome) = adjudicate_scores(adjudication, &scores, &game) {
+            break outcome;
+        }
+        let engine_moves = scores.len() / 2;
+        if adjudication.max_moves.is_some_and(|max_moves| engine_moves >= max_moves) {
+            break (Outcome::Draw, "move limit".to_string());
+        }
+
+        let side = if game.is_whites_turn() { 0 } else { 1 };
+        let (limits, timeout) = move_limits(time_control, clocks, moves_left, side);
+        let started = Instant::now();
+        let report = players[side].play(&game, &limits, timeout);
+        let elapsed = started.elapsed();
+        let report = match report {
+            Ok(report) => report,
+            Err(error) => {
+                failure = Some((side == 0, format!("{}: {}", players[side].name(), error)));
+                continue;
+            }
+        };
+        if let TimeControl::Clock { moves, base, increment } = time_control {
+            if elapsed > clocks[side] + TIME_MARGIN {
+                failure = Some((side == 0, format!("{} lost on time", players[side].name())));
+                continue;
+            }
+            clocks[side] = clocks[side].saturating_sub(elapsed) + increment;
+            if let Some(left) = moves_left[side].as_mut() {
+                *left -= 1;
+                if *left == 0 {
+                    *left = moves.unwrap_or(1);                     // a new period starts
+                    clocks[side] += base;
+                }
+            }
+        }
+        if !make_move(&mut game, report.played) {
+            failure = Some((side == 0, format!("{}: illegal move {}", players[side].name(), report.played.to_uci())));
+            continue;
+        }
+        let score = match report.score {
+            Some(Score::Centipawns(centipawns)) => centipawns,
+            Some(Score::Mate(moves)) if moves > 0 => MATE_VALUE - moves,
+            Some(Score::Mate(moves)) => -MATE_VALUE - moves,
+            None => 0,
+        };
+        scores.push(if side == 0 { score } else { -score });
+    };
+
+    tags.push(("Result".to_string(), outcome.to_pgn().to_string()));
+    if !matches!(time_control, TimeControl::Depth(_) | TimeControl::Nodes(_)) {
+        tags.push(("TimeControl".to_string(), time_control.to_string()));
+    }
+    tags.push(("Termination".to_string(), termination));
+    PgnGame { tags, start: opening.start.clone(), moves: move_history(&game), outcome }
+}
+
+fn move_limits(time_control: TimeControl, clocks: [Duration; 2], moves_left: [Option<u32>; 2], side: usize) -> (SearchLimits, Duration) {
+    match time_control {
+        TimeControl::Clock { increment, .. } => {
+            let limits = SearchLimits {
+                white_time: Some(clocks[0]),
+                black_time: Some(clocks[1]),
+                white_increment: Some(increment),
+                black_increment: Some(increment),
+                moves_to_go: moves_left[side],
+                ..Default::default()
+            };
+            (limits, clocks[side] + TIME_MARGIN)
+        }
+        TimeControl::MoveTime(move_time) => (SearchLimits { move_time: Some(move_time), ..Default::default() }, move_time + TIME_MARGIN * 10),
+        TimeControl::Depth(depth) => (SearchLimits { depth: Some(depth), ..Default::default() }, FIXED_LIMIT_TIMEOUT),
+        TimeControl::Nodes(nodes) => (SearchLimits { nodes: Some(nodes), ..Default::default() }, FIXED_LIMIT_TIMEOUT),
+    }
+}
+
+// Resignation and draw adjudication from the latest scores, which alternate between the engines
+fn adjudicate_scores(adjudication: &Adjudication, scores: &[i32], game: &GAME) -> Option<(Outcome, String)> {
+    if let Some(threshold) = adjudication.resign_score {
+        let window = 2 * adjudication.resign_moves.max(1);
+        if scores.len() >= window {
+            let recent = &scores[scores.len() - window..];
+            if recent.iter().all(|score| *score <= -threshold) {
+                return Some((Outcome::BlackWins, "adjudication: white resigns".to_string()));
+            }
+            if recent.iter().all(|score| *score >= threshold) {
+                return Some((Outcome::WhiteWins, "adjudication: black resigns".to_string()));
+            }
+        }
+    }
+    if let Some(threshold) = adjudication.draw_score {
+        let window = 2 * adjudication.draw_moves.max(1);
+        let fullmove_number = game_to_fen(game).rsplit(' ').next().and_then(|number| number.parse::<u32>().ok()).unwrap_or(0);
+        if fullmove_number >= adjudication.draw_after && scores.len() >= window && scores[scores.len() - window..].iter().all(|score| score.abs() <= threshold) {
+            return Some((Outcome::Draw, "adjudication: draw".to_string()));
+        }
+    }
+    None
+}
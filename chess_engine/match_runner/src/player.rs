@@ -0,0 +1,174 @@
+use chess_logic::*;
+use uci_client::UciClient;
+
+use std::time::Duration;
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A move an engine chose and the score it gave, from its own side.
+#[derive(Clone, Copy, Debug)]
+pub struct MoveReport {
+    pub played: Move,
+    pub score: Option<Score>,
+}
+
+/// Something that plays moves: the engine in this process or an external UCI binary.
+pub trait Player {
+    fn name(&self) -> &str;
+    fn new_game(&mut self) -> Result<(), String>;
+    /// Chooses a move in the game; `timeout` is how long the player may take at most.
+    fn play(&mut self, game: &GAME, limits: &SearchLimits, timeout: Duration) -> Result<MoveReport, String>;
+}
+
+/// How to start an engine, written as key=value pairs: `cmd=internal` or `cmd=<path>`,
+/// `name=`, `arg=` for each argument of a UCI binary and `option.<Name>=` for its options.
+/// The internal engine understands `option.Hash`, `option.Threads`, `option.UCI_Elo` and the
+/// switches of `SearchOptions` by their field names, with `option.skill` taking a level.
+/// `option.depth` caps its search depth whatever the time control, for fixed depth opponents.
+/// # Engine configurations
+///```
+/// let spec = match_runner::EngineSpec::parse(&["cmd=internal", "name=NoNull", "option.null_move_pruning=false"]).unwrap();
+/// assert_eq!(spec.name, "NoNull");
+/// let mut player = spec.start().unwrap();
+/// let report = player.play(&chess_logic::init_game(), &chess_logic::SearchLimits { depth: Some(2), ..Default::default() }, std::time::Duration::from_secs(5)).unwrap();
+/// assert!(chess_logic::generate_legal_moves(&chess_logic::init_game()).contains(&report.played));
+/// assert!(match_runner::EngineSpec::parse(&["name=nothing"]).is_err());
+/// assert!(match_runner::EngineSpec::parse(&["cmd=internal", "option.Ponies=3"]).unwrap().start().is_err());
+///```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EngineSpec {
+    pub name: String,
+    pub command: String,                                        // "internal" for the engine in this process
+    pub arguments: Vec<String>,
+    pub options: Vec<(String, String)>,
+}
+
+impl EngineSpec {
+    pub fn parse(pairs: &[&str]) -> Result<EngineSpec, String> {
+        let mut spec = EngineSpec { name: String::new(), command: String::new(), arguments: Vec::new(), options: Vec::new() };
+        for pair in pairs {
+            let (key, value) = pair.split_once('=').ok_or_else(|| format!("expected key=value, found {}", pair))?;
+            match key {
+                "cmd" => spec.command = value.to_string(),
+                "name" => spec.name = value.to_string(),
+                "arg" => spec.arguments.push(value.to_string()),
+                key => match key.strip_prefix("option.") {
+                    Some(option) => spec.options.push((option.to_string(), value.to_string())),
+                    None => return Err(format!("unknown engine setting {}", key)),
+                },
+            }
+        }
+        if spec.command.is_empty() {
+            return Err("an engine needs cmd=internal or cmd=<path>".to_string());
+        }
+        if spec.name.is_empty() {
+            spec.name = spec.command.rsplit(['/', '\\']).next().unwrap_or("engine").to_string();
+        }
+        Ok(spec)
+    }
+
+    /// The inverse of `parse`.
+    pub fn to_pairs(&self) -> Vec<String> {
+        let mut pairs = vec![format!("cmd={}", self.command), format!("name={}", self.name)];
+        pairs.extend(self.arguments.iter().map(|argument| format!("arg={}", argument)));
+        pairs.extend(self.options.iter().map(|(name, value)| format!("option.{}={}", name, value)));
+        pairs
+    }
+
+    pub fn is_internal(&self) -> bool {
+        self.command == "internal"
+    }
+
+    pub fn start(&self) -> Result<Box<dyn Player>, String> {
+        if self.is_internal() {
+            let depth_limit = match self.options.iter().find(|(name, _)| name == "depth") {
+                Some((_, value)) => Some(value.parse::<u8>().map_err(|_| format!("invalid value for depth: {}", value))?),
+                None => None,
+            };
+            return Ok(Box::new(InternalPlayer { name: self.name.clone(), searcher: Searcher::new(self.search_options()?), depth_limit }));
+        }
+        let arguments: Vec<&str> = self.arguments.iter().map(String::as_str).collect();
+        let mut client = UciClient::spawn(&self.command, &arguments, STARTUP_TIMEOUT).map_err(|error| error.to_string())?;
+        for (name, value) in self.options.iter() {
+            client.set_option(name, value).map_err(|error| error.to_string())?;
+        }
+        client.is_ready(STARTUP_TIMEOUT).map_err(|error| error.to_string())?;
+        Ok(Box::new(UciPlayer { name: self.name.clone(), client }))
+    }
+
+    fn search_options(&self) -> Result<SearchOptions, String> {
+        let mut options = SearchOptions::default();
+        for (name, value) in self.options.iter() {
+            let invalid = || format!("invalid value for {}: {}", name, value);
+            let switch = || value.parse::<bool>().map_err(|_| invalid());
+            match name.as_str() {
+                "Hash" => options.hash_size_mb = value.parse().map_err(|_| invalid())?,
+                "Threads" => options.threads = value.parse().map_err(|_| invalid())?,
+                "move_ordering" => options.move_ordering = switch()?,
+                "null_move_pruning" => options.null_move_pruning = switch()?,
+                "late_move_reductions" => options.late_move_reductions = switch()?,
+                "futility_pruning" => options.futility_pruning = switch()?,
+                "reverse_futility_pruning" => options.reverse_futility_pruning = switch()?,
+                "check_extensions" => options.check_extensions = switch()?,
+                "aspiration_windows" => options.aspiration_windows = switch()?,
+                "skill" => options.skill = Skill::new(value.parse().map_err(|_| invalid())?),
+                "UCI_Elo" => options.skill = Skill::from_elo(value.parse().map_err(|_| invalid())?),
+                "depth" => {}                                   // a limit of the player, not of the search
+                _ => return Err(format!("the internal engine has no option {}", name)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+struct InternalPlayer {
+    name: String,
+    searcher: Searcher,
+    depth_limit: Option<u8>,
+}
+
+impl Player for InternalPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        self.searcher.clear();
+        Ok(())
+    }
+
+    fn play(&mut self, game: &GAME, limits: &SearchLimits, _timeout: Duration) -> Result<MoveReport, String> {
+        let limits = match self.depth_limit {
+            Some(depth_limit) => SearchLimits { depth: Some(limits.depth.map_or(depth_limit, |depth| depth.min(depth_limit))), ..*limits },
+            None => *limits,
+        };
+        let result = self.searcher.search(game, limits);
+        match result.best_move {
+            Some(played) => Ok(MoveReport { played, score: Some(Score::from_search(result.score)) }),
+            None => Err("no move found".to_string()),
+        }
+    }
+}
+
+struct UciPlayer {
+    name: String,
+    client: UciClient,
+}
+
+impl Player for UciPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        self.client.new_game().and_then(|_| self.client.is_ready(STARTUP_TIMEOUT)).map_err(|error| error.to_string())
+    }
+
+    fn play(&mut self, game: &GAME, limits: &SearchLimits, timeout: Duration) -> Result<MoveReport, String> {
+        self.client.set_position(game).map_err(|error| error.to_string())?;
+        let outcome = self.client.go(limits, timeout).map_err(|error| error.to_string())?;
+        let played = move_from_uci(game, &outcome.best_move.best_move).ok_or_else(|| format!("illegal move {}", outcome.best_move.best_move))?;
+        let score = outcome.infos.iter().rev().find_map(|info| info.score);
+        Ok(MoveReport { played, score })
+    }
+}
@@ -0,0 +1,113 @@
+use chess_logic::EndgameTables;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::openings::{Opening, load_openings};
+use crate::play::{Adjudication, TimeControl};
+
+/// What the match and tournament binaries share: how games are played and where they are written.
+pub struct PlaySettings {
+    pub time_control: TimeControl,
+    pub concurrency: usize,
+    pub openings: Vec<Opening>,
+    pub pgn_output: Option<String>,
+    pub adjudication: Adjudication,
+}
+
+impl Default for PlaySettings {
+    fn default() -> PlaySettings {
+        PlaySettings {
+            time_control: TimeControl::Clock { moves: None, base: Duration::from_secs(10), increment: Duration::from_millis(100) },
+            concurrency: 1,
+            openings: vec![Opening::startpos()],
+            pgn_output: None,
+            adjudication: Adjudication::default(),
+        }
+    }
+}
+
+impl PlaySettings {
+    pub const USAGE: &'static str = "[--tc 10+0.1|40/60+0.6|movetime=ms|depth=n|nodes=n] [--concurrency n] [--openings file.epd|file.pgn] [--pgnout file] [--resign score=cp moves=n] [--draw score=cp moves=n after=n] [--max-moves n] [--tb directory]";
+
+    /// Takes a command line flag with the values that follow it. Flags that are not
+    /// about playing games give `Ok(false)`.
+    /// # Shared flags
+    ///```
+    /// use match_runner::*;
+    /// let mut settings = PlaySettings::default();
+    /// assert_eq!(settings.apply("--tc", &["depth=4"]), Ok(true));
+    /// assert_eq!(settings.time_control, TimeControl::Depth(4));
+    /// assert_eq!(settings.apply("--draw", &["score=5", "after=30"]), Ok(true));
+    /// assert_eq!((settings.adjudication.draw_score, settings.adjudication.draw_moves, settings.adjudication.draw_after), (Some(5), 8, 30));
+    /// assert_eq!(settings.apply("--games", &["10"]), Ok(false));
+    /// assert!(settings.apply("--concurrency", &[]).is_err());
+    ///```
+    pub fn apply(&mut self, flag: &str, values: &[&str]) -> Result<bool, String> {
+        let single = || values.first().copied().ok_or_else(|| format!("{} needs a value", flag));
+        match flag {
+            "--tc" => self.time_control = TimeControl::parse(single()?)?,
+            "--concurrency" => self.concurrency = single()?.parse::<usize>().map_err(|_| "invalid --concurrency".to_string())?.max(1),
+            "--openings" => self.openings = load_openings(single()?)?,
+            "--pgnout" => self.pgn_output = Some(single()?.to_string()),
+            "--max-moves" => self.adjudication.max_moves = Some(single()?.parse().map_err(|_| "invalid --max-moves".to_string())?),
+            "--tb" => self.adjudication.tables = Some(EndgameTables::open(single()?)?),
+            "--resign" | "--draw" => {
+                let pairs = key_values(flag, values)?;
+                let (mut score, mut moves, mut after) = (None, None, 40);
+                for (key, value) in pairs {
+                    let number = value.parse::<i64>().map_err(|_| format!("invalid number for {}: {}", flag, value))?;
+                    match key {
+                        "score" => score = Some(number as i32),
+                        "moves" => moves = Some(number as usize),
+                        "after" if flag == "--draw" => after = number as u32,
+                        _ => return Err(format!("unknown setting {} for {}", key, flag)),
+                    }
+                }
+                if flag == "--resign" {
+                    self.adjudication.resign_score = Some(score.unwrap_or(600));
+                    self.adjudication.resign_moves = moves.unwrap_or(4);
+                } else {
+                    self.adjudication.draw_score = Some(score.unwrap_or(10));
+                    self.adjudication.draw_moves = moves.unwrap_or(8);
+                    self.adjudication.draw_after = after;
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Splits `key=value` words.
+pub fn key_values<'a>(flag: &str, values: &[&'a str]) -> Result<Vec<(&'a str, &'a str)>, String> {
+    values.iter().map(|pair| pair.split_once('=').ok_or_else(|| format!("expected key=value after {}, found {}", flag, pair))).collect()
+}
+
+/// Groups command line arguments into flags and the values up to the next flag.
+pub fn split_flags(args: &[String]) -> Vec<(&str, Vec<&str>)> {
+    let mut flags: Vec<(&str, Vec<&str>)> = Vec::new();
+    for arg in args {
+        if arg.starts_with("--") || flags.is_empty() {
+            flags.push((arg.as_str(), Vec::new()));
+        } else if let Some((_, values)) = flags.last_mut() {
+            values.push(arg.as_str());
+        }
+    }
+    flags
+}
+
+/// The current date as the PGN Date tag writes it.
+pub fn pgn_date() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() / 86_400) as i64;
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant's algorithm
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
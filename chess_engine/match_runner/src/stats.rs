@@ -0,0 +1,155 @@
+use chess_logic::Outcome;
+
+/// Wins, draws and losses from the first engine's side.
+/// # Elo from a match
+///```
+/// use match_runner::MatchScore;
+/// let score = MatchScore { wins: 60, draws: 20, losses: 20 };
+/// assert_eq!(score.games(), 100);
+/// assert!((score.score() - 0.7).abs() < 1e-9);
+/// let (elo, error) = score.elo().unwrap();
+/// assert!((elo - 147.2).abs() < 0.1);
+/// assert!(error > 50.0 && error < 100.0);
+/// assert!(score.los() > 0.999);
+/// assert_eq!(MatchScore { wins: 3, draws: 0, losses: 0 }.elo(), None);
+///```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchScore {
+    /// Counts a game in which the first engine had white when `first_is_white`.
+    pub fn add(&mut self, outcome: Outcome, first_is_white: bool) {
+        match (outcome, first_is_white) {
+            (Outcome::WhiteWins, true) | (Outcome::BlackWins, false) => self.wins += 1,
+            (Outcome::WhiteWins, false) | (Outcome::BlackWins, true) => self.losses += 1,
+            (Outcome::Draw, _) => self.draws += 1,
+            (Outcome::Unfinished, _) => {}
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points per game, between 0 and 1.
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    // Variance of the points of one game
+    fn variance(&self) -> f64 {
+        let games = self.games() as f64;
+        let score = self.score();
+        (self.wins as f64 * (1.0 - score).powi(2) + self.draws as f64 * (0.5 - score).powi(2) + self.losses as f64 * score.powi(2)) / games
+    }
+
+    /// The Elo difference and the half width of its 95% confidence interval, or None while
+    /// one side has scored every point.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let score = self.score();
+        if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+        let margin = 1.959964 * (self.variance() / self.games() as f64).sqrt();
+        let lower = elo_from_score((score - margin).max(1e-6));
+        let upper = elo_from_score((score + margin).min(1.0 - 1e-6));
+        Some((elo_from_score(score), (upper - lower) / 2.0))
+    }
+
+    /// Likelihood of superiority: how likely the first engine is the stronger one, from wins and losses.
+    pub fn los(&self) -> f64 {
+        let decisive = (self.wins + self.losses) as f64;
+        if decisive == 0.0 {
+            return 0.5;
+        }
+        0.5 * (1.0 + erf((self.wins as f64 - self.losses as f64) / (2.0 * decisive).sqrt()))
+    }
+}
+
+pub fn elo_from_score(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10() + 0.0                // never -0
+}
+
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// Abramowitz and Stegun 7.1.26, good to about 1e-7
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let value = 1.0 - polynomial * (-x * x).exp();
+    if x < 0.0 { -value } else { value }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtDecision {
+    AcceptH0,                                                   // the gain is at most elo0
+    AcceptH1,                                                   // the gain is at least elo1
+    Continue,
+}
+
+/// Sequential probability ratio test of H0: the first engine is elo0 stronger, against
+/// H1: it is elo1 stronger, with error rates alpha and beta. The log-likelihood ratio uses
+/// the normal approximation of the game results.
+/// # Stopping a match early
+///```
+/// use match_runner::*;
+/// let sprt = Sprt { elo0: 0.0, elo1: 10.0, alpha: 0.05, beta: 0.05 };
+/// let (lower, upper) = sprt.bounds();
+/// assert!((lower + 2.944).abs() < 0.001 && (upper - 2.944).abs() < 0.001);
+/// assert_eq!(sprt.decide(&MatchScore { wins: 10, draws: 10, losses: 10 }), SprtDecision::Continue);
+/// assert_eq!(sprt.decide(&MatchScore { wins: 400, draws: 200, losses: 250 }), SprtDecision::AcceptH1);
+/// assert_eq!(sprt.decide(&MatchScore { wins: 250, draws: 200, losses: 400 }), SprtDecision::AcceptH0);
+///```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Sprt {
+        Sprt { elo0: 0.0, elo1: 5.0, alpha: 0.05, beta: 0.05 }
+    }
+}
+
+impl Sprt {
+    /// The log-likelihood ratios at which H0 and H1 are accepted.
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        if score.games() == 0 {
+            return 0.0;
+        }
+        let variance = score.variance();
+        if variance == 0.0 {
+            return 0.0;
+        }
+        let (score0, score1) = (score_from_elo(self.elo0), score_from_elo(self.elo1));
+        score.games() as f64 * (score1 - score0) * (2.0 * score.score() - score0 - score1) / (2.0 * variance)
+    }
+
+    pub fn decide(&self, score: &MatchScore) -> SprtDecision {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtDecision::AcceptH1
+        } else if llr <= lower {
+            SprtDecision::AcceptH0
+        } else {
+            SprtDecision::Continue
+        }
+    }
+}
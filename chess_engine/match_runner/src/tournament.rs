@@ -0,0 +1,422 @@
+use chess_logic::Outcome;
+
+use std::fmt;
+
+use crate::player::EngineSpec;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    RoundRobin,
+    DoubleRoundRobin,
+    Swiss(u32),                                                 // number of rounds
+    Gauntlet,                                                   // the first participant plays everyone else twice
+}
+
+impl Format {
+    pub fn parse(text: &str) -> Result<Format, String> {
+        match text {
+            "round-robin" => Ok(Format::RoundRobin),
+            "double-round-robin" => Ok(Format::DoubleRoundRobin),
+            "gauntlet" => Ok(Format::Gauntlet),
+            _ => match text.strip_prefix("swiss=").map(|rounds| rounds.parse::<u32>()) {
+                Some(Ok(rounds)) if rounds > 0 => Ok(Format::Swiss(rounds)),
+                _ => Err(format!("unknown format {}, expected round-robin, double-round-robin, swiss=<rounds> or gauntlet", text)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::RoundRobin => write!(f, "round-robin"),
+            Format::DoubleRoundRobin => write!(f, "double-round-robin"),
+            Format::Swiss(rounds) => write!(f, "swiss={}", rounds),
+            Format::Gauntlet => write!(f, "gauntlet"),
+        }
+    }
+}
+
+/// An engine configuration, or a human whose results are entered by hand.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Participant {
+    pub name: String,
+    pub engine: Option<EngineSpec>,
+}
+
+/// A scheduled game; participants are indices into the tournament's list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pairing {
+    pub round: u32,
+    pub white: usize,
+    pub black: usize,
+    pub opening: usize,                                         // games of a pair with reversed colours share their opening
+    pub result: Option<Outcome>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub participant: usize,
+    pub points: f64,
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub byes: u32,
+    pub sonneborn_berger: f64,
+}
+
+/// A tournament among engines and humans: its schedule, the results so far, and its
+/// standings with Sonneborn-Berger as the tiebreak. Swiss rounds are paired one at a
+/// time once the previous round is complete; a Swiss bye is worth a point.
+/// # A small round robin
+///```
+/// use match_runner::*;
+/// use chess_logic::Outcome;
+/// let humans = ["Ann", "Bob", "Cid"].iter().map(|name| Participant { name: name.to_string(), engine: None }).collect();
+/// let mut tournament = Tournament::new("Club", Format::RoundRobin, humans).unwrap();
+/// assert_eq!(tournament.pairings.len(), 3);
+/// for game in 0..3 {
+///     let pairing = tournament.pairings[game];
+///     // Ann beats everyone, Bob and Cid draw
+///     let outcome = match (pairing.white, pairing.black) {
+///         (0, _) => Outcome::WhiteWins,
+///         (_, 0) => Outcome::BlackWins,
+///         _ => Outcome::Draw,
+///     };
+///     tournament.record(game, outcome).unwrap();
+/// }
+/// assert!(tournament.is_finished());
+/// let standings = tournament.standings();
+/// assert_eq!((standings[0].participant, standings[0].points, standings[0].sonneborn_berger), (0, 2.0, 1.0));
+/// assert_eq!(standings[1].points, 0.5);
+/// assert!(tournament.crosstable().contains("Ann"));
+///
+/// let saved = tournament.to_text();
+/// assert_eq!(Tournament::from_text(&saved).unwrap().to_text(), saved);
+///```
+/// # Swiss pairings
+///```
+/// use match_runner::*;
+/// use chess_logic::Outcome;
+/// let players = (0..5).map(|index| Participant { name: format!("P{}", index), engine: None }).collect();
+/// let mut tournament = Tournament::new("Open", Format::Swiss(3), players).unwrap();
+/// while !tournament.is_finished() {
+///     let round = tournament.current_round();
+///     for game in 0..tournament.pairings.len() {
+///         if tournament.pairings[game].round == round {
+///             tournament.record(game, Outcome::WhiteWins).unwrap();
+///         }
+///     }
+///     tournament.pair_next_round();
+/// }
+/// assert_eq!(tournament.pairings.len(), 6);
+/// assert_eq!(tournament.byes.len(), 3);
+/// let points: f64 = tournament.standings().iter().map(|standing| standing.points).sum();
+/// assert_eq!(points, 9.0);
+///```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tournament {
+    pub name: String,
+    pub format: Format,
+    pub participants: Vec<Participant>,
+    pub pairings: Vec<Pairing>,
+    pub byes: Vec<(u32, usize)>,                                // round, participant
+}
+
+impl Tournament {
+    pub fn new(name: &str, format: Format, participants: Vec<Participant>) -> Result<Tournament, String> {
+        if participants.len() < 2 {
+            return Err("a tournament needs at least two participants".to_string());
+        }
+        let mut tournament = Tournament { name: name.to_string(), format, participants, pairings: Vec::new(), byes: Vec::new() };
+        match format {
+            Format::RoundRobin | Format::DoubleRoundRobin => tournament.schedule_round_robin(),
+            Format::Gauntlet => {
+                for opponent in 1..tournament.participants.len() {
+                    let round = 2 * opponent as u32 - 1;
+                    tournament.pairings.push(Pairing { round, white: 0, black: opponent, opening: opponent - 1, result: None });
+                    tournament.pairings.push(Pairing { round: round + 1, white: opponent, black: 0, opening: opponent - 1, result: None });
+                }
+            }
+            Format::Swiss(_) => tournament.pair_swiss_round(1),
+        }
+        Ok(tournament)
+    }
+
+    // Berger tables by the circle method; with an odd number of players one of them rests each round
+    fn schedule_round_robin(&mut self) {
+        let players = self.participants.len();
+        let slots = players + players % 2;
+        let mut circle: Vec<usize> = (0..slots).collect();
+        let mut first_leg = Vec::new();
+        for round in 0..slots - 1 {
+            for board in 0..slots / 2 {
+                let (first, second) = (circle[board], circle[slots - 1 - board]);
+                if first >= players || second >= players {
+                    continue;
+                }
+                let first_is_white = if board == 0 { round % 2 == 0 } else { board % 2 == 1 };
+                let (white, black) = if first_is_white { (first, second) } else { (second, first) };
+                first_leg.push(Pairing { round: round as u32 + 1, white, black, opening: first_leg.len(), result: None });
+            }
+            circle[1..].rotate_right(1);
+        }
+        self.pairings = first_leg.clone();
+        if self.format == Format::DoubleRoundRobin {
+            let rounds = slots as u32 - 1;
+            self.pairings.extend(first_leg.iter().map(|pairing| Pairing { round: pairing.round + rounds, white: pairing.black, black: pairing.white, ..*pairing }));
+        }
+    }
+
+    pub fn rounds(&self) -> u32 {
+        match self.format {
+            Format::Swiss(rounds) => rounds,
+            _ => self.pairings.iter().map(|pairing| pairing.round).max().unwrap_or(0),
+        }
+    }
+
+    /// The latest round that has been paired.
+    pub fn current_round(&self) -> u32 {
+        let games = self.pairings.iter().map(|pairing| pairing.round);
+        games.chain(self.byes.iter().map(|(round, _)| *round)).max().unwrap_or(0)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pairings.iter().all(|pairing| pairing.result.is_some()) && self.current_round() >= self.rounds()
+    }
+
+    pub fn record(&mut self, game: usize, outcome: Outcome) -> Result<(), String> {
+        let pairing = self.pairings.get_mut(game).ok_or_else(|| format!("there is no game {}", game + 1))?;
+        pairing.result = if outcome == Outcome::Unfinished { None } else { Some(outcome) };
+        Ok(())
+    }
+
+    /// Pairs the next Swiss round once every game of the current one has a result.
+    /// Returns whether a round was paired.
+    pub fn pair_next_round(&mut self) -> bool {
+        let round = self.current_round();
+        let complete = self.pairings.iter().filter(|pairing| pairing.round == round).all(|pairing| pairing.result.is_some());
+        match self.format {
+            Format::Swiss(rounds) if complete && round < rounds => {
+                self.pair_swiss_round(round + 1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn pair_swiss_round(&mut self, round: u32) {
+        let points = self.points();
+        let mut ranked: Vec<usize> = (0..self.participants.len()).collect();
+        ranked.sort_by(|first, second| points[*second].partial_cmp(&points[*first]).unwrap().then(first.cmp(second)));
+
+        if ranked.len() % 2 == 1 {
+            let had_bye = |player: &usize| self.byes.iter().any(|(_, with_bye)| with_bye == player);
+            let position = ranked.iter().rposition(|player| !had_bye(player)).unwrap_or(ranked.len() - 1);
+            self.byes.push((round, ranked.remove(position)));
+        }
+        let met = |first: usize, second: usize| {
+            self.pairings.iter().any(|pairing| (pairing.white, pairing.black) == (first, second) || (pairing.white, pairing.black) == (second, first))
+        };
+        let pairs = pair_in_order(&ranked, &|first, second| !met(first, second)).unwrap_or_else(|| pair_in_order(&ranked, &|_, _| true).unwrap());
+
+        for (first, second) in pairs {
+            let whites = |player: usize| self.pairings.iter().filter(|pairing| pairing.white == player).count() as i32
+                - self.pairings.iter().filter(|pairing| pairing.black == player).count() as i32;
+            let last_was_white = |player: usize| self.pairings.iter().rev().find(|pairing| pairing.white == player || pairing.black == player).map(|pairing| pairing.white == player);
+            // The player who had white less often gets it, then the one who had black last time
+            let first_is_white = match whites(first).cmp(&whites(second)) {
+                std::cmp::Ordering::Less => true,
+                std::cmp::Ordering::Greater => false,
+                std::cmp::Ordering::Equal => last_was_white(first) != Some(true),
+            };
+            let (white, black) = if first_is_white { (first, second) } else { (second, first) };
+            self.pairings.push(Pairing { round, white, black, opening: round as usize - 1, result: None });
+        }
+    }
+
+    fn points(&self) -> Vec<f64> {
+        let mut points = vec![0.0; self.participants.len()];
+        for pairing in self.pairings.iter() {
+            let (white, black) = match pairing.result {
+                Some(Outcome::WhiteWins) => (1.0, 0.0),
+                Some(Outcome::BlackWins) => (0.0, 1.0),
+                Some(Outcome::Draw) => (0.5, 0.5),
+                _ => continue,
+            };
+            points[pairing.white] += white;
+            points[pairing.black] += black;
+        }
+        for (_, player) in self.byes.iter() {
+            points[*player] += 1.0;
+        }
+        points
+    }
+
+    /// Standings by points, then Sonneborn-Berger: the points of the opponents a participant
+    /// beat plus half the points of those it drew with.
+    pub fn standings(&self) -> Vec<Standing> {
+        let points = self.points();
+        let mut standings: Vec<Standing> = (0..self.participants.len()).map(|participant| Standing {
+            participant,
+            points: points[participant],
+            games: 0,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            byes: self.byes.iter().filter(|(_, player)| *player == participant).count() as u32,
+            sonneborn_berger: 0.0,
+        }).collect();
+        for pairing in self.pairings.iter() {
+            let white_score = match pairing.result {
+                Some(Outcome::WhiteWins) => 1.0,
+                Some(Outcome::BlackWins) => 0.0,
+                Some(Outcome::Draw) => 0.5,
+                _ => continue,
+            };
+            for (player, opponent, score) in [(pairing.white, pairing.black, white_score), (pairing.black, pairing.white, 1.0 - white_score)].iter() {
+                let standing = &mut standings[*player];
+                standing.games += 1;
+                if *score == 1.0 {
+                    standing.wins += 1;
+                } else if *score == 0.0 {
+                    standing.losses += 1;
+                } else {
+                    standing.draws += 1;
+                }
+                standing.sonneborn_berger += score * points[*opponent];
+            }
+        }
+        standings.sort_by(|first, second| {
+            second.points.partial_cmp(&first.points).unwrap()
+                .then(second.sonneborn_berger.partial_cmp(&first.sonneborn_berger).unwrap())
+                .then(second.wins.cmp(&first.wins))
+                .then(first.participant.cmp(&second.participant))
+        });
+        standings
+    }
+
+    /// The standings with every result against every opponent: 1, = or 0 from the row's side,
+    /// `.` for a game still to be played, one sign per game in the order they were scheduled.
+    pub fn crosstable(&self) -> String {
+        let standings = self.standings();
+        let rank_of: Vec<usize> = {
+            let mut ranks = vec![0; self.participants.len()];
+            for (rank, standing) in standings.iter().enumerate() {
+                ranks[standing.participant] = rank;
+            }
+            ranks
+        };
+        let mut cells = vec![vec![String::new(); self.participants.len()]; self.participants.len()];
+        for pairing in self.pairings.iter() {
+            let (white_sign, black_sign) = match pairing.result {
+                Some(Outcome::WhiteWins) => ('1', '0'),
+                Some(Outcome::BlackWins) => ('0', '1'),
+                Some(Outcome::Draw) => ('=', '='),
+                _ => ('.', '.'),
+            };
+            cells[pairing.white][pairing.black].push(white_sign);
+            cells[pairing.black][pairing.white].push(black_sign);
+        }
+        let name_width = self.participants.iter().map(|participant| participant.name.len()).max().unwrap_or(4).max(4);
+        let cell_width = cells.iter().flatten().map(String::len).max().unwrap_or(1).max(standings.len().to_string().len()) + 1;
+
+        let mut table = format!("{:>3}  {:<width$} {:>5} {:>6} {:>5}  ", "#", "name", "pts", "sb", "games", width = name_width);
+        for rank in 0..standings.len() {
+            table += &format!("{:>width$}", rank + 1, width = cell_width);
+        }
+        table.push('\n');
+        for (rank, standing) in standings.iter().enumerate() {
+            let player = standing.participant;
+            table += &format!("{:>3}  {:<width$} {:>5.1} {:>6.2} {:>5}  ", rank + 1, self.participants[player].name, standing.points, standing.sonneborn_berger, standing.games, width = name_width);
+            let mut row = vec![String::new(); standings.len()];
+            for (opponent, cell) in cells[player].iter().enumerate() {
+                row[rank_of[opponent]] = if opponent == player { "*".to_string() } else { cell.clone() };
+            }
+            for cell in row {
+                table += &format!("{:>width$}", cell, width = cell_width);
+            }
+            table.push('\n');
+        }
+        table
+    }
+
+    /// The tournament as text, one tab separated record a line, for `from_text` to read back.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("name\t{}\nformat\t{}\n", self.name, self.format);
+        for participant in self.participants.iter() {
+            let kind = participant.engine.as_ref().map_or("human".to_string(), |engine| engine.to_pairs().join("\t"));
+            text += &format!("player\t{}\t{}\n", participant.name, kind);
+        }
+        for pairing in self.pairings.iter() {
+            let result = pairing.result.unwrap_or(Outcome::Unfinished).to_pgn();
+            text += &format!("game\t{}\t{}\t{}\t{}\t{}\n", pairing.round, pairing.white, pairing.black, pairing.opening, result);
+        }
+        for (round, player) in self.byes.iter() {
+            text += &format!("bye\t{}\t{}\n", round, player);
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Tournament, String> {
+        let mut tournament = Tournament { name: String::new(), format: Format::RoundRobin, participants: Vec::new(), pairings: Vec::new(), byes: Vec::new() };
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let invalid = || format!("line {}: cannot read '{}'", number + 1, line);
+            let index = |field: usize| fields.get(field).and_then(|value| value.parse::<usize>().ok()).ok_or_else(invalid);
+            match fields[0] {
+                "name" => tournament.name = fields.get(1).ok_or_else(invalid)?.to_string(),
+                "format" => tournament.format = Format::parse(fields.get(1).ok_or_else(invalid)?)?,
+                "player" if fields.len() >= 3 => {
+                    let engine = if fields[2] == "human" { None } else { Some(EngineSpec::parse(&fields[2..])?) };
+                    tournament.participants.push(Participant { name: fields[1].to_string(), engine });
+                }
+                "game" if fields.len() == 6 => tournament.pairings.push(Pairing {
+                    round: index(1)? as u32,
+                    white: index(2)?,
+                    black: index(3)?,
+                    opening: index(4)?,
+                    result: Outcome::from_pgn(fields[5]).ok_or_else(invalid).map(|outcome| if outcome == Outcome::Unfinished { None } else { Some(outcome) })?,
+                }),
+                "bye" if fields.len() == 3 => tournament.byes.push((index(1)? as u32, index(2)?)),
+                _ => return Err(invalid()),
+            }
+        }
+        let players = tournament.participants.len();
+        if tournament.pairings.iter().any(|pairing| pairing.white >= players || pairing.black >= players) || tournament.byes.iter().any(|(_, player)| *player >= players) {
+            return Err("a game refers to a participant that is not listed".to_string());
+        }
+        Ok(tournament)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let temporary = format!("{}.tmp", path);
+        std::fs::write(&temporary, self.to_text()).and_then(|_| std::fs::rename(&temporary, path)).map_err(|error| format!("cannot write {}: {}", path, error))
+    }
+
+    pub fn load(path: &str) -> Result<Tournament, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+        Tournament::from_text(&text)
+    }
+}
+
+// Pairs the players top down in their order, each with the first opponent the rule allows,
+// going back when the rest cannot be paired
+fn pair_in_order(players: &[usize], allowed: &dyn Fn(usize, usize) -> bool) -> Option<Vec<(usize, usize)>> {
+    if players.is_empty() {
+        return Some(Vec::new());
+    }
+    let first = players[0];
+    for position in 1..players.len() {
+        if !allowed(first, players[position]) {
+            continue;
+        }
+        let rest: Vec<usize> = players[1..].iter().enumerate().filter(|(index, _)| index + 1 != position).map(|(_, player)| *player).collect();
+        if let Some(mut pairs) = pair_in_order(&rest, allowed) {
+            pairs.insert(0, (first, players[position]));
+            return Some(pairs);
+        }
+    }
+    None
+}
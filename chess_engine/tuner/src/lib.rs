@@ -0,0 +1,109 @@
+use chess_logic::*;
+
+use std::fs;
+
+/// A position and the result of its game for white: 1, 0.5 or 0. Blank lines and comments give None.
+///```
+/// let (game, result) = tuner::parse_position("8/8/8/8/8/8/8/k6K w - - c9 \"1-0\";").unwrap().unwrap();
+/// assert!(game.is_whites_turn());
+/// assert_eq!(result, 1.0);
+/// let (_, result) = tuner::parse_position("8/8/8/8/8/8/8/k6K b - - 0 40 [0.5]").unwrap().unwrap();
+/// assert_eq!(result, 0.5);
+/// assert_eq!(tuner::parse_position("8/8/8/8/8/8/8/k6K w - - 0-1").unwrap().unwrap().1, 0.0);
+/// assert!(tuner::parse_position("# comment").unwrap().is_none());
+/// assert!(tuner::parse_position("8/8/8/8/8/8/8/k6K w - - 0 1").is_err());
+/// assert!(tuner::parse_position("8/8/8/8/8/8/8/k6K w - - [1.5]").is_err());
+///```
+pub fn parse_position(line: &str) -> Result<Option<(GAME, f64)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 4 {
+        return Err("too few fields for a position".to_string());
+    }
+    // Four EPD fields, then the move counters if they are there
+    let mut fen_length = 4;
+    while fen_length < fields.len().min(6) && fields[fen_length].parse::<u32>().is_ok() {
+        fen_length += 1;
+    }
+    let game = init_game_from_fen(&fields[..fen_length].join(" "))?;
+
+    let rest = fields[fen_length..].join(" ");
+    let label = match (rest.find('['), rest.find(']')) {
+        (Some(open), Some(close)) if open < close => rest[open + 1..close].trim().to_string(),
+        _ => rest.split(|symbol: char| symbol.is_whitespace() || symbol == ';' || symbol == '"')
+            .find(|token| Outcome::from_pgn(token).is_some())
+            .unwrap_or("")
+            .to_string(),
+    };
+    let result = match Outcome::from_pgn(&label) {
+        Some(Outcome::WhiteWins) => 1.0,
+        Some(Outcome::BlackWins) => 0.0,
+        Some(Outcome::Draw) => 0.5,
+        _ => match label.parse::<f64>() {
+            Ok(result) if (0.0..=1.0).contains(&result) => result,
+            _ => return Err(format!("no game result in '{}'", rest)),
+        },
+    };
+    Ok(Some((game, result)))
+}
+
+/// Saves the weights as NAME = v, v, ... for every group, after a header with k, the pass and the error.
+/// The file is written under another name first so an interrupted write leaves the last checkpoint intact.
+///```
+/// use chess_logic::*;
+/// let path = std::env::temp_dir().join("tuner_checkpoint_doctest.txt");
+/// let path = path.to_str().unwrap();
+/// let mut params = EvalParams::default();
+/// let mut values = params.values();
+/// values[0] += 7;
+/// *values.last_mut().unwrap() -= 3;
+/// params.set_values(&values);
+/// tuner::write_checkpoint(path, &params, 1.25, 4, 0.0625).unwrap();
+///
+/// let mut resumed = EvalParams::default();
+/// assert_eq!(tuner::read_checkpoint(path, &mut resumed), Ok((Some(1.25), 4)));
+/// assert_eq!(resumed.values(), values);
+///
+/// std::fs::write(path, "k = 1.25\nMIDDLEGAME_VALUES = 1, 2\n").unwrap();
+/// assert!(tuner::read_checkpoint(path, &mut resumed).is_err());
+/// std::fs::remove_file(path).unwrap();
+///```
+pub fn write_checkpoint(path: &str, params: &EvalParams, k: f64, pass: usize, error: f64) -> std::io::Result<()> {
+    let mut text = format!("k = {}\npass = {}\n# error {:.8}\n", k, pass, error);
+    for (name, group) in params.groups() {
+        let values: Vec<String> = group.iter().map(|value| value.to_string()).collect();
+        text += &format!("{} = {}\n", name, values.join(", "));
+    }
+    let temporary = format!("{}.tmp", path);
+    fs::write(&temporary, text)?;
+    fs::rename(&temporary, path)
+}
+
+/// Reads a checkpoint of write_checkpoint into params, giving back its k and pass.
+pub fn read_checkpoint(path: &str, params: &mut EvalParams) -> Result<(Option<f64>, usize), String> {
+    let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let mut k = None;
+    let mut pass = 0;
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let (name, value) = line.split_once('=').ok_or_else(|| format!("expected NAME = values: {}", line))?;
+        let (name, value) = (name.trim(), value.trim());
+        match name {
+            "k" => k = Some(value.parse().map_err(|_| format!("invalid k: {}", value))?),
+            "pass" => pass = value.parse().map_err(|_| format!("invalid pass: {}", value))?,
+            _ => {
+                let mut groups = params.groups_mut();
+                let (_, group) = groups.iter_mut().find(|(group_name, _)| *group_name == name).ok_or_else(|| format!("unknown weights {}", name))?;
+                let values: Vec<i32> = value.split(',').map(|value| value.trim().parse::<i32>()).collect::<Result<_, _>>().map_err(|_| format!("invalid weights for {}", name))?;
+                if values.len() != group.len() {
+                    return Err(format!("{} needs {} weights, found {}", name, group.len(), values.len()));
+                }
+                group.copy_from_slice(&values);
+            }
+        }
+    }
+    Ok((k, pass))
+}
+
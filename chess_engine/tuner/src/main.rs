@@ -0,0 +1,221 @@
+use chess_logic::*;
+use tuner::*;
+
+use std::fs;
+use std::time::Instant;
+
+// Texel tuning: find the weights for which the static evaluation, mapped through a sigmoid,
+// best predicts the results of the games the positions were taken from.
+//
+// tuner <positions> [--threads n] [--passes n] [--step n] [--k value]
+//       [--resume file] [--checkpoint file] [--output file.rs]
+//
+// Each line of the positions file is a FEN or EPD followed by the game result, written as
+// c9 "1-0";  or  [1.0]  or a bare 1-0 / 0-1 / 1/2-1/2. Results are from white's side.
+
+struct Options {
+    positions: String,
+    threads: usize,
+    passes: usize,
+    step: i32,
+    k: Option<f64>,
+    resume: Option<String>,
+    checkpoint: String,
+    output: String,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let options = match parse_options(&args[1..]) {
+        Ok(options) => options,
+        Err(error) => {
+            println!("{}", error);
+            println!("usage: tuner <positions> [--threads n] [--passes n] [--step n] [--k value] [--resume file] [--checkpoint file] [--output file.rs]");
+            return;
+        }
+    };
+
+    let text = match fs::read_to_string(&options.positions) {
+        Ok(text) => text,
+        Err(error) => {
+            println!("cannot read {}: {}", options.positions, error);
+            return;
+        }
+    };
+    let mut positions = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        match parse_position(line) {
+            Ok(Some(position)) => positions.push(position),
+            Ok(None) => {}
+            Err(error) => println!("line {}: {}", number + 1, error),
+        }
+    }
+    if positions.is_empty() {
+        println!("no labeled positions in {}", options.positions);
+        return;
+    }
+    println!("{} positions, {} threads", positions.len(), options.threads);
+
+    let mut params = EvalParams::default();
+    let mut first_pass = 1;
+    let mut k = options.k;
+    if let Some(path) = options.resume.as_ref() {
+        match read_checkpoint(path, &mut params) {
+            Ok((checkpoint_k, pass)) => {
+                k = k.or(checkpoint_k);
+                first_pass = pass + 1;
+                println!("resuming from {} after pass {}", path, pass);
+            }
+            Err(error) => {
+                println!("cannot resume from {}: {}", path, error);
+                return;
+            }
+        }
+    }
+    let k = k.unwrap_or_else(|| {
+        let k = find_k(&positions, &params, options.threads);
+        println!("k {:.4}", k);
+        k
+    });
+
+    let mut values = params.values();
+    let mut best_error = total_error(&positions, &params, k, options.threads);
+    println!("starting error {:.8}", best_error);
+
+    for pass in first_pass..first_pass + options.passes {
+        let started = Instant::now();
+        let mut improved = 0;
+        for index in 0..values.len() {
+            let original = values[index];
+            let mut changed = false;
+            for delta in [options.step, -options.step] {
+                values[index] = original + delta;
+                params.set_values(&values);
+                let error = total_error(&positions, &params, k, options.threads);
+                if error < best_error {
+                    best_error = error;
+                    changed = true;
+                    break;
+                }
+            }
+            if changed {
+                improved += 1;
+            } else {
+                values[index] = original;
+            }
+        }
+        params.set_values(&values);
+        println!("pass {} error {:.8} changed {} weights in {:.1}s", pass, best_error, improved, started.elapsed().as_secs_f64());
+        if let Err(error) = write_checkpoint(&options.checkpoint, &params, k, pass, best_error) {
+            println!("cannot write checkpoint {}: {}", options.checkpoint, error);
+        }
+        if improved == 0 {
+            break;
+        }
+    }
+
+    match fs::write(&options.output, rust_source(&params)) {
+        Ok(()) => println!("tuned weights written to {}", options.output),
+        Err(error) => println!("cannot write {}: {}", options.output, error),
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        positions: String::new(),
+        threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        passes: 100,
+        step: 1,
+        k: None,
+        resume: None,
+        checkpoint: "tuning.checkpoint".to_string(),
+        output: "tuned_weights.rs".to_string(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            options.positions = arg.clone();
+            continue;
+        }
+        let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+        let invalid = |_| format!("invalid value for {}: {}", arg, value);
+        match arg.as_str() {
+            "--threads" => options.threads = value.parse::<usize>().map_err(invalid)?.max(1),
+            "--passes" => options.passes = value.parse().map_err(invalid)?,
+            "--step" => options.step = value.parse::<i32>().map_err(invalid)?.max(1),
+            "--k" => options.k = Some(value.parse::<f64>().map_err(|_| format!("invalid value for --k: {}", value))?),
+            "--resume" => options.resume = Some(value.clone()),
+            "--checkpoint" => options.checkpoint = value.clone(),
+            "--output" => options.output = value.clone(),
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+    if options.positions.is_empty() {
+        return Err("no positions file given".to_string());
+    }
+    Ok(options)
+}
+
+fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+// Mean squared difference between the results and the sigmoid of the white-relative evaluations
+fn total_error(positions: &[(GAME, f64)], params: &EvalParams, k: f64, threads: usize) -> f64 {
+    let chunk_size = positions.len().div_ceil(threads);
+    let sum: f64 = std::thread::scope(|scope| {
+        let workers: Vec<_> = positions.chunks(chunk_size.max(1)).map(|chunk| {
+            scope.spawn(move || {
+                chunk.iter().map(|(game, result)| {
+                    let predicted = sigmoid(evaluate_trace_with(game, params).score as f64, k);
+                    (result - predicted) * (result - predicted)
+                }).sum::<f64>()
+            })
+        }).collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).sum()
+    });
+    sum / positions.len() as f64
+}
+
+// The sigmoid scale that fits the starting weights best, narrowed down one decimal at a time
+fn find_k(positions: &[(GAME, f64)], params: &EvalParams, threads: usize) -> f64 {
+    let mut best = (1.0, total_error(positions, params, 1.0, threads));
+    let mut step = 0.1;
+    for _ in 0..4 {
+        let center = best.0;
+        for offset in -10..=10 {
+            let k = center + offset as f64 * step;
+            if k <= 0.0 {
+                continue;
+            }
+            let error = total_error(positions, params, k, threads);
+            if error < best.1 {
+                best = (k, error);
+            }
+        }
+        step /= 10.0;
+    }
+    best.0
+}
+
+// The weights as constant declarations laid out like the ones in chess_logic/src/evaluation.rs
+fn rust_source(params: &EvalParams) -> String {
+    let mut source = String::from("// Tuned weights, to replace the constants of the same name in chess_logic/src/evaluation.rs\n");
+    for (name, group) in params.groups() {
+        let visibility = if name.ends_with("_VALUES") { "pub " } else { "" };
+        if group.len() == 1 {
+            source += &format!("{}const {}: i32 = {};\n", visibility, name, group[0]);
+        } else if group.len() == 64 {
+            source += &format!("\n#[rustfmt::skip]\n{}const {}: [i32; 64] = [\n", visibility, name);
+            for row in group.chunks(8) {
+                let row: Vec<String> = row.iter().map(|value| format!("{:>4},", value)).collect();
+                source += &format!("   {}\n", row.join(""));
+            }
+            source += "];\n";
+        } else {
+            let values: Vec<String> = group.iter().map(|value| value.to_string()).collect();
+            source += &format!("{}const {}: [i32; {}] = [{}];\n", visibility, name, group.len(), values.join(", "));
+        }
+    }
+    source
+}
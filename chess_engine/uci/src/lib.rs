@@ -0,0 +1,595 @@
+use chess_logic::*;
+
+pub mod xboard;
+
+use std::io::BufRead;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const ENGINE_NAME: &str = "chess_engine";
+pub const ENGINE_AUTHOR: &str = "chess_engine developers";
+pub(crate) const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const MAX_HASH_MB: usize = 4096;
+const MAX_THREADS: usize = 64;
+const MAX_MULTI_PV: usize = 64;
+
+// (UCI name, getter, setter) of every switch of the search that can be turned off from a GUI
+type Switch = (&'static str, fn(&SearchOptions) -> bool, fn(&mut SearchOptions, bool));
+const SEARCH_SWITCHES: [Switch; 7] = [
+    ("MoveOrdering", |options| options.move_ordering, |options, value| options.move_ordering = value),
+    ("NullMovePruning", |options| options.null_move_pruning, |options, value| options.null_move_pruning = value),
+    ("LateMoveReductions", |options| options.late_move_reductions, |options, value| options.late_move_reductions = value),
+    ("FutilityPruning", |options| options.futility_pruning, |options, value| options.futility_pruning = value),
+    ("ReverseFutilityPruning", |options| options.reverse_futility_pruning, |options, value| options.reverse_futility_pruning = value),
+    ("CheckExtensions", |options| options.check_extensions, |options, value| options.check_extensions = value),
+    ("AspirationWindows", |options| options.aspiration_windows, |options, value| options.aspiration_windows = value),
+];
+
+/// A parsed `go` command.
+#[derive(Clone, Debug, Default)]
+pub struct GoCommand {
+    pub limits: SearchLimits,
+    pub search_moves: Vec<String>,
+    pub ponder: bool,
+    pub infinite: bool,
+}
+
+impl GoCommand {
+    ///```
+    /// use uci::GoCommand;
+    /// use std::time::Duration;
+    /// let go = GoCommand::parse("go wtime 60000 btime 55000 winc 1000 binc 1000 movestogo 20 searchmoves e2e4 d2d4");
+    /// assert_eq!(go.limits.white_time, Some(Duration::from_secs(60)));
+    /// assert_eq!(go.limits.black_increment, Some(Duration::from_secs(1)));
+    /// assert_eq!(go.limits.moves_to_go, Some(20));
+    /// assert_eq!(go.search_moves, vec!["e2e4", "d2d4"]);
+    /// assert!(!go.infinite);
+    ///```
+    pub fn parse(command: &str) -> GoCommand {
+        let mut go = GoCommand::default();
+        let tokens: Vec<&str> = command.split_whitespace().collect();
+        let milliseconds = |index: usize| tokens.get(index + 1).and_then(|value| value.parse::<i64>().ok()).map(|value| Duration::from_millis(value.max(0) as u64));
+        let number = |index: usize| tokens.get(index + 1).and_then(|value| value.parse::<u64>().ok());
+
+        let mut index = 1;
+        while index < tokens.len() {
+            match tokens[index] {
+                "wtime" => go.limits.white_time = milliseconds(index),
+                "btime" => go.limits.black_time = milliseconds(index),
+                "winc" => go.limits.white_increment = milliseconds(index),
+                "binc" => go.limits.black_increment = milliseconds(index),
+                "movetime" => go.limits.move_time = milliseconds(index),
+                "movestogo" => go.limits.moves_to_go = number(index).map(|moves| moves as u32),
+                "depth" => go.limits.depth = number(index).map(|depth| depth.min(u8::MAX as u64) as u8),
+                "mate" => go.limits.mate = number(index).map(|moves| moves.min(u8::MAX as u64) as u8),
+                "nodes" => go.limits.nodes = number(index),
+                "ponder" => go.ponder = true,
+                "infinite" => go.infinite = true,
+                "searchmoves" => {
+                    // Moves run until the next keyword
+                    while tokens.get(index + 1).is_some_and(|token| token.len() >= 4 && token.as_bytes()[1].is_ascii_digit()) {
+                        index += 1;
+                        go.search_moves.push(tokens[index].to_string());
+                    }
+                    index += 1;
+                    continue;
+                }
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            }
+            index += if matches!(tokens[index], "ponder" | "infinite") { 1 } else { 2 };
+        }
+        go
+    }
+}
+
+/// Reads the position of a `position startpos|fen <fen> [moves ...]` command.
+///```
+/// use chess_logic::*;
+/// let game = uci::parse_position("position startpos moves e2e4 e7e5 g1f3").unwrap();
+/// assert!(!game.is_whites_turn());
+/// assert!(uci::parse_position("position fen 8/8/8/8/8/8/8/k6K w - - 0 1 moves h1g1").is_ok());
+/// assert!(uci::parse_position("position startpos moves e2e5").is_err());
+///```
+pub fn parse_position(command: &str) -> Result<GAME, String> {
+    let tokens: Vec<&str> = command.split_whitespace().collect();
+    let moves_index = tokens.iter().position(|token| *token == "moves").unwrap_or(tokens.len());
+    let mut game = match tokens.get(1) {
+        Some(&"startpos") => init_game_from_fen(STARTPOS)?,
+        Some(&"fen") => init_game_from_fen(&tokens[2..moves_index].join(" "))?,
+        _ => return Err("expected startpos or fen".to_string()),
+    };
+    for notation in tokens.iter().skip(moves_index + 1) {
+        match move_from_uci(&game, notation) {
+            Some(played) => {
+                make_move(&mut game, played);
+            }
+            None => return Err(format!("illegal move {}", notation)),
+        }
+    }
+    Ok(game)
+}
+
+/// The `info` lines for one finished iteration, one per MultiPV line.
+pub fn info_lines(result: &SearchResult, elapsed: Duration) -> Vec<String> {
+    let milliseconds = elapsed.as_millis() as u64;
+    let nodes_per_second = result.nodes * 1000 / milliseconds.max(1);
+    result.lines.iter().enumerate().map(|(index, line)| {
+        format!(
+            "info depth {} multipv {} score {} nodes {} nps {} time {} pv {}",
+            line.depth, index + 1, line.score(), result.nodes, nodes_per_second, milliseconds, line.uci().join(" ")
+        )
+    }).collect()
+}
+
+fn best_move_line(result: &SearchResult) -> String {
+    match result.best_move {
+        Some(best_move) => match result.pv.get(1) {
+            Some(ponder_move) if result.pv[0] == best_move => format!("bestmove {} ponder {}", best_move.to_uci(), ponder_move.to_uci()),
+            _ => format!("bestmove {}", best_move.to_uci()),
+        },
+        None => "bestmove 0000".to_string(),                    // no legal moves
+    }
+}
+
+/// Engine side of the protocol. Every line it sends goes to the output channel,
+/// searches run on their own thread so that `stop` and `isready` are answered at once.
+/// # Scripted session
+///```
+/// use std::sync::mpsc::channel;
+/// let transcript = "uci\nisready\nposition startpos moves e2e4\ngo depth 3\nisready\nucinewgame\nquit\n";
+/// let (sender, receiver) = channel();
+/// uci::run(transcript.as_bytes(), sender);
+/// let output: Vec<String> = receiver.try_iter().collect();
+/// assert!(output.contains(&"uciok".to_string()));
+/// assert_eq!(output.iter().filter(|line| *line == "readyok").count(), 2);
+/// assert!(output.iter().any(|line| line.starts_with("info depth 3 multipv 1 score cp")));
+/// assert!(output.last().unwrap().starts_with("bestmove "));
+///```
+/// # Playing from an opening book
+///```
+/// use std::sync::mpsc::channel;
+/// // A Polyglot book with d2d4 as the only move of the starting position
+/// let mut entry = 0x463b96181691fc9cu64.to_be_bytes().to_vec();
+/// entry.extend_from_slice(&[0x02, 0xdb, 0, 1, 0, 0, 0, 0]);
+/// let path = std::env::temp_dir().join("uci_own_book_doctest.bin");
+/// std::fs::write(&path, entry).unwrap();
+/// let transcript = format!("setoption name OwnBook value true\nsetoption name BookFile value {}\nposition startpos\ngo depth 1\n", path.display());
+/// let (sender, receiver) = channel();
+/// uci::run(transcript.as_bytes(), sender);
+/// let output: Vec<String> = receiver.try_iter().collect();
+/// assert_eq!(output, ["bestmove d2d4"]);
+///```
+/// # Syzygy tables
+///```
+/// use std::sync::mpsc::channel;
+/// // 69 plies on the clock, only the fastest mate in 16 beats the fifty move rule
+/// let transcript = "setoption name SyzygyPath value ../chess_logic/testdata/syzygy\n\
+///     setoption name MultiPV value 16\n\
+///     position fen K7/1R6/2k5/8/8/8/8/8 w - - 69 80\n\
+///     go depth 1\n";
+/// let (sender, receiver) = channel();
+/// uci::run(transcript.as_bytes(), sender);
+/// let output: Vec<String> = receiver.try_iter().collect();
+/// assert_eq!(output[0], "info string tables for up to 3 pieces");
+/// let lines: Vec<&String> = output.iter().filter(|line| line.starts_with("info depth 1 ")).collect();
+/// assert_eq!(lines.len(), 8);                                 // of 16 legal moves
+/// for line in lines {
+///     let first_move = line.split(" pv ").nth(1).unwrap().split_whitespace().next().unwrap();
+///     assert!(["a8a7", "a8b8", "b7b8", "b7b2", "b7b1", "b7a7", "b7g7", "b7h7"].contains(&first_move));
+/// }
+///```
+/// # Pondering
+///```
+/// use std::sync::mpsc::channel;
+/// // The ponder search becomes the real one on ponderhit and is not started again
+/// let transcript = "position startpos moves e2e4 e7e5\ngo ponder depth 4 wtime 60000 btime 60000\nponderhit\nisready\nposition startpos\n";
+/// let (sender, receiver) = channel();
+/// uci::run(transcript.as_bytes(), sender);
+/// let output: Vec<String> = receiver.try_iter().collect();
+/// assert_eq!(output.iter().filter(|line| line.starts_with("info depth 1 ")).count(), 1);
+/// assert!(output.iter().any(|line| line.starts_with("info depth 4 ")));
+/// assert_eq!(output.iter().filter(|line| line.starts_with("bestmove")).count(), 1);
+///```
+pub struct UciEngine {
+    output: Sender<String>,
+    game: GAME,
+    options: SearchOptions,
+    searcher: Option<Searcher>,                                 // lent to the search thread while it runs
+    search: Option<JoinHandle<Searcher>>,
+    stop: Arc<AtomicBool>,
+    ponder_hit: Arc<AtomicBool>,
+    hold_best_move: Arc<AtomicBool>,                            // infinite and ponder searches wait for stop or ponderhit
+    own_book: bool,
+    book: Option<OpeningBook>,
+    tablebase: Option<Tablebase>,
+    endgame_tables: Option<EndgameTables>,
+    skill_level: u8,
+    limit_strength: bool,                                       // UCI_Elo instead of the skill level decides
+    elo: u32,
+    #[cfg(feature = "nnue")]
+    network: Option<Arc<Network>>,
+    #[cfg(feature = "nnue")]
+    use_network: bool,
+}
+
+impl UciEngine {
+    pub fn new(output: Sender<String>) -> UciEngine {
+        let options = SearchOptions::default();
+        let searcher = Searcher::new(options);
+        UciEngine {
+            output,
+            game: init_game_from_fen(STARTPOS).unwrap(),
+            options,
+            stop: searcher.stop_handle(),
+            ponder_hit: searcher.ponder_hit_handle(),
+            searcher: Some(searcher),
+            search: None,
+            hold_best_move: Arc::new(AtomicBool::new(false)),
+            own_book: false,
+            book: None,
+            tablebase: None,
+            endgame_tables: None,
+            skill_level: Skill::MAX_LEVEL,
+            limit_strength: false,
+            elo: Skill::MAX_ELO,
+            #[cfg(feature = "nnue")]
+            network: None,
+            #[cfg(feature = "nnue")]
+            use_network: false,
+        }
+    }
+
+    fn send(&self, line: String) {
+        let _ = self.output.send(line);                         // nobody listening any more is not an error here
+    }
+
+    /// Handles one line of input, returns false once the engine should exit.
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let command = line.split_whitespace().next().unwrap_or("");
+        match command {
+            "uci" => {
+                self.send(format!("id name {}", ENGINE_NAME));
+                self.send(format!("id author {}", ENGINE_AUTHOR));
+                self.send(format!("option name Hash type spin default {} min 1 max {}", self.options.hash_size_mb, MAX_HASH_MB));
+                self.send(format!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS));
+                self.send(format!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTI_PV));
+                self.send("option name Ponder type check default false".to_string());
+                self.send("option name Clear Hash type button".to_string());
+                self.send("option name OwnBook type check default false".to_string());
+                self.send("option name BookFile type string default <empty>".to_string());
+                self.send("option name SyzygyPath type string default <empty>".to_string());
+                self.send("option name EndgameTablePath type string default <empty>".to_string());
+                self.send(format!("option name Skill Level type spin default {} min 0 max {}", Skill::MAX_LEVEL, Skill::MAX_LEVEL));
+                self.send("option name UCI_LimitStrength type check default false".to_string());
+                self.send(format!("option name UCI_Elo type spin default {} min {} max {}", Skill::MAX_ELO, Skill::MIN_ELO, Skill::MAX_ELO));
+                #[cfg(feature = "nnue")]
+                {
+                    self.send("option name EvalFile type string default <empty>".to_string());
+                    self.send("option name UseNNUE type check default false".to_string());
+                }
+                for (name, get, _) in SEARCH_SWITCHES.iter() {
+                    self.send(format!("option name {} type check default {}", name, get(&self.options)));
+                }
+                self.send("uciok".to_string());
+            }
+            "isready" => self.send("readyok".to_string()),
+            "ucinewgame" => {
+                self.wait_for_search();
+                self.searcher_mut().clear();
+                self.game = init_game_from_fen(STARTPOS).unwrap();
+            }
+            "position" => {
+                self.wait_for_search();
+                match parse_position(line) {
+                    Ok(game) => self.game = game,
+                    Err(error) => self.send(format!("info string {}", error)),
+                }
+            }
+            "go" => {
+                self.wait_for_search();
+                let go = GoCommand::parse(line);
+                if let Some(book_move) = self.book_move(&go) {
+                    self.send(format!("bestmove {}", book_move.to_uci()));
+                } else if let Some((table_move, dtm)) = self.table_move(&go) {
+                    self.send(format!("info depth 1 score {} pv {}", dtm.score(), table_move.to_uci()));
+                    self.send(format!("bestmove {}", table_move.to_uci()));
+                } else {
+                    self.start_search(go);
+                }
+            }
+            "stop" => {
+                self.stop.store(true, Ordering::Relaxed);
+                self.wait_for_search();
+            }
+            "ponderhit" => self.ponder_hit(),
+            "setoption" => {
+                self.wait_for_search();
+                self.set_option(line);
+            }
+            "quit" => {
+                self.stop.store(true, Ordering::Relaxed);
+                self.wait_for_search();
+                return false;
+            }
+            "" => {}
+            _ => self.send(format!("info string unknown command {}", command)),
+        }
+        true
+    }
+
+    fn searcher_mut(&mut self) -> &mut Searcher {
+        self.searcher.as_mut().expect("the searcher is home while no search runs")
+    }
+
+    // A weighted pick from the book for timed searches, analysis always searches
+    fn book_move(&self, go: &GoCommand) -> Option<Move> {
+        if !self.own_book || go.ponder || go.infinite || !go.search_moves.is_empty() {
+            return None;
+        }
+        let random = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_nanos() as u64);
+        self.book.as_ref()?.weighted_move(&self.game, random)
+    }
+
+    // The best move of a position the endgame tables know, under the same conditions as the book
+    fn table_move(&self, go: &GoCommand) -> Option<(Move, Dtm)> {
+        if go.ponder || go.infinite || !go.search_moves.is_empty() {
+            return None;
+        }
+        self.endgame_tables.as_ref()?.best_move(&self.game)
+    }
+
+    fn start_search(&mut self, go: GoCommand) {
+        let mut searcher = self.searcher.take().expect("the searcher is home while no search runs");
+        let mut search_moves: Vec<Move> = go.search_moves.iter().filter_map(|notation| move_from_uci(&self.game, notation)).collect();
+        // With tables for the position only the moves that keep its result are searched
+        if search_moves.is_empty() {
+            if let Some(Ok(root_moves)) = self.tablebase.as_ref().map(|tablebase| tablebase.root_moves(&self.game)) {
+                search_moves = root_moves;
+            }
+        }
+        searcher.set_search_moves(&search_moves);
+
+        let start = Instant::now();
+        let info_output = self.output.clone();
+        searcher.set_info_handler(Box::new(move |result| {
+            for line in info_lines(result, start.elapsed()) {
+                let _ = info_output.send(line);
+            }
+        }));
+
+        // An infinite search runs until told otherwise, a ponder search keeps its clock for the ponderhit
+        let limits = if go.infinite {
+            SearchLimits { depth: go.limits.depth, nodes: go.limits.nodes, mate: go.limits.mate, ..Default::default() }
+        } else {
+            go.limits
+        };
+        self.stop.store(false, Ordering::Relaxed);
+        self.ponder_hit.store(false, Ordering::Relaxed);
+        self.hold_best_move.store(go.ponder || go.infinite, Ordering::Relaxed);
+
+        let game = self.game.clone();
+        let output = self.output.clone();
+        let stop = self.stop.clone();
+        let hold_best_move = self.hold_best_move.clone();
+        let ponder = go.ponder;
+        self.search = Some(thread::spawn(move || {
+            let result = if ponder { searcher.ponder(&game, limits) } else { searcher.search(&game, limits) };
+            while hold_best_move.load(Ordering::Relaxed) && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let _ = output.send(best_move_line(&result));
+            searcher
+        }));
+    }
+
+    // Finished searches are collected, ones that would wait for stop forever are stopped first
+    fn wait_for_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            if self.hold_best_move.load(Ordering::Relaxed) && !self.stop.load(Ordering::Relaxed) {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+            self.searcher = Some(search.join().expect("search thread panicked"));
+        }
+    }
+
+    // The predicted move was played: the ponder search goes on as a timed search with the clock
+    // starting now, and a search that already finished answers at once
+    fn ponder_hit(&mut self) {
+        self.ponder_hit.store(true, Ordering::Relaxed);
+        self.hold_best_move.store(false, Ordering::Relaxed);
+    }
+
+    fn set_option(&mut self, line: &str) {
+        let after_name = match line.find("name ") {
+            Some(index) => &line[index + 5..],
+            None => return,
+        };
+        let (name, value) = match after_name.find(" value ") {
+            Some(index) => (after_name[..index].trim(), after_name[index + 7..].trim()),
+            None => (after_name.trim(), ""),
+        };
+        let number = value.parse::<usize>().ok();
+
+        match name.to_lowercase().as_str() {
+            "hash" => {
+                if let Some(size_mb) = number {
+                    self.options.hash_size_mb = size_mb.clamp(1, MAX_HASH_MB);
+                    self.replace_searcher();
+                }
+            }
+            "threads" => {
+                if let Some(threads) = number {
+                    self.options.threads = threads.clamp(1, MAX_THREADS);
+                    self.searcher_mut().options = self.options;
+                }
+            }
+            "multipv" => {
+                if let Some(lines) = number {
+                    self.options.multi_pv = lines.clamp(1, MAX_MULTI_PV);
+                    self.searcher_mut().options = self.options;
+                }
+            }
+            "clear hash" => self.searcher_mut().clear(),
+            "skill level" => {
+                if let Some(level) = number {
+                    self.skill_level = level.min(Skill::MAX_LEVEL as usize) as u8;
+                    self.update_skill();
+                }
+            }
+            "uci_limitstrength" => {
+                self.limit_strength = value.eq_ignore_ascii_case("true");
+                self.update_skill();
+            }
+            "uci_elo" => {
+                if let Some(elo) = number {
+                    self.elo = (elo as u32).clamp(Skill::MIN_ELO, Skill::MAX_ELO);
+                    self.update_skill();
+                }
+            }
+            "ownbook" => self.own_book = value.eq_ignore_ascii_case("true"),
+            "bookfile" => {
+                self.book = None;
+                if !value.is_empty() && value != "<empty>" {
+                    match OpeningBook::open(value) {
+                        Ok(book) => self.book = Some(book),
+                        Err(error) => self.send(format!("info string {}", error)),
+                    }
+                }
+            }
+            "syzygypath" => {
+                self.tablebase = None;
+                if !value.is_empty() && value != "<empty>" {
+                    match Tablebase::open(value) {
+                        Ok(tablebase) => {
+                            self.send(format!("info string tables for up to {} pieces", tablebase.max_pieces()));
+                            self.tablebase = Some(tablebase);
+                        }
+                        Err(error) => self.send(format!("info string {}", error)),
+                    }
+                }
+            }
+            "endgametablepath" => {
+                self.endgame_tables = None;
+                if !value.is_empty() && value != "<empty>" {
+                    match EndgameTables::open(value) {
+                        Ok(tables) => {
+                            self.send(format!("info string endgame tables {}", tables.signatures().join(" ")));
+                            self.endgame_tables = Some(tables);
+                        }
+                        Err(error) => self.send(format!("info string {}", error)),
+                    }
+                }
+            }
+            #[cfg(feature = "nnue")]
+            "evalfile" => {
+                self.network = None;
+                if !value.is_empty() && value != "<empty>" {
+                    match Network::open(value) {
+                        Ok(network) => {
+                            self.send(format!("info string network with {} hidden units", network.hidden_size()));
+                            self.network = Some(Arc::new(network));
+                        }
+                        Err(error) => self.send(format!("info string {}", error)),
+                    }
+                }
+                self.apply_network();
+            }
+            #[cfg(feature = "nnue")]
+            "usennue" => {
+                self.use_network = value.eq_ignore_ascii_case("true");
+                if self.use_network && self.network.is_none() {
+                    self.send("info string no network loaded, set EvalFile first".to_string());
+                }
+                self.apply_network();
+            }
+            "ponder" => {}                                      // pondering is driven by the GUI, nothing to set up
+            lowercase_name => {
+                match SEARCH_SWITCHES.iter().find(|(switch, _, _)| switch.to_lowercase() == lowercase_name) {
+                    Some((_, _, set)) => {
+                        set(&mut self.options, value.eq_ignore_ascii_case("true"));
+                        self.searcher_mut().options = self.options;
+                    }
+                    None => self.send(format!("info string unknown option {}", name)),
+                }
+            }
+        }
+    }
+
+    fn update_skill(&mut self) {
+        self.options.skill = if self.limit_strength { Skill::from_elo(self.elo) } else { Skill::new(self.skill_level) };
+        self.searcher_mut().options = self.options;
+    }
+
+    // A new table size needs a new searcher, the stop flag moves with it
+    fn replace_searcher(&mut self) {
+        let searcher = Searcher::new(self.options);
+        self.stop = searcher.stop_handle();
+        self.ponder_hit = searcher.ponder_hit_handle();
+        self.searcher = Some(searcher);
+        #[cfg(feature = "nnue")]
+        self.apply_network();
+    }
+
+    // The handcrafted evaluation is used until UseNNUE is set and a network is loaded
+    #[cfg(feature = "nnue")]
+    fn apply_network(&mut self) {
+        let network = if self.use_network { self.network.clone() } else { None };
+        self.searcher_mut().set_network(network);
+    }
+}
+
+/// Runs a session until `quit` or the end of the input. A first command of `xboard`
+/// switches to the CECP protocol, anything else is taken as UCI. At the end of the input a
+/// search with limits still runs to its end, only infinite and ponder searches are stopped.
+///```
+/// use std::sync::mpsc::channel;
+/// let transcript = "setoption name Threads value 2\n\
+///     position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\n\
+///     go mate 1\n\
+///     go infinite\n\
+///     stop\n";
+/// let (sender, receiver) = channel();
+/// uci::run(transcript.as_bytes(), sender);
+/// let best_moves: Vec<String> = receiver.try_iter().filter(|line| line.starts_with("bestmove")).collect();
+/// assert_eq!(best_moves.len(), 2);
+/// assert_eq!(best_moves[0], "bestmove a1a8");
+///```
+/// # A transcript without quit
+///```
+/// use std::sync::mpsc::channel;
+/// let (sender, receiver) = channel();
+/// uci::run("position startpos\ngo depth 5\n".as_bytes(), sender);
+/// let output: Vec<String> = receiver.try_iter().collect();
+/// assert!(output.iter().any(|line| line.starts_with("info depth 5 ")));
+/// assert!(output.last().unwrap().starts_with("bestmove "));
+///```
+pub fn run<R: BufRead>(input: R, output: Sender<String>) {
+    let mut lines = input.lines().map_while(Result::ok).map(|line| line.trim().to_string()).filter(|line| !line.is_empty());
+    let first_line = match lines.next() {
+        Some(line) => line,
+        None => return,
+    };
+    if first_line == "xboard" {
+        let mut engine = xboard::XboardEngine::new(output);
+        for line in std::iter::once(first_line).chain(lines) {
+            if !engine.handle_command(&line) {
+                return;
+            }
+        }
+        engine.finish_search();
+    } else {
+        let mut engine = UciEngine::new(output);
+        for line in std::iter::once(first_line).chain(lines) {
+            if !engine.handle_command(&line) {
+                return;
+            }
+        }
+        engine.wait_for_search();
+    }
+}
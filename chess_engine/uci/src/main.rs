@@ -0,0 +1,20 @@
+use std::io::{self, Write};
+use std::sync::mpsc::channel;
+use std::thread;
+
+fn main() {
+    let (sender, receiver) = channel::<String>();
+    // A single writer keeps lines from the search thread and the command loop whole
+    let printer = thread::spawn(move || {
+        let stdout = io::stdout();
+        for line in receiver {
+            let mut stdout = stdout.lock();
+            let _ = writeln!(stdout, "{}", line);
+            let _ = stdout.flush();
+        }
+    });
+
+    let stdin = io::stdin();
+    uci::run(stdin.lock(), sender);
+    printer.join().unwrap();
+}
@@ -0,0 +1,312 @@
+use chess_logic::*;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{ENGINE_NAME, STARTPOS};
+
+const XBOARD_MATE_SCORE: i32 = 100000;                          // mate in n is reported as 100000 + n
+const DEFAULT_MOVE_TIME: Duration = Duration::from_secs(5);     // used until the GUI sends a time control
+
+/// One line of CECP thinking output: `ply score time nodes pv`, time in centiseconds.
+///```
+/// use chess_logic::*;
+/// let game = init_game_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+/// let result = Searcher::new(SearchOptions::default()).search(&game, SearchLimits { depth: Some(2), ..Default::default() });
+/// let line = uci::xboard::post_line(&game, &result, std::time::Duration::from_millis(250));
+/// assert!(line.starts_with("1 100001 25 "));
+/// assert!(line.ends_with(" Ra8#"));
+///```
+pub fn post_line(game: &GAME, result: &SearchResult, elapsed: Duration) -> String {
+    let score = match Score::from_search(result.score) {
+        Score::Centipawns(centipawns) => centipawns,
+        Score::Mate(moves) if moves > 0 => XBOARD_MATE_SCORE + moves,
+        Score::Mate(moves) => -XBOARD_MATE_SCORE + moves,
+    };
+    format!("{} {} {} {} {}", result.depth, score, elapsed.as_millis() / 10, result.nodes, pv_to_san(game, &result.pv).join(" "))
+}
+
+// The result command of a finished game, or None while it goes on. Draws by the fifty-move
+// rule, repetition and insufficient material are claimed like mates and stalemates
+fn game_result(game: &GAME) -> Option<String> {
+    let (outcome, reason) = game_outcome(game)?;
+    let comment = match outcome {
+        Outcome::WhiteWins => "White mates".to_string(),
+        Outcome::BlackWins => "Black mates".to_string(),
+        _ => reason[..1].to_uppercase() + &reason[1..],
+    };
+    Some(format!("{} {{{}}}", outcome.to_pgn(), comment))
+}
+
+/// Engine side of the Chess Engine Communication Protocol (xboard, protocol version 2).
+/// # Scripted session
+///```
+/// use std::sync::mpsc::channel;
+/// let transcript = "xboard\nprotover 2\nnew\nsd 3\npost\nusermove e2e4\nping 1\nquit\n";
+/// let (sender, receiver) = channel();
+/// uci::run(transcript.as_bytes(), sender);
+/// let output: Vec<String> = receiver.try_iter().collect();
+/// assert!(output[0].starts_with("feature "));
+/// assert!(output.iter().any(|line| line.starts_with("3 ")));
+/// assert!(output.iter().any(|line| line.starts_with("move ")));
+/// assert_eq!(output.last().unwrap(), "pong 1");
+///```
+/// # Interrupted thinking
+///```
+/// use chess_logic::*;
+/// use std::sync::mpsc::channel;
+/// // force stops the search without a move, so undo takes back 1...e5 and black is to move
+/// let transcript = "xboard\nprotover 2\nnew\nforce\ne2e4\ne7e5\nsd 60\ngo\nforce\nundo\nsd 1\ngo\n";
+/// let (sender, receiver) = channel();
+/// uci::run(transcript.as_bytes(), sender);
+/// let moves: Vec<String> = receiver.try_iter().filter(|line| line.starts_with("move ")).collect();
+/// assert_eq!(moves.len(), 1);
+/// let after_e4 = init_game_from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+/// assert!(move_from_uci(&after_e4, &moves[0][5..]).is_some());
+///```
+/// # Claiming draws
+///```
+/// use std::sync::mpsc::channel;
+/// let result = |transcript: &str| {
+///     let (sender, receiver) = channel();
+///     uci::run(transcript.as_bytes(), sender);
+///     receiver.try_iter().filter(|line| line.starts_with("1/2-1/2")).collect::<Vec<String>>()
+/// };
+/// // The engine's rook move is the hundredth half move without a capture or a pawn move
+/// assert_eq!(result("xboard\nprotover 2\nsetboard 4k3/8/8/8/8/8/R7/4K3 w - - 99 80\nsd 1\ngo\n"), ["1/2-1/2 {Fifty-move rule}"]);
+/// assert_eq!(result("xboard\nprotover 2\nsetboard 8/8/4k3/8/8/3NK3/8/8 w - - 0 1\ngo\n"), ["1/2-1/2 {Insufficient material}"]);
+///```
+pub struct XboardEngine {
+    output: Sender<String>,
+    game: GAME,
+    searcher: Option<Searcher>,                                 // lent to the search thread while it thinks
+    search: Option<JoinHandle<(Searcher, Option<Move>)>>,
+    stop: Arc<AtomicBool>,
+    discard: Arc<AtomicBool>,                                   // a stopped search keeps its move to itself
+    force: bool,                                                // only record moves, never think
+    engine_is_white: bool,
+    post: bool,
+    moves_per_session: u32,
+    base_time: Duration,
+    increment: Duration,
+    move_time: Option<Duration>,                                // st
+    depth: Option<u8>,                                          // sd
+    engine_time: Option<Duration>,
+    plies: u32,                                                 // played since new or setboard, for moves per session
+}
+
+impl XboardEngine {
+    pub fn new(output: Sender<String>) -> XboardEngine {
+        let searcher = Searcher::new(SearchOptions::default());
+        XboardEngine {
+            output,
+            game: init_game_from_fen(STARTPOS).unwrap(),
+            stop: searcher.stop_handle(),
+            discard: Arc::new(AtomicBool::new(false)),
+            searcher: Some(searcher),
+            search: None,
+            force: false,
+            engine_is_white: false,
+            post: false,
+            moves_per_session: 0,
+            base_time: Duration::ZERO,
+            increment: Duration::ZERO,
+            move_time: None,
+            depth: None,
+            engine_time: None,
+            plies: 0,
+        }
+    }
+
+    fn send(&self, line: String) {
+        let _ = self.output.send(line);
+    }
+
+    /// Handles one line of input, returns false once the engine should exit.
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let mut tokens = line.split_whitespace();
+        let command = tokens.next().unwrap_or("");
+        let arguments: Vec<&str> = tokens.collect();
+        let first_number = || arguments.first().and_then(|value| value.parse::<u64>().ok());
+
+        // Clock updates and moving now must not wait for the search
+        match command {
+            "?" => {
+                self.stop.store(true, Ordering::Relaxed);
+                self.finish_search();
+                return true;
+            }
+            "time" => {
+                self.engine_time = first_number().map(|centiseconds| Duration::from_millis(centiseconds * 10));
+                return true;
+            }
+            "otim" | "hard" | "easy" | "computer" | "accepted" | "rejected" | "xboard" | "random" => return true,
+            _ => {}
+        }
+        // These take the move away from the engine, a move it has not sent yet is thrown away
+        if matches!(command, "new" | "force" | "result" | "quit" | "undo" | "remove" | "setboard") {
+            self.discard.store(true, Ordering::Relaxed);
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        self.finish_search();
+
+        match command {
+            "ping" => self.send(format!("pong {}", arguments.first().unwrap_or(&""))),
+            "protover" => {
+                self.send(format!(
+                    "feature ping=1 setboard=1 usermove=1 time=1 draw=0 sigint=0 sigterm=0 reuse=1 analyze=0 colors=0 myname=\"{}\" done=1",
+                    ENGINE_NAME
+                ));
+            }
+            "new" => {
+                self.game = init_game_from_fen(STARTPOS).unwrap();
+                self.searcher_mut().clear();
+                self.force = false;
+                self.engine_is_white = false;
+                self.move_time = None;
+                self.depth = None;
+                self.plies = 0;
+            }
+            "force" | "result" => self.force = true,
+            "go" => {
+                self.force = false;
+                self.engine_is_white = self.game.is_whites_turn();
+                self.think();
+            }
+            "playother" => {
+                self.force = false;
+                self.engine_is_white = !self.game.is_whites_turn();
+            }
+            "usermove" => self.user_move(arguments.first().unwrap_or(&"")),
+            "level" => {
+                self.moves_per_session = arguments.first().and_then(|moves| moves.parse().ok()).unwrap_or(0);
+                // Base time is minutes or minutes:seconds
+                let mut base = arguments.get(1).unwrap_or(&"0").split(':').map(|part| part.parse::<u64>().unwrap_or(0));
+                let minutes = base.next().unwrap_or(0);
+                let seconds = base.next().unwrap_or(0);
+                self.base_time = Duration::from_secs(minutes * 60 + seconds);
+                self.increment = Duration::from_secs_f64(arguments.get(2).and_then(|increment| increment.parse::<f64>().ok()).unwrap_or(0.0).max(0.0));
+                self.move_time = None;
+            }
+            "st" => self.move_time = first_number().map(Duration::from_secs),
+            "sd" => self.depth = first_number().map(|depth| depth.min(u8::MAX as u64) as u8),
+            "undo" => self.take_back(1),
+            "remove" => self.take_back(2),
+            "setboard" => match init_game_from_fen(&arguments.join(" ")) {
+                Ok(game) => {
+                    self.game = game;
+                    self.plies = 0;
+                }
+                Err(error) => self.send(format!("tellusererror Illegal position: {}", error)),
+            },
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "quit" => return false,
+            // Without the usermove prefix a bare move is still understood
+            other => match move_from_uci(&self.game, other) {
+                Some(_) => self.user_move(other),
+                None => self.send(format!("Error (unknown command): {}", other)),
+            },
+        }
+        true
+    }
+
+    fn searcher_mut(&mut self) -> &mut Searcher {
+        self.searcher.as_mut().expect("the searcher is home while the engine does not think")
+    }
+
+    fn user_move(&mut self, notation: &str) {
+        match move_from_uci(&self.game, notation) {
+            Some(played) => {
+                make_move(&mut self.game, played);
+                self.plies += 1;
+                if !self.force && self.game.is_whites_turn() == self.engine_is_white {
+                    self.think();
+                }
+            }
+            None => self.send(format!("Illegal move: {}", notation)),
+        }
+    }
+
+    fn take_back(&mut self, plies: u32) {
+        for _ in 0..plies.min(self.plies) {
+            unmake_move(&mut self.game);
+            self.plies -= 1;
+        }
+    }
+
+    fn limits(&self) -> SearchLimits {
+        let mut limits = SearchLimits { depth: self.depth, ..Default::default() };
+        if let Some(move_time) = self.move_time {
+            limits.move_time = Some(move_time);
+        } else if self.depth.is_none() || self.engine_time.is_some() {
+            let time_left = self.engine_time.unwrap_or(if self.base_time > Duration::ZERO { self.base_time } else { DEFAULT_MOVE_TIME * 30 });
+            limits.white_time = Some(time_left);
+            limits.black_time = Some(time_left);
+            limits.white_increment = Some(self.increment);
+            limits.black_increment = Some(self.increment);
+            if self.moves_per_session > 0 {
+                let engine_moves_played = self.plies / 2;
+                limits.moves_to_go = Some(self.moves_per_session - engine_moves_played % self.moves_per_session);
+            }
+        }
+        limits
+    }
+
+    fn think(&mut self) {
+        if let Some(result) = game_result(&self.game) {
+            self.send(result);
+            return;
+        }
+        let mut searcher = self.searcher.take().expect("the searcher is home while the engine does not think");
+        searcher.set_search_moves(&[]);
+        if self.post {
+            let start = Instant::now();
+            let post_output = self.output.clone();
+            let post_game = self.game.clone();
+            searcher.set_info_handler(Box::new(move |result| {
+                let _ = post_output.send(post_line(&post_game, result, start.elapsed()));
+            }));
+        } else {
+            searcher.set_info_handler(Box::new(|_| {}));
+        }
+
+        let limits = self.limits();
+        self.stop.store(false, Ordering::Relaxed);
+        self.discard.store(false, Ordering::Relaxed);
+        let game = self.game.clone();
+        let output = self.output.clone();
+        let discard = self.discard.clone();
+        self.search = Some(thread::spawn(move || {
+            let result = searcher.search(&game, limits);
+            if discard.load(Ordering::Relaxed) {
+                return (searcher, None);
+            }
+            if let Some(best_move) = result.best_move {
+                let _ = output.send(format!("move {}", best_move.to_uci()));
+                let mut after = game.clone();
+                make_move(&mut after, best_move);
+                if let Some(result) = game_result(&after) {
+                    let _ = output.send(result);
+                }
+            }
+            (searcher, result.best_move)
+        }));
+    }
+
+    // The move a finished search sent is played on the engine's own board as well, a discarded
+    // search leaves the board alone
+    pub(crate) fn finish_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            let (searcher, best_move) = search.join().expect("search thread panicked");
+            self.searcher = Some(searcher);
+            if let Some(best_move) = best_move {
+                make_move(&mut self.game, best_move);
+                self.plies += 1;
+            }
+        }
+    }
+}
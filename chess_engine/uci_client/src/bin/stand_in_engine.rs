@@ -0,0 +1,81 @@
+// A tiny scripted UCI engine for testing the client. It always answers with the
+// first legal move, and its first argument picks a way to misbehave:
+//   crash-on-go     exits with status 3 when asked to search
+//   ignore-stop     never answers a search, not even after stop
+//   no-uciok        never finishes the handshake
+//   illegal-move    answers every search with a1a1
+
+use chess_logic::*;
+
+use std::io::{self, BufRead, Write};
+use std::process;
+
+fn main() {
+    let behaviour = std::env::args().nth(1).unwrap_or_default();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut game = init_game();
+    let mut holding_best_move = false;                          // searching infinite or pondering
+
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let command = line.split_whitespace().next().unwrap_or("");
+        match command {
+            "uci" if behaviour != "no-uciok" => {
+                writeln!(out, "id name Stand-in").unwrap();
+                writeln!(out, "id author nobody").unwrap();
+                writeln!(out, "option name Hash type spin default 1 min 1 max 1").unwrap();
+                writeln!(out, "uciok").unwrap();
+            }
+            "isready" => writeln!(out, "readyok").unwrap(),
+            "position" => {
+                game = position_from_command(&line).unwrap_or_else(init_game);
+            }
+            "go" => match behaviour.as_str() {
+                "crash-on-go" => process::exit(3),
+                "ignore-stop" => {}
+                _ => {
+                    writeln!(out, "info depth 1 score cp 13 nodes 20 nps 20000 time 1 pv {}", first_move(&game)).unwrap();
+                    writeln!(out, "info string thinking hard").unwrap();
+                    if line.contains("infinite") || line.contains("ponder") {
+                        holding_best_move = true;
+                    } else {
+                        writeln!(out, "bestmove {}", first_move(&game)).unwrap();
+                    }
+                }
+            },
+            "stop" | "ponderhit" if holding_best_move => {
+                holding_best_move = false;
+                writeln!(out, "bestmove {}", first_move(&game)).unwrap();
+            }
+            "quit" => break,
+            _ => {}
+        }
+        out.flush().unwrap();
+    }
+}
+
+fn first_move(game: &GAME) -> String {
+    if std::env::args().nth(1).as_deref() == Some("illegal-move") {
+        return "a1a1".to_string();
+    }
+    generate_legal_moves(game).first().map_or("0000".to_string(), |played| played.to_uci())
+}
+
+fn position_from_command(line: &str) -> Option<GAME> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let moves_index = tokens.iter().position(|token| *token == "moves").unwrap_or(tokens.len());
+    let mut game = match tokens.get(1)? {
+        &"startpos" => init_game(),
+        _ => init_game_from_fen(&tokens[2..moves_index].join(" ")).ok()?,
+    };
+    for notation in tokens.iter().skip(moves_index + 1) {
+        let played = move_from_uci(&game, notation)?;
+        make_move(&mut game, played);
+    }
+    Some(game)
+}
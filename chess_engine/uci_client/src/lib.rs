@@ -0,0 +1,376 @@
+use chess_logic::*;
+
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const STOP_GRACE: Duration = Duration::from_secs(1);            // how long bestmove may take after stop
+const QUIT_GRACE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientError {
+    Spawn(String),
+    Timeout(String),
+    Crashed(String),
+    Protocol(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::Spawn(message) => write!(f, "could not start engine: {}", message),
+            ClientError::Timeout(message) => write!(f, "engine timed out: {}", message),
+            ClientError::Crashed(message) => write!(f, "engine crashed: {}", message),
+            ClientError::Protocol(message) => write!(f, "engine protocol error: {}", message),
+        }
+    }
+}
+
+/// The fields of an `info` line the client understands, the rest are skipped.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EngineInfo {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub multipv: Option<u32>,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time_ms: Option<u64>,
+    pub pv: Vec<String>,
+    pub string: Option<String>,
+}
+
+/// Reads an engine's `info` line.
+///```
+/// use chess_logic::Score;
+/// let info = uci_client::parse_info("info depth 12 seldepth 18 multipv 1 score mate -3 nodes 12000 nps 600000 time 20 pv e2e4 e7e5").unwrap();
+/// assert_eq!(info.depth, Some(12));
+/// assert_eq!(info.score, Some(Score::Mate(-3)));
+/// assert_eq!(info.pv, vec!["e2e4", "e7e5"]);
+/// assert_eq!(uci_client::parse_info("info string hello there").unwrap().string.as_deref(), Some("hello there"));
+/// assert!(uci_client::parse_info("bestmove e2e4").is_none());
+///```
+pub fn parse_info(line: &str) -> Option<EngineInfo> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.first() != Some(&"info") {
+        return None;
+    }
+    let mut info = EngineInfo::default();
+    let number = |index: usize| tokens.get(index + 1).and_then(|value| value.parse::<u64>().ok());
+    let mut index = 1;
+    while index < tokens.len() {
+        match tokens[index] {
+            "depth" => info.depth = number(index).map(|depth| depth as u32),
+            "seldepth" => info.seldepth = number(index).map(|depth| depth as u32),
+            "multipv" => info.multipv = number(index).map(|line| line as u32),
+            "nodes" => info.nodes = number(index),
+            "nps" => info.nps = number(index),
+            "time" => info.time_ms = number(index),
+            "score" => {
+                let value = tokens.get(index + 2).and_then(|value| value.parse::<i32>().ok());
+                info.score = match (tokens.get(index + 1), value) {
+                    (Some(&"cp"), Some(centipawns)) => Some(Score::Centipawns(centipawns)),
+                    (Some(&"mate"), Some(moves)) => Some(Score::Mate(moves)),
+                    _ => None,
+                };
+                index += 3;
+                // An optional bound follows the score
+                if matches!(tokens.get(index), Some(&"lowerbound") | Some(&"upperbound")) {
+                    index += 1;
+                }
+                continue;
+            }
+            // Both run to the end of the line
+            "pv" => {
+                info.pv = tokens[index + 1..].iter().map(|played| played.to_string()).collect();
+                break;
+            }
+            "string" => {
+                info.string = Some(tokens[index + 1..].join(" "));
+                break;
+            }
+            _ => {
+                index += 1;
+                continue;
+            }
+        }
+        index += 2;
+    }
+    Some(info)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BestMove {
+    pub best_move: String,
+    pub ponder: Option<String>,
+}
+
+///```
+/// let best = uci_client::parse_best_move("bestmove g1f3 ponder g8f6").unwrap();
+/// assert_eq!(best.best_move, "g1f3");
+/// assert_eq!(best.ponder.as_deref(), Some("g8f6"));
+///```
+pub fn parse_best_move(line: &str) -> Option<BestMove> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("bestmove") {
+        return None;
+    }
+    let best_move = tokens.next()?.to_string();
+    let ponder = match (tokens.next(), tokens.next()) {
+        (Some("ponder"), Some(ponder)) => Some(ponder.to_string()),
+        _ => None,
+    };
+    Some(BestMove { best_move, ponder })
+}
+
+/// The `position` command for a game, built from the position it was set up from and the moves played since.
+///```
+/// use chess_logic::*;
+/// let mut game = init_game();
+/// for notation in ["e2e4", "c7c5"].iter() {
+///     let played = move_from_uci(&game, notation).unwrap();
+///     make_move(&mut game, played);
+/// }
+/// assert_eq!(uci_client::position_command(&game), "position startpos moves e2e4 c7c5");
+/// let endgame = init_game_from_fen("8/8/8/8/8/8/8/k6K w - - 0 1").unwrap();
+/// assert_eq!(uci_client::position_command(&endgame), "position fen 8/8/8/8/8/8/8/k6K w - - 0 1");
+///```
+pub fn position_command(game: &GAME) -> String {
+    let start_fen = game_to_fen(&starting_position(game));
+    let mut command = if start_fen == STARTPOS { "position startpos".to_string() } else { format!("position fen {}", start_fen) };
+    let moves = move_history(game);
+    if !moves.is_empty() {
+        command.push_str(" moves");
+        for played in moves {
+            command.push(' ');
+            command.push_str(&played.to_uci());
+        }
+    }
+    command
+}
+
+/// The `go` command for search limits, `go infinite` when there are none.
+///```
+/// use chess_logic::SearchLimits;
+/// use std::time::Duration;
+/// let limits = SearchLimits { white_time: Some(Duration::from_secs(60)), black_time: Some(Duration::from_secs(50)), depth: Some(10), ..Default::default() };
+/// assert_eq!(uci_client::go_command(&limits), "go depth 10 wtime 60000 btime 50000");
+/// assert_eq!(uci_client::go_command(&SearchLimits::default()), "go infinite");
+///```
+pub fn go_command(limits: &SearchLimits) -> String {
+    let mut command = "go".to_string();
+    let mut add = |name: &str, value: Option<u128>| {
+        if let Some(value) = value {
+            command.push_str(&format!(" {} {}", name, value));
+        }
+    };
+    add("depth", limits.depth.map(u128::from));
+    add("nodes", limits.nodes.map(u128::from));
+    add("mate", limits.mate.map(u128::from));
+    add("movetime", limits.move_time.map(|time| time.as_millis()));
+    add("wtime", limits.white_time.map(|time| time.as_millis()));
+    add("btime", limits.black_time.map(|time| time.as_millis()));
+    add("winc", limits.white_increment.map(|time| time.as_millis()));
+    add("binc", limits.black_increment.map(|time| time.as_millis()));
+    add("movestogo", limits.moves_to_go.map(u128::from));
+    if command == "go" {
+        command.push_str(" infinite");
+    }
+    command
+}
+
+/// What the engine said during one search.
+#[derive(Clone, Debug)]
+pub struct SearchOutcome {
+    pub best_move: BestMove,
+    pub infos: Vec<EngineInfo>,
+}
+
+/// A UCI engine running as a child process. Its output is read on a separate
+/// thread, so every wait has a timeout and a dead engine is noticed instead of hanging.
+pub struct UciClient {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    pub name: String,
+    pub author: String,
+    pub options: Vec<String>,                                   // the option lines sent during the handshake
+}
+
+impl UciClient {
+    /// Starts the engine and completes the `uci`/`uciok` handshake.
+    pub fn spawn(program: &str, arguments: &[&str], timeout: Duration) -> Result<UciClient, ClientError> {
+        let mut child = Command::new(program)
+            .args(arguments)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| ClientError::Spawn(format!("{}: {}", program, error)))?;
+        let stdin = child.stdin.take().expect("stdin was piped");
+        let stdout = child.stdout.take().expect("stdout was piped");
+
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut client = UciClient { child, stdin, lines, name: String::new(), author: String::new(), options: Vec::new() };
+        client.send("uci")?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let line = client.read_line(deadline, "waiting for uciok")?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                client.name = name.trim().to_string();
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                client.author = author.trim().to_string();
+            } else if line.starts_with("option ") {
+                client.options.push(line);
+            } else if line.trim() == "uciok" {
+                return Ok(client);
+            }
+        }
+    }
+
+    pub fn send(&mut self, command: &str) -> Result<(), ClientError> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| self.crashed())
+    }
+
+    fn crashed(&mut self) -> ClientError {
+        match self.child.try_wait() {
+            Ok(Some(status)) => ClientError::Crashed(format!("exited with {}", status)),
+            _ => ClientError::Crashed("closed its output".to_string()),
+        }
+    }
+
+    fn read_line(&mut self, deadline: Instant, waiting_for: &str) -> Result<String, ClientError> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(remaining) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(ClientError::Timeout(waiting_for.to_string())),
+            Err(RecvTimeoutError::Disconnected) => {
+                // Give the process a moment to be reaped so the exit status can be reported
+                thread::sleep(Duration::from_millis(10));
+                Err(self.crashed())
+            }
+        }
+    }
+
+    pub fn is_ready(&mut self, timeout: Duration) -> Result<(), ClientError> {
+        self.send("isready")?;
+        let deadline = Instant::now() + timeout;
+        while self.read_line(deadline, "waiting for readyok")?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), ClientError> {
+        self.send(&format!("setoption name {} value {}", name, value))
+    }
+
+    pub fn new_game(&mut self) -> Result<(), ClientError> {
+        self.send("ucinewgame")
+    }
+
+    pub fn set_position(&mut self, game: &GAME) -> Result<(), ClientError> {
+        self.send(&position_command(game))
+    }
+
+    /// Searches the current position. When `timeout` passes first the engine is told
+    /// to stop, and only an engine that still does not answer is an error. This is
+    /// also how a search without limits is ended.
+    pub fn go(&mut self, limits: &SearchLimits, timeout: Duration) -> Result<SearchOutcome, ClientError> {
+        self.send(&go_command(limits))?;
+        self.wait_for_best_move(timeout)
+    }
+
+    /// Lets the engine think on the opponent's time: it searches the position after the move it
+    /// expects until `ponder_hit` or `stop_pondering`. The limits are the ones of its next move.
+    pub fn ponder(&mut self, game: &GAME, limits: &SearchLimits) -> Result<(), ClientError> {
+        self.set_position(game)?;
+        self.send(&go_command(limits).replacen("go", "go ponder", 1))
+    }
+
+    /// The expected move was played, the ponder search goes on as the search for the next move.
+    pub fn ponder_hit(&mut self, timeout: Duration) -> Result<SearchOutcome, ClientError> {
+        self.send("ponderhit")?;
+        self.wait_for_best_move(timeout)
+    }
+
+    /// Another move was played, the ponder search is stopped and its answer dropped.
+    pub fn stop_pondering(&mut self, timeout: Duration) -> Result<(), ClientError> {
+        self.send("stop")?;
+        let deadline = Instant::now() + timeout;
+        while !self.read_line(deadline, "waiting for bestmove")?.starts_with("bestmove") {}
+        Ok(())
+    }
+
+    fn wait_for_best_move(&mut self, timeout: Duration) -> Result<SearchOutcome, ClientError> {
+        let mut infos = Vec::new();
+        let mut deadline = Instant::now() + timeout;
+        let mut stopped = false;
+        loop {
+            let line = match self.read_line(deadline, "waiting for bestmove") {
+                Ok(line) => line,
+                Err(ClientError::Timeout(_)) if !stopped => {
+                    self.send("stop")?;
+                    stopped = true;
+                    deadline = Instant::now() + STOP_GRACE;
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+            if let Some(info) = parse_info(&line) {
+                infos.push(info);
+            } else if line.starts_with("bestmove") {
+                return match parse_best_move(&line) {
+                    Some(best_move) => Ok(SearchOutcome { best_move, infos }),
+                    None => Err(ClientError::Protocol(format!("malformed bestmove: {}", line))),
+                };
+            }
+        }
+    }
+
+    /// Asks for a move in a game and checks that it is legal there.
+    pub fn best_move(&mut self, game: &GAME, limits: &SearchLimits, timeout: Duration) -> Result<Move, ClientError> {
+        self.set_position(game)?;
+        let outcome = self.go(limits, timeout)?;
+        move_from_uci(game, &outcome.best_move.best_move)
+            .ok_or_else(|| ClientError::Protocol(format!("illegal best move {}", outcome.best_move.best_move)))
+    }
+
+    /// Asks the engine to exit and kills it if it does not.
+    pub fn quit(mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + QUIT_GRACE;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
+
+impl Drop for UciClient {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
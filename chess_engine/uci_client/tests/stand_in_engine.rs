@@ -0,0 +1,121 @@
+use chess_logic::*;
+use uci_client::{ClientError, UciClient};
+
+use std::time::{Duration, Instant};
+
+const ENGINE: &str = env!("CARGO_BIN_EXE_stand_in_engine");
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn depth_one() -> SearchLimits {
+    SearchLimits { depth: Some(1), ..Default::default() }
+}
+
+#[test]
+fn handshake_reads_identity_and_options() {
+    let mut client = UciClient::spawn(ENGINE, &[], TIMEOUT).unwrap();
+    assert_eq!(client.name, "Stand-in");
+    assert_eq!(client.author, "nobody");
+    assert_eq!(client.options.len(), 1);
+    client.is_ready(TIMEOUT).unwrap();
+    client.quit();
+}
+
+#[test]
+fn plays_a_legal_move_in_the_current_game() {
+    let mut client = UciClient::spawn(ENGINE, &[], TIMEOUT).unwrap();
+    let mut game = init_game();
+    for _ in 0..6 {
+        let played = client.best_move(&game, &depth_one(), TIMEOUT).unwrap();
+        assert!(make_move(&mut game, played));
+    }
+    assert_eq!(move_history(&game).len(), 6);
+}
+
+#[test]
+fn collects_info_lines() {
+    let mut client = UciClient::spawn(ENGINE, &[], TIMEOUT).unwrap();
+    client.set_position(&init_game()).unwrap();
+    let outcome = client.go(&depth_one(), TIMEOUT).unwrap();
+    assert_eq!(outcome.infos.len(), 2);
+    assert_eq!(outcome.infos[0].score, Some(Score::Centipawns(13)));
+    assert_eq!(outcome.infos[1].string.as_deref(), Some("thinking hard"));
+    assert_eq!(outcome.infos[0].pv, vec![outcome.best_move.best_move.clone()]);
+}
+
+#[test]
+fn infinite_search_is_stopped_at_the_timeout() {
+    let mut client = UciClient::spawn(ENGINE, &[], TIMEOUT).unwrap();
+    client.set_position(&init_game()).unwrap();
+    let start = Instant::now();
+    let outcome = client.go(&SearchLimits::default(), Duration::from_millis(100)).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert!(!outcome.best_move.best_move.is_empty());
+}
+
+#[test]
+fn ponder_search_answers_on_ponderhit_or_stop() {
+    let mut client = UciClient::spawn(ENGINE, &[], TIMEOUT).unwrap();
+    let mut game = init_game();
+    let expected = move_from_uci(&game, "e2e4").unwrap();
+    make_move(&mut game, expected);
+    client.ponder(&game, &depth_one()).unwrap();
+    let outcome = client.ponder_hit(TIMEOUT).unwrap();
+    assert!(move_from_uci(&game, &outcome.best_move.best_move).is_some());
+
+    client.ponder(&game, &depth_one()).unwrap();
+    client.stop_pondering(TIMEOUT).unwrap();
+    client.is_ready(TIMEOUT).unwrap();
+}
+
+#[test]
+fn unresponsive_engine_times_out() {
+    let mut client = UciClient::spawn(ENGINE, &["ignore-stop"], TIMEOUT).unwrap();
+    client.set_position(&init_game()).unwrap();
+    match client.go(&depth_one(), Duration::from_millis(50)) {
+        Err(ClientError::Timeout(_)) => {}
+        other => panic!("expected a timeout, got {:?}", other.map(|outcome| outcome.best_move)),
+    }
+}
+
+#[test]
+fn handshake_times_out() {
+    match UciClient::spawn(ENGINE, &["no-uciok"], Duration::from_millis(100)) {
+        Err(ClientError::Timeout(_)) => {}
+        other => panic!("expected a timeout, got {:?}", other.map(|client| client.name.clone())),
+    }
+}
+
+#[test]
+fn crash_is_reported() {
+    let mut client = UciClient::spawn(ENGINE, &["crash-on-go"], TIMEOUT).unwrap();
+    client.set_position(&init_game()).unwrap();
+    match client.go(&depth_one(), TIMEOUT) {
+        Err(ClientError::Crashed(message)) => assert!(message.contains('3'), "{}", message),
+        other => panic!("expected a crash, got {:?}", other.map(|outcome| outcome.best_move)),
+    }
+}
+
+#[test]
+fn illegal_best_move_is_a_protocol_error() {
+    let mut client = UciClient::spawn(ENGINE, &["illegal-move"], TIMEOUT).unwrap();
+    match client.best_move(&init_game(), &depth_one(), TIMEOUT) {
+        Err(ClientError::Protocol(_)) => {}
+        other => panic!("expected a protocol error, got {:?}", other),
+    }
+}
+
+#[test]
+fn missing_program_fails_to_spawn() {
+    assert!(matches!(UciClient::spawn("/nonexistent/engine", &[], TIMEOUT), Err(ClientError::Spawn(_))));
+}
+
+#[test]
+fn position_after_castling_reaches_the_engine() {
+    // The engine rebuilds the game from the position command, so its reply must be legal in our game
+    let mut game = init_game_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    let played = move_from_uci(&game, "e1g1").unwrap();
+    make_move(&mut game, played);
+    let mut client = UciClient::spawn(ENGINE, &[], TIMEOUT).unwrap();
+    let reply = client.best_move(&game, &depth_one(), TIMEOUT).unwrap();
+    assert!(generate_legal_moves(&game).contains(&reply));
+}
@@ -1,11 +1,20 @@
 use ggez::{Context, ContextBuilder, GameResult};
 use ggez::graphics::{self, Color, Rect};
-use ggez::event::{self, EventHandler, MouseButton};
+use ggez::event::{self, EventHandler, KeyCode, KeyMods, MouseButton};
 use glam::*;
 
 const SCREEN_WIDTH: f32 = 800.0;
 const SCREEN_HEIGHT: f32 = 800.0;
-use chess_logic;
+const ENGINE_MOVE_TIME_MS: u64 = 1000;
+const ENGINE_TIMEOUT_MS: u64 = 5000;
+use chess_logic::{self, Skill};
+use uci_client::{ClientError, SearchOutcome, UciClient};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// What the engine thread hands back: the engine itself and its move, or why there is none
+type EngineReply = (UciClient, Result<(chess_logic::Move, SearchOutcome), ClientError>);
 
 fn main() {
     // Make a Context.
@@ -14,12 +23,11 @@ fn main() {
         .build()
         .expect("aieee, could not create ggez context!");
 
-        ggez::graphics::set_window_title(&ctx, "Chess Graphical Interface");
-
     // Create an instance of your event handler.
     // Usually, you should provide it with the Context object to
     // use when setting your game up.
     let my_game = MyGame::new(&mut ctx).unwrap();
+    my_game.show_difficulty(&ctx);
 
     // Run!
     event::run(ctx, event_loop, my_game);
@@ -41,7 +49,70 @@ struct MyGame {
     black_square: graphics::Image,
     white_square: graphics::Image,
     mouse_button_press_down: Option<ggez::mint::Point2<f32>>,
-    game: chess_logic::GAME
+    game: chess_logic::GAME,
+    engine: Option<UciClient>,                                  // external UCI engine playing black, from CHESS_ENGINE
+    book: Option<chess_logic::OpeningBook>,                     // Polyglot book the engine plays from first, from CHESS_BOOK
+    skill_level: u8,                                            // engine difficulty from 0 to 20, from CHESS_SKILL, changed with the up and down keys
+    ponder_position: Option<String>,                            // what the engine thinks about during white's turn, as FEN
+    engine_reply: Option<Receiver<EngineReply>>,                // while the engine thinks about its move on its own thread
+    show_hints: bool,                                           // tactics overlay, switched with the h key
+}
+
+// Starts the engine named by the CHESS_ENGINE environment variable, if there is one
+pub fn start_external_engine() -> Option<UciClient> {
+    let path = std::env::var("CHESS_ENGINE").ok()?;
+    match UciClient::spawn(path.as_str(), &[], Duration::from_millis(ENGINE_TIMEOUT_MS)) {
+        Ok(mut engine) => {
+            println!("playing against {}", engine.name);
+            // Engines that can ponder think on the human's time
+            if engine.options.iter().any(|option| option.starts_with("option name Ponder ")) && engine.set_option("Ponder", "true").is_err() {
+                return None;
+            }
+            Some(engine)
+        }
+        Err(error) => {
+            println!("{}", error);
+            None
+        }
+    }
+}
+
+// Loads the opening book named by the CHESS_BOOK environment variable, if there is one
+pub fn load_opening_book() -> Option<chess_logic::OpeningBook> {
+    let path = std::env::var("CHESS_BOOK").ok()?;
+    match chess_logic::OpeningBook::open(&path) {
+        Ok(book) => {
+            println!("opening book {} with {} entries", path, book.len());
+            Some(book)
+        }
+        Err(error) => {
+            println!("{}", error);
+            None
+        }
+    }
+}
+
+// The difficulty named by the CHESS_SKILL environment variable, full strength without it
+pub fn initial_skill_level() -> u8 {
+    std::env::var("CHESS_SKILL").ok().and_then(|level| level.parse::<u8>().ok()).map_or(Skill::MAX_LEVEL, |level| level.min(Skill::MAX_LEVEL))
+}
+
+fn engine_move_limits() -> chess_logic::SearchLimits {
+    chess_logic::SearchLimits { move_time: Some(Duration::from_millis(ENGINE_MOVE_TIME_MS)), ..Default::default() }
+}
+
+// The engine's answer to white's last move: a ponder hit when it was the expected move,
+// otherwise a new search after the ponder search is stopped
+fn engine_answer(engine: &mut UciClient, game: &chess_logic::GAME, ponder_position: Option<String>, limits: &chess_logic::SearchLimits) -> Result<SearchOutcome, ClientError> {
+    let timeout = Duration::from_millis(ENGINE_TIMEOUT_MS);
+    if let Some(ponder_position) = ponder_position {
+        if chess_logic::game_to_fen(game) == ponder_position {
+            return engine.ponder_hit(timeout);
+        }
+        engine.stop_pondering(timeout)?;
+    }
+    engine.set_position(game)?;
+    engine.go(limits, timeout)
 }
 
 pub fn get_square_from_mouse_pos(pos: ggez::mint::Point2<f32>) -> Result<ggez::mint::Point2<u8>, String> {
@@ -74,7 +145,7 @@ impl MyGame {
 
         let game = chess_logic::init_game();
 
-        let s = MyGame {
+        let mut s = MyGame {
             black_rook,
             white_rook,
             black_queen,
@@ -91,7 +162,14 @@ impl MyGame {
             white_square,
             mouse_button_press_down: None,
             game,
+            engine: start_external_engine(),
+            book: load_opening_book(),
+            skill_level: initial_skill_level(),
+            ponder_position: None,
+            engine_reply: None,
+            show_hints: false,
         };
+        s.send_skill_level();
 
         Ok(s)
     }
@@ -129,6 +207,125 @@ impl MyGame {
         return image;
     }
 
+    // Passes the difficulty on to the engine, which plays at full strength at the top level
+    fn send_skill_level(&mut self) {
+        self.stop_pondering();
+        let level = self.skill_level.to_string();
+        if let Some(engine) = self.engine.as_mut() {
+            if let Err(error) = engine.set_option("Skill Level", &level) {
+                println!("{}", error);
+                self.engine = None;
+            }
+        }
+    }
+
+    pub fn show_difficulty(&self, ctx: &Context) {
+        if self.engine.is_none() {
+            graphics::set_window_title(ctx, "Chess Graphical Interface");
+        } else if self.skill_level == Skill::MAX_LEVEL {
+            graphics::set_window_title(ctx, "Chess Graphical Interface - full strength (up/down to change)");
+        } else {
+            let title = format!("Chess Graphical Interface - level {} of {} (up/down to change)", self.skill_level, Skill::MAX_LEVEL);
+            graphics::set_window_title(ctx, &title);
+        }
+    }
+
+    // Lets the external engine answer when it is black's turn, from the opening book while it has a move.
+    // The engine thinks on its own thread so the window keeps drawing, update() plays the move once it
+    // arrives. When the human played the move the engine was pondering on, that search becomes its answer
+    pub fn play_engine_move(&mut self) {
+        if self.game.is_whites_turn() || chess_logic::game_outcome(&self.game).is_some() {
+            return;
+        }
+        let random = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_nanos() as u64);
+        if let Some(played) = self.book.as_ref().and_then(|book| book.weighted_move(&self.game, random)) {
+            self.stop_pondering();
+            self.play_move(played);
+            return;
+        }
+        let ponder_position = self.ponder_position.take();
+        let mut engine = match self.engine.take() {
+            Some(engine) => engine,
+            None => return,
+        };
+        let game = self.game.clone();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let answer = engine_answer(&mut engine, &game, ponder_position, &engine_move_limits()).and_then(|outcome| {
+                let played = chess_logic::move_from_uci(&game, &outcome.best_move.best_move)
+                    .ok_or_else(|| ClientError::Protocol(format!("illegal best move {}", outcome.best_move.best_move)))?;
+                Ok((played, outcome))
+            });
+            let _ = sender.send((engine, answer));
+        });
+        self.engine_reply = Some(receiver);
+    }
+
+    // Plays the engine's move when its thread has answered, then lets it ponder on the reply it expects.
+    // An engine that failed to answer is dropped and the human plays on alone
+    fn receive_engine_move(&mut self) {
+        let reply = match self.engine_reply.as_ref().map(|receiver| receiver.try_recv()) {
+            None | Some(Err(TryRecvError::Empty)) => return,
+            Some(Err(TryRecvError::Disconnected)) => {
+                println!("the engine thread ended without an answer");
+                self.engine_reply = None;
+                return;
+            }
+            Some(Ok(reply)) => reply,
+        };
+        self.engine_reply = None;
+        match reply {
+            (engine, Ok((played, outcome))) => {
+                self.engine = Some(engine);
+                self.play_move(played);
+                self.start_pondering(outcome.best_move.ponder, &engine_move_limits());
+            }
+            (_, Err(error)) => println!("{}", error),
+        }
+    }
+
+    // Starts the engine on the position after the reply it expects, it keeps searching until white moves
+    fn start_pondering(&mut self, expected: Option<String>, limits: &chess_logic::SearchLimits) {
+        if chess_logic::game_outcome(&self.game).is_some() {
+            return;
+        }
+        let expected = match expected.and_then(|notation| chess_logic::move_from_uci(&self.game, &notation)) {
+            Some(expected) => expected,
+            None => return,
+        };
+        let engine = match self.engine.as_mut() {
+            Some(engine) => engine,
+            None => return,
+        };
+        let mut ponder_game = self.game.clone();
+        chess_logic::make_move(&mut ponder_game, expected);
+        match engine.ponder(&ponder_game, limits) {
+            Ok(()) => self.ponder_position = Some(chess_logic::game_to_fen(&ponder_game)),
+            Err(error) => {
+                println!("{}", error);
+                self.engine = None;
+            }
+        }
+    }
+
+    fn stop_pondering(&mut self) {
+        if self.ponder_position.take().is_none() {
+            return;
+        }
+        if let Some(engine) = self.engine.as_mut() {
+            if let Err(error) = engine.stop_pondering(Duration::from_millis(ENGINE_TIMEOUT_MS)) {
+                println!("{}", error);
+                self.engine = None;
+            }
+        }
+    }
+
+    // Moves go through make_move so the game keeps its history, and the engine gets the
+    // position as the start position and every move since
+    fn play_move(&mut self, played: chess_logic::Move) {
+        chess_logic::make_move(&mut self.game, played);
+    }
+
     pub fn draw_chess_board(&mut self, board: [u8; 64], ctx: &mut Context) -> GameResult<()> {
         let mut rank  = 0;
         let mut grabbed_piece_pos: Option<ggez::mint::Point2<u8>> = None;
@@ -204,6 +401,30 @@ impl MyGame {
     }
 }
 
+// Marks the tiles of every tactic on the board: hanging pieces red, pinned and skewered pieces
+// orange, pieces that can uncover an attack blue, and the squares of forks and checks green
+pub fn draw_hints(ctx: &mut Context, game: &chess_logic::GAME) -> GameResult<()> {
+    let tactics = chess_logic::find_tactics(game);
+    let mut marks: Vec<(usize, Color)> = Vec::new();
+    let red = Color::new(1.0, 0.0, 0.0, 0.35);
+    let orange = Color::new(1.0, 0.6, 0.0, 0.35);
+    let blue = Color::new(0.0, 0.4, 1.0, 0.35);
+    let green = Color::new(0.0, 0.8, 0.0, 0.35);
+    marks.extend(tactics.hanging.iter().map(|piece| (piece.tile, red)));
+    marks.extend(tactics.pins.iter().map(|pin| (pin.pinned, orange)));
+    marks.extend(tactics.skewers.iter().map(|skewer| (skewer.front, orange)));
+    marks.extend(tactics.discovered.iter().map(|discovered| (discovered.blocker, blue)));
+    marks.extend(tactics.forks.iter().map(|fork| (fork.played.to as usize, green)));
+    marks.extend(tactics.checks.iter().map(|check| (check.to as usize, green)));
+
+    let size = SCREEN_WIDTH / 8.0;
+    for (tile, color) in marks {
+        let rect = Rect::new((tile % 8) as f32 * size, (tile / 8) as f32 * size, size, size);
+        draw_rectangle(ctx, rect, color)?;
+    }
+    Ok(())
+}
+
 pub fn get_mouse_position(ctx: &mut Context) -> ggez::mint::Point2<f32> {
     ggez::input::mouse::position(ctx)
 }
@@ -223,6 +444,7 @@ pub fn draw_rectangle(ctx: &mut Context, rect: Rect, color: Color) -> GameResult
 
 impl EventHandler<ggez::GameError> for MyGame {
     fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
+        self.receive_engine_move();
         Ok(())
     }
 
@@ -244,6 +466,10 @@ impl EventHandler<ggez::GameError> for MyGame {
         x: f32,
         y: f32
     ) {
+        // The board belongs to the engine until its move arrives
+        if self.engine_reply.is_some() {
+            return;
+        }
         let mouse_up = ggez::mint::Point2{x, y};
 
         let mouse_up_board_pos = get_square_from_mouse_pos(mouse_up);
@@ -258,10 +484,48 @@ impl EventHandler<ggez::GameError> for MyGame {
         let move_notation_mouse_up = pos_array[(mouse_up_board_pos.x) as usize].to_string() + (8-mouse_up_board_pos.y).to_string().as_str();
         let move_notation_mouse_down = pos_array[(mouse_down_board_pos.x) as usize].to_string() + (8-mouse_down_board_pos.y).to_string().as_str();
 
-        let is_valid = chess_logic::move_piece_from_to(move_notation_mouse_down.as_str(), move_notation_mouse_up.as_str(), &mut self.game);
-        println!("{}, {}", move_notation_mouse_down, move_notation_mouse_up);
-        if !is_valid {
-            println!("{}", "not a valid move");
+        let notation = move_notation_mouse_down + move_notation_mouse_up.as_str();
+        println!("{}", notation);
+        // A pawn dropped on the last rank becomes a queen
+        let played = chess_logic::move_from_uci(&self.game, &notation)
+            .or_else(|| chess_logic::move_from_uci(&self.game, &format!("{}q", notation)));
+        match played {
+            Some(played) => {
+                self.play_move(played);
+                self.play_engine_move();
+            }
+            None => println!("{}", "not a valid move"),
+        }
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
+        let level = match keycode {
+            KeyCode::Up | KeyCode::Down if self.engine_reply.is_some() => {
+                println!("the difficulty can change once the engine has moved");
+                return;
+            }
+            KeyCode::Up => (self.skill_level + 1).min(Skill::MAX_LEVEL),
+            KeyCode::Down => self.skill_level.saturating_sub(1),
+            KeyCode::Escape => {
+                event::quit(ctx);
+                return;
+            }
+            KeyCode::H => {
+                self.show_hints = !self.show_hints;
+                if self.show_hints {
+                    let tactics = chess_logic::find_tactics(&self.game);
+                    for line in tactics.to_lines(&self.game) {
+                        println!("{}", line);
+                    }
+                }
+                return;
+            }
+            _ => return,
+        };
+        if level != self.skill_level {
+            self.skill_level = level;
+            self.send_skill_level();
+            self.show_difficulty(ctx);
         }
     }
 
@@ -271,6 +535,9 @@ impl EventHandler<ggez::GameError> for MyGame {
 
         let board = self.game.get_board();
         self.draw_chess_board(board, ctx)?;
+        if self.show_hints {
+            draw_hints(ctx, &self.game)?;
+        }
 
         graphics::present(ctx)
     }